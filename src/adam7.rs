@@ -1,6 +1,6 @@
 //! Utility functions related to handling of
 //! [the Adam7 algorithm](https://en.wikipedia.org/wiki/Adam7_algorithm).
-use core::ops::RangeTo;
+use core::ops::{Range, RangeTo};
 
 #[cfg(doc)]
 use crate::decoder::Reader;
@@ -49,7 +49,7 @@ impl Adam7Info {
     /// nevertheless exposed as a public API, because it helps to provide self-contained example
     /// usage of [`expand_interlaced_row`](crate::expand_interlaced_row).
     pub fn new(pass: u8, line: u32, width: u32) -> Self {
-        assert!(1 <= pass && pass <= 7);
+        assert!((1..=7).contains(&pass));
         assert!(width > 0);
 
         let info = PassConstants::PASSES[pass as usize - 1];
@@ -67,18 +67,61 @@ impl Adam7Info {
         PassConstants::PASSES[self.pass as usize - 1]
     }
 
+    /// The position of the sample `idx` of this line in the full image, as `(x, y)`.
+    pub(crate) fn image_position(&self, idx: u32) -> (u32, u32) {
+        let pass = self.pass_constants();
+        (
+            idx * u32::from(pass.x_sampling) + u32::from(pass.x_offset),
+            self.line * u32::from(pass.y_sampling) + u32::from(pass.y_offset),
+        )
+    }
+
+    /// The indices of the samples of this line whose `x` position lies within `columns`.
+    pub(crate) fn samples_within(&self, columns: Range<u32>) -> Range<u32> {
+        let pass = self.pass_constants();
+        let index = |x: u32| {
+            x.saturating_sub(u32::from(pass.x_offset))
+                .div_ceil(u32::from(pass.x_sampling))
+                .min(self.samples)
+        };
+        index(columns.start)..index(columns.end)
+    }
+
     /// How often to repeat a pixel.
-    fn splat_pixel_repeat(self, idx: usize) -> u8 {
+    pub(crate) fn splat_pixel_repeat(self, idx: usize) -> u8 {
         let pass = self.pass_constants();
         let x_pixel = idx as u32 * u32::from(pass.x_sampling) + u32::from(pass.x_offset);
         (self.width - x_pixel).min(pass.splat_x_repeat().into()) as u8
     }
 
-    fn splat_line_repeat(self, height: u32) -> u8 {
+    pub(crate) fn splat_line_repeat(self, height: u32) -> u8 {
         let pass = self.pass_constants();
         let y_line = self.line * u32::from(pass.y_sampling) + u32::from(pass.y_offset);
         (height - y_line).min(pass.splat_y_repeat().into()) as u8
     }
+
+    /// The index of this line among the lines of all passes, i.e. the rows of the image data.
+    pub(crate) fn row_index(&self, height: u32) -> u32 {
+        count_lines(
+            &PassConstants::PASSES[..usize::from(self.pass) - 1],
+            self.width,
+            height,
+        ) + self.line
+    }
+}
+
+/// The number of lines of all passes of an image, i.e. the number of rows of its image data.
+pub(crate) fn count_interlaced_rows(width: u32, height: u32) -> u32 {
+    count_lines(&PassConstants::PASSES, width, height)
+}
+
+/// Counts the lines of `passes`, skipping passes without samples.
+fn count_lines(passes: &[PassConstants], width: u32, height: u32) -> u32 {
+    passes
+        .iter()
+        .filter(|pass| pass.count_samples(width) > 0)
+        .map(|pass| pass.count_lines(height))
+        .sum()
 }
 
 #[derive(Clone, Copy)]
@@ -216,6 +259,9 @@ impl Iterator for Adam7Iterator {
 /// that the exact image is recovered after all passes are applied. However the intermediate states
 /// of the output are left to the implementation, as long as it follows the restriction of
 /// resulting in the intended image when all passes have been applied.
+///
+/// The [`Reader`] chooses the variant according to its
+/// [`InterlaceHandling`](crate::InterlaceHandling).
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum Adam7Variant {
@@ -411,6 +457,44 @@ pub fn expand_pass(
     }
 }
 
+/// Copies the pixels of `img` that belong to the interlaced row described by `interlace_info`
+/// into `interlaced_row`.
+///
+/// This is the inverse of [`expand_pass`] and is used when encoding Adam7 interlaced images.
+/// `interlaced_row` must be zero-initialized when `bits_per_pixel` is less than 8, as sub-byte
+/// pixels are or-ed into place.
+pub(crate) fn subsample_pass(
+    interlaced_row: &mut [u8],
+    img: &[u8],
+    img_row_stride: usize,
+    interlace_info: &Adam7Info,
+    bits_per_pixel: u8,
+) {
+    match bits_per_pixel {
+        1 | 2 | 4 => {
+            let mask = (1u8 << bits_per_pixel) - 1;
+            let bit_indices = expand_adam7_bits(img_row_stride, interlace_info, bits_per_pixel);
+
+            for (idx, pos) in bit_indices.enumerate() {
+                let px = (img[pos.byte] >> (8 - bits_per_pixel - pos.bit)) & mask;
+                let out_bit = idx * usize::from(bits_per_pixel);
+                let shift = 8 - bits_per_pixel - (out_bit % 8) as u8;
+                interlaced_row[out_bit / 8] |= px << shift;
+            }
+        }
+        _ => {
+            debug_assert!(bits_per_pixel % 8 == 0);
+            let bytes_pp = bits_per_pixel / 8;
+            let byte_indices = expand_adam7_bytes(img_row_stride, interlace_info, bytes_pp);
+
+            for (bytepos, px) in byte_indices.zip(interlaced_row.chunks_exact_mut(bytes_pp.into()))
+            {
+                px.copy_from_slice(&img[bytepos..][..px.len()]);
+            }
+        }
+    }
+}
+
 /// Expand pass, but also ensure that after each pass the whole image has been initialized up to
 /// the data available. In constrast to `expand_pass` there are no holes left in the image.
 ///
@@ -581,6 +665,17 @@ pub fn expand_pass_splat(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_samples_within() {
+        // Pass 2 samples the columns 4, 12 and 20 of a row with 21 pixels.
+        let info = Adam7Info::new(2, 0, 21);
+        assert_eq!(info.samples_within(0..21), 0..3);
+        assert_eq!(info.samples_within(5..12), 1..1);
+        assert_eq!(info.samples_within(4..13), 0..2);
+        assert_eq!(info.samples_within(13..21), 2..3);
+        assert_eq!(info.samples_within(0..4), 0..0);
+    }
+
     #[test]
     fn test_adam7() {
         /*
@@ -937,6 +1032,37 @@ mod tests {
         }
     }
 
+    /// Check that splitting an image into its passes and expanding them again is lossless.
+    #[test]
+    fn subsample_expand_roundtrip() {
+        // Ragged sizes so that some passes are partial or empty.
+        const WIDTH: u32 = 11;
+        const HEIGHT: u32 = 5;
+
+        for &bpp in &[1u8, 2, 4, 8, 16, 24, 32, 48, 64] {
+            let bytes_of = |pix: u32| (u32::from(bpp) * pix).next_multiple_of(8) as usize / 8;
+            let rowbytes = bytes_of(WIDTH);
+
+            let mut img: Vec<u8> = (0..rowbytes * HEIGHT as usize)
+                .map(|i| (i * 37 + 11) as u8)
+                .collect();
+            // Clear the padding bits at the end of each row, they are not part of any pass.
+            let padding = rowbytes * 8 - bpp as usize * WIDTH as usize;
+            for row in img.chunks_exact_mut(rowbytes) {
+                *row.last_mut().unwrap() &= !((1u16 << padding) - 1) as u8;
+            }
+
+            let mut expanded = vec![0; img.len()];
+            for adam7_info in Adam7Iterator::new(WIDTH, HEIGHT) {
+                let mut interlaced_row = vec![0; bytes_of(adam7_info.samples)];
+                subsample_pass(&mut interlaced_row, &img, rowbytes, &adam7_info, bpp);
+                expand_pass(&mut expanded, rowbytes, &interlaced_row, &adam7_info, bpp);
+            }
+
+            assert_eq!(img, expanded, "bpp={bpp}");
+        }
+    }
+
     #[test]
     fn test_expand_pass_splat_1bpp() {
         let width = 8;
@@ -1009,6 +1135,17 @@ mod tests {
         assert_eq!(*expanded.last().unwrap(), 0xff);
     }
 
+    #[test]
+    fn test_row_index() {
+        for (width, height) in [(1, 1), (2, 1), (5, 3), (8, 8), (13, 37)] {
+            let infos: Vec<_> = Adam7Iterator::new(width, height).collect();
+            assert_eq!(count_interlaced_rows(width, height) as usize, infos.len());
+            for (idx, info) in infos.iter().enumerate() {
+                assert_eq!(info.row_index(height) as usize, idx);
+            }
+        }
+    }
+
     #[cfg(test)]
     fn create_adam7_info_for_tests(pass: u8, line: u32, img_width: usize) -> Adam7Info {
         let width = {
@@ -51,6 +51,10 @@ pub const zTXt: ChunkType = ChunkType(*b"zTXt");
 pub const iTXt: ChunkType = ChunkType(*b"iTXt");
 // Significant bits
 pub const sBIT: ChunkType = ChunkType(*b"sBIT");
+/// Palette histogram
+pub const hIST: ChunkType = ChunkType(*b"hIST");
+/// Suggested palette
+pub const sPLT: ChunkType = ChunkType(*b"sPLT");
 
 // -- Extension chunks --
 
@@ -60,6 +64,18 @@ pub const acTL: ChunkType = ChunkType(*b"acTL");
 pub const fcTL: ChunkType = ChunkType(*b"fcTL");
 /// Frame data
 pub const fdAT: ChunkType = ChunkType(*b"fdAT");
+/// Image offset
+pub const oFFs: ChunkType = ChunkType(*b"oFFs");
+/// Calibration of pixel values
+pub const pCAL: ChunkType = ChunkType(*b"pCAL");
+/// Physical scale of image subject
+pub const sCAL: ChunkType = ChunkType(*b"sCAL");
+/// Indicator of stereo image
+pub const sTER: ChunkType = ChunkType(*b"sTER");
+/// GIF Graphic Control Extension
+pub const gIFg: ChunkType = ChunkType(*b"gIFg");
+/// GIF Application Extension
+pub const gIFx: ChunkType = ChunkType(*b"gIFx");
 
 // -- Chunk type determination --
 
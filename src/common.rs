@@ -4,6 +4,7 @@ use crate::text_metadata::{ITXtChunk, TEXtChunk, ZTXtChunk};
 use crate::Filter;
 use crate::{chunk, encoder};
 use io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{borrow::Cow, convert::TryFrom, fmt, io};
 
 /// Describes how a pixel is encoded.
@@ -53,7 +54,7 @@ impl ColorType {
     pub(crate) fn checked_raw_row_length(self, depth: BitDepth, width: u32) -> Option<usize> {
         // No overflow can occur in 64 bits, we multiply 32-bit with 5 more bits.
         let bits = u64::from(width) * u64::from(self.samples_u8()) * u64::from(depth.into_u8());
-        TryFrom::try_from(1 + (bits + 7) / 8).ok()
+        TryFrom::try_from(1 + bits.div_ceil(8)).ok()
     }
 
     pub(crate) fn raw_row_length_from_width(self, depth: BitDepth, width: u32) -> usize {
@@ -419,6 +420,350 @@ impl DeflateCompression {
     }
 }
 
+/// The time of the last modification of the image, from the `tIME` chunk, in UTC.
+///
+/// See https://www.w3.org/TR/png-3/#11tIME for more details.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Time {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+}
+
+impl Time {
+    /// Creates a time from its calendar date and time of day.
+    ///
+    /// Returns `None` if the date does not exist, or if the time of day is out of range. The
+    /// second may be 60 to allow for leap seconds.
+    pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Option<Self> {
+        let leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let days_in_month = match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if leap_year => 29,
+            2 => 28,
+            _ => return None,
+        };
+        let valid = (1..=days_in_month).contains(&day) && hour < 24 && minute < 60 && second <= 60;
+        valid.then_some(Time {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        })
+    }
+
+    /// The year, e.g. 1995.
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    /// The month, from 1 to 12.
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    /// The day of the month, from 1 to 31.
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    /// The hour, from 0 to 23.
+    pub fn hour(&self) -> u8 {
+        self.hour
+    }
+
+    /// The minute, from 0 to 59.
+    pub fn minute(&self) -> u8 {
+        self.minute
+    }
+
+    /// The second, from 0 to 60 for leap seconds.
+    pub fn second(&self) -> u8 {
+        self.second
+    }
+
+    pub(crate) fn to_be_bytes(self) -> [u8; 7] {
+        let [year_hi, year_lo] = self.year.to_be_bytes();
+        [
+            year_hi,
+            year_lo,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+        ]
+    }
+
+    pub(crate) fn encode<W: Write>(self, w: &mut W) -> encoder::Result<()> {
+        encoder::write_chunk(w, chunk::tIME, &self.to_be_bytes())
+    }
+}
+
+impl From<SystemTime> for Time {
+    /// Converts a system time to UTC, rounded down to the second. Times outside of the years 0 to
+    /// 65535 are clamped to the first or last second of that range.
+    fn from(time: SystemTime) -> Self {
+        let secs = match time.duration_since(UNIX_EPOCH) {
+            Ok(after) => i64::try_from(after.as_secs()).unwrap_or(i64::MAX),
+            Err(before) => {
+                let before = before.duration();
+                let secs = i64::try_from(before.as_secs()).unwrap_or(i64::MAX);
+                -secs - i64::from(before.subsec_nanos() > 0)
+            }
+        };
+        // Enough days to be outside of the representable years in both directions.
+        let days = secs.div_euclid(86_400).clamp(-1_000_000, 25_000_000);
+        let secs = secs.rem_euclid(86_400) as u32;
+
+        // The civil date of the days since 1970-01-01 in the proleptic Gregorian calendar, with
+        // years starting in March so that leap days are at the end of the year.
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_from_march = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+        let month = if month_from_march < 10 {
+            month_from_march + 3
+        } else {
+            month_from_march - 9
+        };
+        let year = 400 * era + year_of_era + i64::from(month <= 2);
+
+        match u16::try_from(year) {
+            Ok(year) => Time {
+                year,
+                month: month as u8,
+                day: day as u8,
+                hour: (secs / 3600) as u8,
+                minute: (secs / 60 % 60) as u8,
+                second: (secs % 60) as u8,
+            },
+            Err(_) if year < 0 => Time::new(0, 1, 1, 0, 0, 0).unwrap(),
+            Err(_) => Time::new(u16::MAX, 12, 31, 23, 59, 59).unwrap(),
+        }
+    }
+}
+
+/// A palette suggested for displays with a limited number of colors, from an `sPLT` chunk.
+///
+/// See https://www.w3.org/TR/png-3/#11sPLT for more details.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SuggestedPalette {
+    /// The name of the palette, 1 to 79 Latin-1 characters that are unique within the image.
+    pub name: String,
+    /// The depth of the samples of the entries, either 8 or 16 bits.
+    pub sample_depth: BitDepth,
+    /// The colors of the palette, independent of the `PLTE` chunk.
+    pub entries: Vec<SuggestedPaletteEntry>,
+}
+
+/// A color of a [`SuggestedPalette`]. The samples are not premultiplied with alpha.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SuggestedPaletteEntry {
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+    pub alpha: u16,
+    /// How often the color occurs in the image, relative to the other entries.
+    pub frequency: u16,
+}
+
+/// The position of the image on a page or screen, from an `oFFs` chunk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageOffset {
+    /// The position of the left edge of the image, to the right of the left edge of the page.
+    pub x: i32,
+    /// The position of the top edge of the image, below the top edge of the page.
+    pub y: i32,
+    /// The unit of both positions.
+    pub unit: OffsetUnit,
+}
+
+impl ImageOffset {
+    pub(crate) fn encode<W: Write>(self, w: &mut W) -> encoder::Result<()> {
+        let mut data = [0; 9];
+        data[..4].copy_from_slice(&self.x.to_be_bytes());
+        data[4..8].copy_from_slice(&self.y.to_be_bytes());
+        data[8] = self.unit as u8;
+        encoder::write_chunk(w, chunk::oFFs, &data)
+    }
+}
+
+/// The unit of an [`ImageOffset`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum OffsetUnit {
+    Pixel = 0,
+    Micrometer = 1,
+}
+
+impl OffsetUnit {
+    /// u8 -> Self.
+    pub fn from_u8(n: u8) -> Option<OffsetUnit> {
+        match n {
+            0 => Some(OffsetUnit::Pixel),
+            1 => Some(OffsetUnit::Micrometer),
+            _ => None,
+        }
+    }
+}
+
+/// The mapping of the samples of the image to physical values, from a `pCAL` chunk.
+///
+/// A sample `x` of the image is first mapped linearly onto the original range, i.e.
+/// `original = x0 + x * (x1 - x0) / max` where `max` is the largest sample of the bit depth,
+/// and then turned into a physical value by the `equation` with the `parameters`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PixelCalibration {
+    /// The name of the calibration, 1 to 79 Latin-1 characters.
+    pub name: String,
+    /// The original value of the smallest sample.
+    pub x0: i32,
+    /// The original value of the largest sample, different from `x0`.
+    pub x1: i32,
+    /// The equation that maps the original values to physical values.
+    pub equation: CalibrationEquation,
+    /// The Latin-1 name of the unit of the physical values, possibly empty.
+    pub unit: String,
+    /// The parameters of the equation, as many as
+    /// [`CalibrationEquation::parameter_count`].
+    pub parameters: Vec<f64>,
+}
+
+/// The equation of a [`PixelCalibration`], with parameters `p0`, `p1` and so on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CalibrationEquation {
+    /// `p0 + p1 * original / (x1 - x0)`
+    Linear = 0,
+    /// `p0 + p1 * exp(p2 * original / (x1 - x0))`
+    BaseE = 1,
+    /// `p0 + p1 * pow(p2, original / (x1 - x0))`
+    ArbitraryBase = 2,
+    /// `p0 + p1 * sinh(p2 * (original - p3) / (x1 - x0))`
+    Hyperbolic = 3,
+}
+
+impl CalibrationEquation {
+    /// u8 -> Self.
+    pub fn from_u8(n: u8) -> Option<CalibrationEquation> {
+        match n {
+            0 => Some(CalibrationEquation::Linear),
+            1 => Some(CalibrationEquation::BaseE),
+            2 => Some(CalibrationEquation::ArbitraryBase),
+            3 => Some(CalibrationEquation::Hyperbolic),
+            _ => None,
+        }
+    }
+
+    /// The number of parameters of the equation.
+    pub fn parameter_count(self) -> usize {
+        match self {
+            CalibrationEquation::Linear => 2,
+            CalibrationEquation::BaseE | CalibrationEquation::ArbitraryBase => 3,
+            CalibrationEquation::Hyperbolic => 4,
+        }
+    }
+}
+
+/// The physical size of a pixel of the image subject, from an `sCAL` chunk.
+///
+/// Unlike the `pHYs` chunk, which describes the output device, this is the size of what the image
+/// shows, e.g. in a map or a photograph of the sky.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PhysicalScale {
+    /// The unit of the width and height.
+    pub unit: ScaleUnit,
+    /// The width of a pixel, a positive number.
+    pub width: f64,
+    /// The height of a pixel, a positive number.
+    pub height: f64,
+}
+
+/// The unit of a [`PhysicalScale`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ScaleUnit {
+    Meter = 1,
+    Radian = 2,
+}
+
+impl ScaleUnit {
+    /// u8 -> Self.
+    pub fn from_u8(n: u8) -> Option<ScaleUnit> {
+        match n {
+            1 => Some(ScaleUnit::Meter),
+            2 => Some(ScaleUnit::Radian),
+            _ => None,
+        }
+    }
+}
+
+/// The layout of a stereo image, from an `sTER` chunk.
+///
+/// The image consists of a left and a right half of equal width, possibly separated by up to
+/// seven columns of padding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum StereoLayout {
+    /// The right-eye image is on the left, for viewing cross-eyed.
+    CrossFuse = 0,
+    /// The left-eye image is on the left, for viewing wall-eyed.
+    DivergingFuse = 1,
+}
+
+impl StereoLayout {
+    /// u8 -> Self.
+    pub fn from_u8(n: u8) -> Option<StereoLayout> {
+        match n {
+            0 => Some(StereoLayout::CrossFuse),
+            1 => Some(StereoLayout::DivergingFuse),
+            _ => None,
+        }
+    }
+}
+
+/// A Graphic Control Extension of a GIF image the PNG image was converted from, from a `gIFg`
+/// chunk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GifGraphicControl {
+    /// The GIF disposal method.
+    pub disposal_method: u8,
+    /// Whether the viewer should wait for user input before continuing.
+    pub user_input: bool,
+    /// The delay before continuing, in hundredths of a second.
+    pub delay_time: u16,
+}
+
+impl GifGraphicControl {
+    pub(crate) fn encode<W: Write>(self, w: &mut W) -> encoder::Result<()> {
+        let mut data = [self.disposal_method, self.user_input as u8, 0, 0];
+        data[2..].copy_from_slice(&self.delay_time.to_be_bytes());
+        encoder::write_chunk(w, chunk::gIFg, &data)
+    }
+}
+
+/// An Application Extension of a GIF image the PNG image was converted from, from a `gIFx` chunk.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GifApplicationExtension {
+    /// The identifier of the application that wrote the extension.
+    pub identifier: [u8; 8],
+    /// The authentication code of the application.
+    pub authentication_code: [u8; 3],
+    /// The data of the extension, without the GIF sub-block framing.
+    pub data: Vec<u8>,
+}
+
 /// An unsigned integer scaled version of a floating point value,
 /// equivalent to an integer quotient with [fixed denominator][ScaledFloat::SCALING]).
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -595,6 +940,18 @@ pub struct CodingIndependentCodePoints {
     pub is_video_full_range_image: bool,
 }
 
+impl CodingIndependentCodePoints {
+    pub(crate) fn encode<W: Write>(self, w: &mut W) -> encoder::Result<()> {
+        let data = [
+            self.color_primaries,
+            self.transfer_function,
+            self.matrix_coefficients,
+            self.is_video_full_range_image as u8,
+        ];
+        encoder::write_chunk(w, chunk::cICP, &data)
+    }
+}
+
 /// Mastering Display Color Volume (mDCV) used at the point of content creation,
 /// as specified in [SMPTE-ST-2086](https://ieeexplore.ieee.org/stamp/stamp.jsp?arnumber=8353899).
 ///
@@ -617,6 +974,41 @@ pub struct MasteringDisplayColorVolume {
     pub min_luminance: u32,
 }
 
+impl MasteringDisplayColorVolume {
+    /// The chromaticities in the units of the chunk, 0.00002, or `None` if they don't fit.
+    pub(crate) fn scaled_chromaticities(&self) -> Option<[u16; 8]> {
+        // `ScaledFloat` uses units of 0.00001, see `StreamingDecoder::parse_mdcv`.
+        let scale = |value: ScaledFloat| u16::try_from(value.into_scaled() / 2).ok();
+        let SourceChromaticities {
+            white,
+            red,
+            green,
+            blue,
+        } = self.chromaticities;
+        Some([
+            scale(red.0)?,
+            scale(red.1)?,
+            scale(green.0)?,
+            scale(green.1)?,
+            scale(blue.0)?,
+            scale(blue.1)?,
+            scale(white.0)?,
+            scale(white.1)?,
+        ])
+    }
+
+    pub(crate) fn encode<W: Write>(self, w: &mut W) -> encoder::Result<()> {
+        let mut data = [0; 24];
+        let chromaticities = self.scaled_chromaticities().unwrap_or_default();
+        for (chunk, value) in data.chunks_exact_mut(2).zip(chromaticities) {
+            chunk.copy_from_slice(&value.to_be_bytes());
+        }
+        data[16..20].copy_from_slice(&self.max_luminance.to_be_bytes());
+        data[20..].copy_from_slice(&self.min_luminance.to_be_bytes());
+        encoder::write_chunk(w, chunk::mDCV, &data)
+    }
+}
+
 /// Content light level information of HDR content.
 ///
 /// See https://www.w3.org/TR/png-3/#cLLI-chunk for more details.
@@ -645,6 +1037,40 @@ pub struct ContentLightLevelInfo {
     pub max_frame_average_light_level: u32,
 }
 
+impl ContentLightLevelInfo {
+    pub(crate) fn encode<W: Write>(self, w: &mut W) -> encoder::Result<()> {
+        let mut data = [0; 8];
+        data[..4].copy_from_slice(&self.max_content_light_level.to_be_bytes());
+        data[4..].copy_from_slice(&self.max_frame_average_light_level.to_be_bytes());
+        encoder::write_chunk(w, chunk::cLLI, &data)
+    }
+}
+
+/// The position of a chunk relative to the critical chunks of the image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChunkLocation {
+    /// After `IHDR`, before any `PLTE` chunk.
+    BeforePlte,
+    /// After the `PLTE` chunk, before the first `IDAT` chunk.
+    BeforeIdat,
+    /// After the first `IDAT` chunk, including chunks between APNG frames.
+    AfterIdat,
+}
+
+/// An ancillary chunk that is not recognized by the decoder.
+///
+/// Such chunks are only kept if the [`UnknownChunkPolicy`](crate::UnknownChunkPolicy) of the
+/// decoder asks for them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownChunk {
+    /// The type of the chunk.
+    pub type_: chunk::ChunkType,
+    /// The data of the chunk, without its length and CRC.
+    pub data: Vec<u8>,
+    /// Where the chunk was found, so that it can be written back in the same place.
+    pub location: ChunkLocation,
+}
+
 /// PNG info struct
 #[derive(Clone, Debug)]
 #[non_exhaustive]
@@ -693,12 +1119,33 @@ pub struct Info<'a> {
     pub content_light_level: Option<ContentLightLevelInfo>,
     /// The EXIF metadata for the image.
     pub exif_metadata: Option<Cow<'a, [u8]>>,
+    /// The time of the last modification of the image, from the `tIME` chunk.
+    pub modification_time: Option<Time>,
+    /// The approximate usage frequencies of the palette entries, from the `hIST` chunk.
+    pub histogram: Option<Vec<u16>>,
+    /// The suggested palettes of the `sPLT` chunks.
+    pub suggested_palettes: Vec<SuggestedPalette>,
+    /// The position of the image, from the `oFFs` chunk.
+    pub offset: Option<ImageOffset>,
+    /// The calibration of the samples, from the `pCAL` chunk.
+    pub pixel_calibration: Option<PixelCalibration>,
+    /// The physical scale of the image subject, from the `sCAL` chunk.
+    pub physical_scale: Option<PhysicalScale>,
+    /// The layout of a stereo image, from the `sTER` chunk.
+    pub stereo_layout: Option<StereoLayout>,
+    /// The GIF Graphic Control Extensions of the `gIFg` chunks.
+    pub gif_graphic_controls: Vec<GifGraphicControl>,
+    /// The GIF Application Extensions of the `gIFx` chunks.
+    pub gif_application_extensions: Vec<GifApplicationExtension>,
     /// tEXt field
     pub uncompressed_latin1_text: Vec<TEXtChunk>,
     /// zTXt field
     pub compressed_latin1_text: Vec<ZTXtChunk>,
     /// iTXt field
     pub utf8_text: Vec<ITXtChunk>,
+    /// Unrecognized ancillary chunks, in the order they were found, if the decoder was asked to
+    /// keep them. The encoder writes them back at their [`location`](UnknownChunk::location).
+    pub unknown_chunks: Vec<UnknownChunk>,
 }
 
 impl Default for Info<'_> {
@@ -726,9 +1173,19 @@ impl Default for Info<'_> {
             mastering_display_color_volume: None,
             content_light_level: None,
             exif_metadata: None,
+            modification_time: None,
+            histogram: None,
+            suggested_palettes: Vec::new(),
+            offset: None,
+            pixel_calibration: None,
+            physical_scale: None,
+            stereo_layout: None,
+            gif_graphic_controls: Vec::new(),
+            gif_application_extensions: Vec::new(),
             uncompressed_latin1_text: Vec::new(),
             compressed_latin1_text: Vec::new(),
             utf8_text: Vec::new(),
+            unknown_chunks: Vec::new(),
         }
     }
 }
@@ -864,31 +1321,13 @@ bitflags::bitflags! {
     ```c
     /// Discard the alpha channel
     const STRIP_ALPHA         = 0x0002; // read only
-    /// Expand 1; 2 and 4-bit samples to bytes
-    const PACKING             = 0x0004; // read and write
     /// Change order of packed pixels to LSB first
     const PACKSWAP            = 0x0008; // read and write
     /// Invert monochrome images
     const INVERT_MONO         = 0x0020; // read and write
-    /// Normalize pixels to the sBIT depth
-    const SHIFT               = 0x0040; // read and write
-    /// Flip RGB to BGR; RGBA to BGRA
-    const BGR                 = 0x0080; // read and write
-    /// Flip RGBA to ARGB or GA to AG
-    const SWAP_ALPHA          = 0x0100; // read and write
-    /// Byte-swap 16-bit samples
-    const SWAP_ENDIAN         = 0x0200; // read and write
-    /// Change alpha from opacity to transparency
-    const INVERT_ALPHA        = 0x0400; // read and write
     const STRIP_FILLER        = 0x0800; // write only
     const STRIP_FILLER_BEFORE = 0x0800; // write only
     const STRIP_FILLER_AFTER  = 0x1000; // write only
-    const GRAY_TO_RGB         = 0x2000; // read only
-    const EXPAND_16           = 0x4000; // read only
-    /// Similar to STRIP_16 but in libpng considering gamma?
-    /// Not entirely sure the documentation says it is more
-    /// accurate but doesn't say precisely how.
-    const SCALE_16            = 0x8000; // read only
     ```
     "]
     #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -897,12 +1336,69 @@ bitflags::bitflags! {
         const IDENTITY            = 0x00000; // read and write */
         /// Strip 16-bit samples to 8 bits
         const STRIP_16            = 0x00001; // read only */
+        /// Unpack 1, 2 and 4-bit grayscale and indexed samples to one byte per sample, without
+        /// scaling their values, e.g. to get one palette index per byte. `EXPAND` and
+        /// `GRAY_TO_RGB` take precedence, as they unpack and scale the samples.
+        const PACKING             = 0x00004; // read only */
         /// Expand paletted images to RGB; expand grayscale images of
         /// less than 8-bit depth to 8-bit depth; and expand tRNS chunks
         /// to alpha channels.
         const EXPAND              = 0x00010; // read only */
+        /// Shift samples of 8 and 16 bits down to their significant bits, given by the sBIT
+        /// chunk, e.g. 12-bit samples stored in 16 bits are returned in `0..4096`. The samples
+        /// are shifted after the other transformations that change their values, so those still
+        /// work on the full range.
+        const SHIFT               = 0x00040; // read only */
+        /// Flip RGB to BGR and RGBA to BGRA.
+        const BGR                 = 0x00080; // read only */
+        /// Move the alpha sample, or the filler of `FILLER`, before the color samples, e.g.
+        /// RGBA to ARGB or GA to AG.
+        const SWAP_ALPHA          = 0x00100; // read only */
+        /// Output 16-bit samples in little-endian instead of big-endian byte order, which is the
+        /// native order on most targets. This is applied after all other transformations.
+        const SWAP_ENDIAN         = 0x00200; // read only */
+        /// Change alpha from opacity to transparency.
+        const INVERT_ALPHA        = 0x00400; // read only */
+        /// Convert grayscale images to RGB, by copying the gray sample into all colors.
+        /// Grayscale images of less than 8-bit depth are expanded to 8-bit depth, tRNS chunks
+        /// are only expanded to alpha with `EXPAND`.
+        const GRAY_TO_RGB         = 0x02000; // read only */
+        /// Expand images like `EXPAND`, then widen samples of less than 16 bits to 16 bits,
+        /// including the samples of the palette and the alpha from tRNS chunks. Implies `EXPAND`.
+        const EXPAND_16           = 0x04000; // read only */
+        /// Reduce 16-bit samples to 8 bits, rounded to the nearest value instead of discarding
+        /// the low byte like `STRIP_16`. Takes precedence over `STRIP_16`.
+        const SCALE_16            = 0x08000; // read only */
         /// Expand paletted images to include an alpha channel. Implies `EXPAND`.
         const ALPHA               = 0x10000; // read only */
+        /// Convert color samples from the gamma of the image, given by its `gAMA` or `sRGB`
+        /// chunk, to the gamma of the display, see `Decoder::set_display_gamma`. Images without
+        /// gamma information and alpha samples are not changed. The palette of indexed images is
+        /// only corrected when they are expanded.
+        const GAMMA               = 0x20000; // read only */
+        /// Add a filler sample after the color samples of 8 and 16-bit images without alpha,
+        /// e.g. RGB to RGBX, see `Decoder::set_filler`. The output color type is `Rgba` or
+        /// `GrayscaleAlpha`, with the filler in place of the alpha sample.
+        const FILLER              = 0x40000; // read only */
+        /// Convert RGB and RGBA images to grayscale, with the weighted sum of the colors, see
+        /// `Decoder::set_gray_coefficients`. Indexed images are only converted together with
+        /// `EXPAND`. Alpha samples, including those expanded from tRNS chunks, are kept.
+        const RGB_TO_GRAY         = 0x80000; // read only */
+        /// Multiply the color samples with the alpha sample, as compositors and GPU textures
+        /// expect, e.g. for RGBA, GrayscaleAlpha and expanded tRNS chunks. The filler of
+        /// `FILLER` is not alpha and doesn't change the samples. See
+        /// `Encoder::set_premultiplied_alpha` for writing such samples.
+        const PREMULTIPLY_ALPHA   = 0x100000; // read only */
+        /// Composite pixels onto a background color and remove the alpha sample, e.g. RGBA to
+        /// RGB. The color is set with `Decoder::set_background_color`, or else taken from the
+        /// `bKGD` chunk, images without either keep their alpha. Transparency from tRNS chunks
+        /// is composited when it is expanded with `EXPAND`.
+        const BACKGROUND          = 0x200000; // read only */
+        /// Rescale samples of 8 and 16 bits from their significant bits, given by the sBIT
+        /// chunk, to the full range of their bit depth, e.g. the 5 bits of red of a 5-6-5 source
+        /// from `0..32` to `0..256`. Samples of less than 8 bits are rescaled when they are
+        /// expanded, the samples of indexed images with `EXPAND`.
+        const RESCALE_SBIT        = 0x400000; // read only */
     }
 }
 
@@ -947,6 +1443,10 @@ pub(crate) enum ParameterErrorKind {
     /// [`DecodingError::Format`]).  The only case when it is possible to resume after an error
     /// is an `UnexpectedEof` scenario - see [`DecodingError::IoError`].
     PolledAfterFatalError,
+    /// The region to decode is empty or does not lie within the frame.
+    RegionOutOfBounds,
+    /// The image data has samples of the given bit depth where 16-bit samples are required.
+    SampleBitDepth(BitDepth),
 }
 
 impl From<ParameterErrorKind> for ParameterError {
@@ -966,6 +1466,10 @@ impl fmt::Display for ParameterError {
             PolledAfterFatalError => {
                 write!(fmt, "A fatal decoding error has been encounted earlier")
             }
+            RegionOutOfBounds => write!(fmt, "Region is empty or outside of the frame"),
+            SampleBitDepth(bit_depth) => {
+                write!(fmt, "Samples have {} bits, expected 16", bit_depth as u8)
+            }
         }
     }
 }
@@ -0,0 +1,572 @@
+//! Asynchronous decoding that does not depend on a particular executor.
+//!
+//! [`AsyncDecoder`] and [`AsyncReader`] drive the same decoder state as [`Decoder`] and [`Reader`].
+//! When the input is not available yet, the blocking decoder stops with a `WouldBlock` error and
+//! the future returns `Poll::Pending`. Polled again, the future retries the operation, which
+//! resumes where it stopped, like it does after an `UnexpectedEof`.
+
+use std::future::poll_fn;
+use std::io::{self, BufRead, ErrorKind, Read};
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+use super::stream::{ChunkHandling, DecodeOptions, DecodingError, Strictness, UnknownChunkPolicy};
+use super::{
+    Decoder, FrameProgress, GrayCoefficients, InterlaceHandling, InterlaceInfo, InterlacedRow,
+    Limits, OutputInfo, Reader, Region, Row, Scale,
+};
+use crate::chunk::ChunkType;
+use crate::common::{BitDepth, ColorType, FrameControl, Info, ScaledFloat, Transformations};
+
+/// An asynchronous source of bytes with an internal buffer, the counterpart of [`BufRead`].
+///
+/// The methods have the same signatures as `AsyncBufRead` of the `futures` crate, so readers of
+/// any async runtime can be adapted by forwarding to them.
+pub trait AsyncBufRead {
+    /// Attempts to return the contents of the internal buffer, filling it with more data from the
+    /// source if it is empty.
+    ///
+    /// An empty buffer signals the end of the input. If no data is available yet, returns
+    /// `Poll::Pending` and arranges for the task of `cx` to be woken up when it is.
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>>;
+
+    /// Marks `amt` bytes of the buffer returned by [`AsyncBufRead::poll_fill_buf`] as read.
+    fn consume(self: Pin<&mut Self>, amt: usize);
+}
+
+impl AsyncBufRead for &[u8] {
+    fn poll_fill_buf(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        Poll::Ready(Ok(*self.get_mut()))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let data = self.get_mut();
+        *data = &data[amt..];
+    }
+}
+
+impl<T: AsyncBufRead + Unpin + ?Sized> AsyncBufRead for &mut T {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        Pin::new(&mut **self.get_mut()).poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        Pin::new(&mut **self.get_mut()).consume(amt)
+    }
+}
+
+/// Presents an [`AsyncBufRead`] to the blocking decoder as a [`BufRead`].
+///
+/// Reading fails with `WouldBlock` while the source is pending, using the waker of the task that
+/// last polled one of the futures of this module.
+pub(crate) struct PollRead<R> {
+    inner: R,
+    waker: Option<Waker>,
+    /// Whether the source returned `Poll::Pending` since the last call to `resume`.
+    pending: bool,
+}
+
+impl<R: AsyncBufRead + Unpin> Read for PollRead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> BufRead for PollRead<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        // The reader is only used from within `resume`, which always provides a waker.
+        let waker = self.waker.as_ref().ok_or(ErrorKind::WouldBlock)?;
+        let mut cx = Context::from_waker(waker);
+        match Pin::new(&mut self.inner).poll_fill_buf(&mut cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => {
+                self.pending = true;
+                Err(ErrorKind::WouldBlock.into())
+            }
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        Pin::new(&mut self.inner).consume(amt)
+    }
+}
+
+/// The blocking decoder state wrapped by the types of this module.
+trait Resumable {
+    type Inner;
+
+    fn source(&mut self) -> &mut PollRead<Self::Inner>;
+}
+
+impl<R: AsyncBufRead + Unpin> Resumable for Decoder<PollRead<R>> {
+    type Inner = R;
+
+    fn source(&mut self) -> &mut PollRead<R> {
+        self.read_decoder.reader_mut()
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> Resumable for Reader<PollRead<R>> {
+    type Inner = R;
+
+    fn source(&mut self) -> &mut PollRead<R> {
+        self.decoder.reader_mut()
+    }
+}
+
+/// Calls `op` whenever the task is polled, until it no longer waits for input.
+async fn resume<S: Resumable, T>(
+    state: &mut S,
+    mut op: impl FnMut(&mut S) -> Result<T, DecodingError>,
+) -> Result<T, DecodingError> {
+    poll_fn(|cx| {
+        let source = state.source();
+        if !source
+            .waker
+            .as_ref()
+            .is_some_and(|w| w.will_wake(cx.waker()))
+        {
+            source.waker = Some(cx.waker().clone());
+        }
+        source.pending = false;
+
+        match op(state) {
+            Err(DecodingError::IoError(_)) if state.source().pending => Poll::Pending,
+            result => Poll::Ready(result),
+        }
+    })
+    .await
+}
+
+/// PNG decoder reading from an [`AsyncBufRead`].
+///
+/// This is the asynchronous counterpart of [`Decoder`]. It does not spawn tasks or depend on an
+/// async runtime, the returned futures can be awaited on any executor.
+///
+/// ```
+/// use png::{AsyncDecoder, DecodingError};
+///
+/// async fn decode(data: &[u8]) -> Result<Vec<u8>, DecodingError> {
+///     let mut reader = AsyncDecoder::new(data).read_info().await?;
+///     let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+///     let info = reader.next_frame(&mut buf).await?;
+///     buf.truncate(info.buffer_size());
+///     Ok(buf)
+/// }
+/// ```
+pub struct AsyncDecoder<R: AsyncBufRead + Unpin> {
+    decoder: Decoder<PollRead<R>>,
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncDecoder<R> {
+    /// Create a new decoder configuration with default limits.
+    pub fn new(r: R) -> Self {
+        AsyncDecoder::new_with_limits(r, Limits::default())
+    }
+
+    /// Create a new decoder configuration with custom limits.
+    pub fn new_with_limits(r: R, limits: Limits) -> Self {
+        AsyncDecoder {
+            decoder: Decoder::new_with_limits(PollRead::new(r), limits),
+        }
+    }
+
+    /// Create a new decoder configuration with custom [`DecodeOptions`].
+    pub fn new_with_options(r: R, decode_options: DecodeOptions) -> Self {
+        AsyncDecoder {
+            decoder: Decoder::new_with_options(PollRead::new(r), decode_options),
+        }
+    }
+
+    /// Limit resource usage, see [`Decoder::set_limits`].
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.decoder.set_limits(limits);
+    }
+
+    /// Set the allowed and performed transformations, see [`Decoder::set_transformations`].
+    pub fn set_transformations(&mut self, transform: Transformations) {
+        self.decoder.set_transformations(transform);
+    }
+
+    /// Set the exponent of the display's transfer function, see [`Decoder::set_display_gamma`].
+    pub fn set_display_gamma(&mut self, display_gamma: ScaledFloat) {
+        self.decoder.set_display_gamma(display_gamma);
+    }
+
+    /// Set the value of filler samples, see [`Decoder::set_filler`].
+    pub fn set_filler(&mut self, filler: u16) {
+        self.decoder.set_filler(filler);
+    }
+
+    /// Set the weights of the colors in gray values, see [`Decoder::set_gray_coefficients`].
+    pub fn set_gray_coefficients(&mut self, coefficients: GrayCoefficients) {
+        self.decoder.set_gray_coefficients(coefficients);
+    }
+
+    /// Set the color that images are composited onto, see [`Decoder::set_background_color`].
+    pub fn set_background_color(&mut self, color: [u16; 3]) {
+        self.decoder.set_background_color(color);
+    }
+
+    /// Set the resolution of decoded frames, see [`Decoder::set_scale`].
+    pub fn set_scale(&mut self, scale: Scale) {
+        self.decoder.set_scale(scale);
+    }
+
+    /// Set how interlaced frames are arranged, see [`Decoder::set_interlace_handling`].
+    pub fn set_interlace_handling(&mut self, interlace_handling: InterlaceHandling) {
+        self.decoder.set_interlace_handling(interlace_handling);
+    }
+
+    /// Set the decoder to ignore all text chunks while parsing.
+    pub fn set_ignore_text_chunk(&mut self, ignore_text_chunk: bool) {
+        self.decoder.set_ignore_text_chunk(ignore_text_chunk);
+    }
+
+    /// Set the decoder to ignore iccp chunks while parsing.
+    pub fn set_ignore_iccp_chunk(&mut self, ignore_iccp_chunk: bool) {
+        self.decoder.set_ignore_iccp_chunk(ignore_iccp_chunk);
+    }
+
+    /// Set which unrecognized ancillary chunks to keep, see [`Decoder::set_unknown_chunk_policy`].
+    pub fn set_unknown_chunk_policy(&mut self, unknown_chunk_policy: UnknownChunkPolicy) {
+        self.decoder.set_unknown_chunk_policy(unknown_chunk_policy);
+    }
+
+    /// Set whether to report errors in ancillary chunks, see [`Decoder::set_strictness`].
+    pub fn set_strictness(&mut self, strictness: Strictness) {
+        self.decoder.set_strictness(strictness);
+    }
+
+    /// Set the handler of chunks of type `type_`, see [`Decoder::set_chunk_handler`].
+    pub fn set_chunk_handler(
+        &mut self,
+        type_: ChunkType,
+        handler: impl FnMut(ChunkType, &[u8]) -> ChunkHandling + Send + Sync + 'static,
+    ) {
+        self.decoder.set_chunk_handler(type_, handler);
+    }
+
+    /// Set the handler of chunks without a handler for their type, see
+    /// [`Decoder::set_default_chunk_handler`].
+    pub fn set_default_chunk_handler(
+        &mut self,
+        handler: impl FnMut(ChunkType, &[u8]) -> ChunkHandling + Send + Sync + 'static,
+    ) {
+        self.decoder.set_default_chunk_handler(handler);
+    }
+
+    /// Set the decoder to ignore and not verify the Adler-32 checksum
+    /// and CRC code.
+    pub fn ignore_checksums(&mut self, ignore_checksums: bool) {
+        self.decoder.ignore_checksums(ignore_checksums);
+    }
+
+    /// Read the PNG header and return the information contained within.
+    ///
+    /// See [`Decoder::read_header_info`].
+    pub async fn read_header_info(&mut self) -> Result<&Info<'static>, DecodingError> {
+        resume(&mut self.decoder, |d| d.read_header_info().map(drop)).await?;
+        self.decoder.read_header_info()
+    }
+
+    /// Reads all meta data until the first IDAT chunk
+    pub async fn read_info(mut self) -> Result<AsyncReader<R>, DecodingError> {
+        self.read_header_info().await?;
+        let mut reader = Reader::new(self.decoder)?;
+        resume(&mut reader, Reader::read_until_first_frame).await?;
+        Ok(AsyncReader { reader })
+    }
+}
+
+/// PNG reader for an [`AsyncBufRead`], the asynchronous counterpart of [`Reader`].
+pub struct AsyncReader<R: AsyncBufRead + Unpin> {
+    reader: Reader<PollRead<R>>,
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncReader<R> {
+    /// Advances to the start of the next animation frame, see [`Reader::next_frame_info`].
+    pub async fn next_frame_info(&mut self) -> Result<&FrameControl, DecodingError> {
+        resume(&mut self.reader, |r| r.next_frame_info().map(drop)).await?;
+        Ok(self.reader.info().frame_control.as_ref().unwrap())
+    }
+
+    /// Get information on the image.
+    ///
+    /// The structure will change as new frames of an animated image are decoded.
+    pub fn info(&self) -> &Info<'static> {
+        self.reader.info()
+    }
+
+    /// Decodes the next frame into `buf`, see [`Reader::next_frame`].
+    ///
+    /// If the future is dropped before it completes, the frame is left partially decoded and the
+    /// rest of it can still be read with [`AsyncReader::next_row`].
+    pub async fn next_frame(&mut self, buf: &mut [u8]) -> Result<OutputInfo, DecodingError> {
+        resume(&mut self.reader, |r| r.next_frame(buf)).await
+    }
+
+    /// Decodes the next frame into `buf` with one element for each 16-bit sample, see
+    /// [`Reader::next_frame_u16`].
+    pub async fn next_frame_u16(&mut self, buf: &mut [u16]) -> Result<OutputInfo, DecodingError> {
+        resume(&mut self.reader, |r| r.next_frame_u16(buf)).await
+    }
+
+    /// Decodes the next frame into `buf` and reports the rows written so far, see
+    /// [`Reader::next_frame_progressive`].
+    ///
+    /// `progress` is called as soon as rows are decoded, also while the future waits for more
+    /// input.
+    pub async fn next_frame_progressive(
+        &mut self,
+        buf: &mut [u8],
+        mut progress: impl FnMut(FrameProgress<'_>),
+    ) -> Result<OutputInfo, DecodingError> {
+        resume(&mut self.reader, |r| {
+            r.next_frame_progressive(buf, &mut progress)
+        })
+        .await
+    }
+
+    /// Decodes the pixels of the next frame within `region` into `buf`, see
+    /// [`Reader::next_frame_region`].
+    pub async fn next_frame_region(
+        &mut self,
+        region: Region,
+        buf: &mut [u8],
+    ) -> Result<OutputInfo, DecodingError> {
+        resume(&mut self.reader, |r| r.next_frame_region(region, buf)).await
+    }
+
+    /// Returns the next processed row of the image, see [`Reader::next_row`].
+    pub async fn next_row(&mut self) -> Result<Option<Row<'_>>, DecodingError> {
+        let row = self.next_interlaced_row().await?;
+        Ok(row.map(|row| Row { data: row.data }))
+    }
+
+    /// Returns the next processed row of the image, see [`Reader::next_interlaced_row`].
+    pub async fn next_interlaced_row(
+        &mut self,
+    ) -> Result<Option<InterlacedRow<'_>>, DecodingError> {
+        let interlace = resume(&mut self.reader, Reader::read_row_into_scratch_buffer).await?;
+        Ok(interlace.map(|interlace| self.reader.scratch_row(interlace)))
+    }
+
+    /// Reads the next row of the image into the provided `output_buffer`, see
+    /// [`Reader::read_row`].
+    pub async fn read_row(
+        &mut self,
+        output_buffer: &mut [u8],
+    ) -> Result<Option<InterlaceInfo>, DecodingError> {
+        resume(&mut self.reader, |r| r.read_row(output_buffer)).await
+    }
+
+    /// Read the rest of the image and chunks and finish up, see [`Reader::finish`].
+    pub async fn finish(&mut self) -> Result<(), DecodingError> {
+        resume(&mut self.reader, Reader::finish).await
+    }
+
+    /// Returns the color type and the number of bits per sample
+    /// of the data returned by [`AsyncReader::next_row`] and [`AsyncReader::next_frame`].
+    pub fn output_color_type(&self) -> (ColorType, BitDepth) {
+        self.reader.output_color_type()
+    }
+
+    /// Return the number of bytes required to hold a deinterlaced image frame, see
+    /// [`Reader::output_buffer_size`].
+    pub fn output_buffer_size(&self) -> Option<usize> {
+        self.reader.output_buffer_size()
+    }
+
+    /// Returns the number of bytes required to hold a deinterlaced row, see
+    /// [`Reader::output_line_size`].
+    pub fn output_line_size(&self, width: u32) -> Option<usize> {
+        self.reader.output_line_size(width)
+    }
+}
+
+impl<R> PollRead<R> {
+    fn new(inner: R) -> Self {
+        PollRead {
+            inner,
+            waker: None,
+            pending: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::block_on;
+
+    /// Returns the data one byte at a time, and is pending before each byte.
+    struct Trickle<'data> {
+        data: &'data [u8],
+        ready: bool,
+        pending_polls: usize,
+    }
+
+    impl<'data> Trickle<'data> {
+        fn new(data: &'data [u8]) -> Self {
+            Trickle {
+                data,
+                ready: false,
+                pending_polls: 0,
+            }
+        }
+    }
+
+    impl AsyncBufRead for Trickle<'_> {
+        fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+            let this = self.get_mut();
+            if this.ready || this.data.is_empty() {
+                Poll::Ready(Ok(&this.data[..this.data.len().min(1)]))
+            } else {
+                this.ready = true;
+                this.pending_polls += 1;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+
+        fn consume(self: Pin<&mut Self>, amt: usize) {
+            let this = self.get_mut();
+            this.data = &this.data[amt..];
+            this.ready = false;
+        }
+    }
+
+    fn decode_blocking(data: &[u8]) -> (Vec<Vec<u8>>, Vec<OutputInfo>) {
+        let mut reader = Decoder::new(data).read_info().unwrap();
+        let mut frames = Vec::new();
+        let mut infos = Vec::new();
+        for _ in 0..reader.info().animation_control.map_or(1, |a| a.num_frames) {
+            let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+            infos.push(reader.next_frame(&mut buf).unwrap());
+            frames.push(buf);
+        }
+        reader.finish().unwrap();
+        (frames, infos)
+    }
+
+    #[test]
+    fn test_async_next_frame() {
+        for path in [
+            "tests/pngsuite/basn0g01.png",
+            "tests/pngsuite/basi2c16.png",
+            "tests/pngsuite/basi3p04.png",
+            "tests/animated/basic_f20.png",
+        ] {
+            let data = std::fs::read(path).unwrap();
+            let (expected_frames, expected_infos) = decode_blocking(&data);
+
+            let mut source = Trickle::new(&data);
+            block_on(async {
+                let mut reader = AsyncDecoder::new(&mut source).read_info().await.unwrap();
+                for (expected, expected_info) in expected_frames.iter().zip(&expected_infos) {
+                    let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+                    let info = reader.next_frame(&mut buf).await.unwrap();
+                    assert_eq!(info, *expected_info, "{}", path);
+                    assert_eq!(buf, *expected, "{}", path);
+                }
+                reader.finish().await.unwrap();
+            });
+            assert!(source.pending_polls >= data.len(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_async_next_frame_progressive() {
+        let data = std::fs::read("tests/pngsuite/basi2c16.png").unwrap();
+        let (expected_frames, _) = decode_blocking(&data);
+
+        let (frame, progress) = block_on(async {
+            let mut reader = AsyncDecoder::new(Trickle::new(&data))
+                .read_info()
+                .await
+                .unwrap();
+            let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+            let mut progress = Vec::new();
+            reader
+                .next_frame_progressive(&mut buf, |p| progress.push(p.rows))
+                .await
+                .unwrap();
+            (buf, progress)
+        });
+        assert_eq!(frame, expected_frames[0]);
+        assert_eq!(progress.len(), 60);
+        assert_eq!(progress.last(), Some(&(31..32)));
+    }
+
+    #[test]
+    fn test_async_next_frame_u16() {
+        let data = std::fs::read("tests/pngsuite/basn6a16.png").unwrap();
+        let (expected_frames, _) = decode_blocking(&data);
+        let expected: Vec<u16> = expected_frames[0]
+            .chunks_exact(2)
+            .map(|s| u16::from_be_bytes([s[0], s[1]]))
+            .collect();
+
+        let frame = block_on(async {
+            let mut reader = AsyncDecoder::new(Trickle::new(&data))
+                .read_info()
+                .await
+                .unwrap();
+            let mut buf = vec![0; reader.output_buffer_size().unwrap() / 2];
+            reader.next_frame_u16(&mut buf).await.unwrap();
+            buf
+        });
+        assert_eq!(frame, expected);
+    }
+
+    #[test]
+    fn test_async_next_row() {
+        let data = std::fs::read("tests/pngsuite/basi0g08.png").unwrap();
+        let mut blocking = Decoder::new(data.as_slice()).read_info().unwrap();
+        let mut expected_rows = Vec::new();
+        while let Some(row) = blocking.next_interlaced_row().unwrap() {
+            expected_rows.push((row.data().to_vec(), *row.interlace()));
+        }
+
+        let rows = block_on(async {
+            let mut decoder = AsyncDecoder::new(Trickle::new(&data));
+            assert_eq!(decoder.read_header_info().await.unwrap().width, 32);
+            let mut reader = decoder.read_info().await.unwrap();
+            let mut rows = Vec::new();
+            while let Some(row) = reader.next_interlaced_row().await.unwrap() {
+                rows.push((row.data().to_vec(), *row.interlace()));
+            }
+            rows
+        });
+        assert_eq!(rows.len(), expected_rows.len());
+        for ((data, interlace), (expected_data, expected_interlace)) in
+            rows.iter().zip(&expected_rows)
+        {
+            assert_eq!(data, expected_data);
+            assert_eq!(interlace.line_number(), expected_interlace.line_number());
+        }
+    }
+
+    #[test]
+    fn test_async_unexpected_eof() {
+        let data = std::fs::read("tests/pngsuite/basn2c08.png").unwrap();
+        let truncated = &data[..data.len() / 2];
+
+        let result = block_on(async {
+            let mut reader = AsyncDecoder::new(Trickle::new(truncated))
+                .read_info()
+                .await?;
+            let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+            reader.next_frame(&mut buf).await
+        });
+        assert!(matches!(
+            result,
+            Err(DecodingError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof
+        ));
+    }
+}
@@ -0,0 +1,178 @@
+//! Compositing of APNG frames onto a full-size canvas.
+//!
+//! See [`Reader::next_composited_frame`](super::Reader::next_composited_frame).
+
+use crate::common::{BitDepth, BlendOp, ColorType, DisposeOp, FrameControl};
+
+/// The rendered state of an animation.
+///
+/// Pixels are stored with the alpha sample last, either as `GrayscaleAlpha` or `Rgba`, with 8 or
+/// 16 bits per sample. 16-bit samples are big endian, just like in the output of `next_frame`.
+pub(crate) struct Canvas {
+    width: u32,
+    /// Bytes per pixel.
+    bpp: usize,
+    sixteen: bool,
+    buffer: Vec<u8>,
+    /// The pixels overwritten by the last frame, restored by `DisposeOp::Previous`.
+    saved: Vec<u8>,
+    /// The last frame drawn, to be disposed of before the next one.
+    last_frame: Option<FrameControl>,
+}
+
+impl Canvas {
+    /// Creates a fully transparent canvas.
+    pub(crate) fn new(width: u32, height: u32, color_type: ColorType, depth: BitDepth) -> Self {
+        debug_assert!(matches!(
+            color_type,
+            ColorType::GrayscaleAlpha | ColorType::Rgba
+        ));
+        debug_assert!(matches!(depth, BitDepth::Eight | BitDepth::Sixteen));
+
+        let bpp = color_type.bytes_per_pixel(depth);
+        Canvas {
+            width,
+            bpp,
+            sixteen: depth == BitDepth::Sixteen,
+            buffer: vec![0; width as usize * height as usize * bpp],
+            saved: Vec::new(),
+            last_frame: None,
+        }
+    }
+
+    pub(crate) fn buffer(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Disposes of the previous frame and then draws `frame` into the region described by
+    /// `fctl`. The rows of `frame` are packed, in the same format as the canvas.
+    pub(crate) fn compose(&mut self, frame: &[u8], fctl: &FrameControl) {
+        self.dispose();
+
+        let mut fctl = *fctl;
+        // The APNG specification requires this for the first frame, there is nothing to restore.
+        if self.last_frame.is_none() && fctl.dispose_op == DisposeOp::Previous {
+            fctl.dispose_op = DisposeOp::Background;
+        }
+
+        let region_len = fctl.width as usize * self.bpp;
+        if fctl.dispose_op == DisposeOp::Previous {
+            self.saved.clear();
+            for y in 0..fctl.height {
+                let start = self.region_row_start(&fctl, y);
+                self.saved
+                    .extend_from_slice(&self.buffer[start..][..region_len]);
+            }
+        }
+
+        for (y, src_row) in (0..fctl.height).zip(frame.chunks_exact(region_len)) {
+            let start = self.region_row_start(&fctl, y);
+            let dst_row = &mut self.buffer[start..][..region_len];
+            match fctl.blend_op {
+                BlendOp::Source => dst_row.copy_from_slice(src_row),
+                BlendOp::Over => {
+                    let pixels = dst_row
+                        .chunks_exact_mut(self.bpp)
+                        .zip(src_row.chunks_exact(self.bpp));
+                    for (dst, src) in pixels {
+                        blend_over(dst, src, self.sixteen);
+                    }
+                }
+            }
+        }
+
+        self.last_frame = Some(fctl);
+    }
+
+    fn dispose(&mut self) {
+        let fctl = match self.last_frame {
+            Some(fctl) if fctl.dispose_op != DisposeOp::None => fctl,
+            _ => return,
+        };
+
+        let region_len = fctl.width as usize * self.bpp;
+        for y in 0..fctl.height {
+            let start = self.region_row_start(&fctl, y);
+            let row = &mut self.buffer[start..][..region_len];
+            if fctl.dispose_op == DisposeOp::Previous {
+                row.copy_from_slice(&self.saved[y as usize * region_len..][..region_len]);
+            } else {
+                row.fill(0);
+            }
+        }
+    }
+
+    /// The index of the first byte of row `y` of a frame in the canvas.
+    fn region_row_start(&self, fctl: &FrameControl, y: u32) -> usize {
+        let stride = self.width as usize * self.bpp;
+        (fctl.y_offset + y) as usize * stride + fctl.x_offset as usize * self.bpp
+    }
+}
+
+/// Alpha-composites the pixel `src` over `dst`, as specified for `APNG_BLEND_OP_OVER`.
+fn blend_over(dst: &mut [u8], src: &[u8], sixteen: bool) {
+    let (max, sample_len) = if sixteen { (0xffff, 2) } else { (0xff, 1) };
+    let sample = |px: &[u8], idx: usize| -> u64 {
+        match sample_len {
+            2 => u16::from_be_bytes([px[2 * idx], px[2 * idx + 1]]).into(),
+            _ => px[idx].into(),
+        }
+    };
+
+    let alpha = src.len() / sample_len - 1;
+    let src_a = sample(src, alpha);
+    if src_a == max {
+        dst.copy_from_slice(src);
+        return;
+    } else if src_a == 0 {
+        return;
+    }
+
+    // The weight of the destination, which shines through where the source is not opaque.
+    let dst_a = (sample(dst, alpha) * (max - src_a) + max / 2) / max;
+    let out_a = src_a + dst_a;
+
+    for idx in 0..=alpha {
+        let value = if idx == alpha {
+            out_a
+        } else {
+            (sample(src, idx) * src_a + sample(dst, idx) * dst_a + out_a / 2) / out_a
+        };
+
+        match sample_len {
+            2 => dst[2 * idx..][..2].copy_from_slice(&(value as u16).to_be_bytes()),
+            _ => dst[idx] = value as u8,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blend_over_extremes() {
+        let mut dst = [10, 20, 30, 40];
+        blend_over(&mut dst, &[1, 2, 3, 0], false);
+        assert_eq!(dst, [10, 20, 30, 40]);
+        blend_over(&mut dst, &[1, 2, 3, 255], false);
+        assert_eq!(dst, [1, 2, 3, 255]);
+    }
+
+    #[test]
+    fn blend_over_partial() {
+        // Half transparent white over opaque black is gray and opaque.
+        let mut dst = [0, 0, 0, 255];
+        blend_over(&mut dst, &[255, 255, 255, 128], false);
+        assert_eq!(dst, [128, 128, 128, 255]);
+
+        // Over a fully transparent pixel the source is unchanged.
+        let mut dst = [0, 0];
+        blend_over(&mut dst, &[200, 100], false);
+        assert_eq!(dst, [200, 100]);
+
+        let mut dst = [0, 0, 0xff, 0xff];
+        blend_over(&mut dst, &[0xff, 0xff, 0x80, 0x00], true);
+        assert_eq!(dst, [0x80, 0x00, 0xff, 0xff]);
+    }
+}
@@ -1,38 +1,195 @@
+mod async_reader;
+mod canvas;
 mod interlace_info;
 mod read_decoder;
+mod region;
+mod scale;
 pub(crate) mod stream;
+#[cfg(test)]
+mod test_utils;
 pub(crate) mod transform;
 mod unfiltering_buffer;
 mod zlib;
 
+pub use self::async_reader::{AsyncBufRead, AsyncDecoder, AsyncReader};
+use self::canvas::Canvas;
 use self::read_decoder::{ImageDataCompletionStatus, ReadDecoder};
-use self::stream::{DecodeOptions, DecodingError, FormatErrorInner};
-use self::transform::{create_transform_fn, TransformFn};
+pub use self::region::Region;
+use self::scale::BoxFilter;
+pub use self::scale::Scale;
+use self::stream::{
+    ChunkHandling, DecodeOptions, DecodingError, FormatErrorInner, Strictness, UnknownChunkPolicy,
+};
+pub use self::transform::GrayCoefficients;
+use self::transform::{create_transform_fn, TransformFn, TransformOptions};
 use self::unfiltering_buffer::UnfilteringBuffer;
 
-use std::io::{BufRead, Seek};
+use std::io::BufRead;
 use std::mem;
+use std::ops::Range;
 
-use crate::adam7::Adam7Info;
+use crate::adam7::{self, Adam7Info, Adam7Variant};
+use crate::chunk::ChunkType;
 use crate::common::{
-    BitDepth, BytesPerPixel, ColorType, Info, ParameterErrorKind, Transformations,
+    BitDepth, BytesPerPixel, ColorType, Info, ParameterErrorKind, ScaledFloat, Transformations,
 };
-use crate::FrameControl;
+use crate::{BlendOp, DisposeOp, FrameControl};
 pub use zlib::{UnfilterBuf, UnfilterRegion};
 
 pub use interlace_info::InterlaceInfo;
 use interlace_info::InterlaceInfoIter;
 
-/*
+/// How [`Reader::next_frame`] arranges the rows of Adam7 interlaced frames in the output buffer.
+///
+/// This only makes a difference for the intermediate states of the buffer, which can be observed
+/// with [`Reader::next_frame_progressive`] or after an error such as
+/// [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) while the input is still streaming in,
+/// and for [`InterlaceHandling::RawRows`]. Frames that are not interlaced are always decoded row
+/// by row.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum InterlaceHandling {
-    /// Outputs the raw rows
+    /// Outputs the raw rows of the passes, without deinterlacing them.
+    ///
+    /// The rows are stacked in the order in which they are decoded, each in a line of
+    /// [`OutputInfo::line_size`] that only holds the samples of its pass. The returned
+    /// [`OutputInfo::height`] is the number of these rows, which is larger than the height of the
+    /// frame.
     RawRows,
-    /// Fill missing the pixels from the existing ones
+    /// Fill the missing pixels from the existing ones, so that each pass refines a complete
+    /// image, see [`Adam7Variant::Splat`].
     Rectangle,
-    /// Only fill the needed pixels
-    Sparkle
+    /// Only fill the pixels of each pass, see [`Adam7Variant::Sparse`].
+    #[default]
+    Sparkle,
+}
+
+impl From<Adam7Variant> for InterlaceHandling {
+    fn from(variant: Adam7Variant) -> Self {
+        match variant {
+            Adam7Variant::Sparse => InterlaceHandling::Sparkle,
+            Adam7Variant::Splat => InterlaceHandling::Rectangle,
+        }
+    }
+}
+
+/// The progress of a frame decoded by [`Reader::next_frame_progressive`].
+#[derive(Debug)]
+pub struct FrameProgress<'data> {
+    /// The frame as far as it has been decoded, in the layout of the [`OutputInfo`] that is
+    /// returned for the frame.
+    pub buffer: &'data [u8],
+    /// The rows of `buffer` that were written since the previous progress.
+    pub rows: Range<u32>,
+    /// The Adam7 pass of the decoded image data, `None` for frames that are not interlaced.
+    pub pass: Option<u8>,
+}
+
+/// The buffer that a frame is decoded into, the bytes of `next_frame` or the samples of
+/// `next_frame_u16`.
+enum FrameBuffer<'a> {
+    Bytes(&'a mut [u8]),
+    /// 16-bit samples, converted from bytes in little endian order if `little_endian` is set.
+    Samples {
+        buf: &'a mut [u16],
+        little_endian: bool,
+    },
+}
+
+impl FrameBuffer<'_> {
+    /// The length of the buffer in bytes.
+    fn len(&self) -> usize {
+        match self {
+            FrameBuffer::Bytes(buf) => buf.len(),
+            FrameBuffer::Samples { buf, .. } => 2 * buf.len(),
+        }
+    }
+
+    /// Shortens the buffer to `len` bytes.
+    fn truncate(&mut self, len: usize) {
+        match self {
+            FrameBuffer::Bytes(buf) => *buf = &mut mem::take(buf)[..len],
+            FrameBuffer::Samples { buf, .. } => *buf = &mut mem::take(buf)[..len / 2],
+        }
+    }
+
+    /// The `len` bytes at `offset`, if the buffer holds bytes.
+    fn bytes_mut(&mut self, offset: usize, len: usize) -> Option<&mut [u8]> {
+        match self {
+            FrameBuffer::Bytes(buf) => Some(&mut buf[offset..][..len]),
+            FrameBuffer::Samples { .. } => None,
+        }
+    }
+
+    /// Writes `data` at the position of the byte `offset`.
+    fn write(&mut self, offset: usize, data: &[u8]) {
+        match self {
+            FrameBuffer::Bytes(buf) => buf[offset..][..data.len()].copy_from_slice(data),
+            FrameBuffer::Samples { buf, little_endian } => {
+                let samples = buf[offset / 2..][..data.len() / 2].iter_mut();
+                for (sample, bytes) in samples.zip(data.chunks_exact(2)) {
+                    let bytes = [bytes[0], bytes[1]];
+                    *sample = if *little_endian {
+                        u16::from_le_bytes(bytes)
+                    } else {
+                        u16::from_be_bytes(bytes)
+                    };
+                }
+            }
+        }
+    }
+
+    /// Writes the pixels of an interlaced row into the rows of the image, which are `stride`
+    /// bytes apart. With `splat`, each pixel also fills the rest of its Adam7 block.
+    fn expand_pass(
+        &mut self,
+        stride: usize,
+        row: &[u8],
+        adam7info: &Adam7Info,
+        bits_pp: u8,
+        splat: bool,
+    ) {
+        if let FrameBuffer::Bytes(buf) = self {
+            if splat {
+                adam7::expand_pass_splat(buf, stride, row, adam7info, bits_pp);
+            } else {
+                adam7::expand_pass(buf, stride, row, adam7info, bits_pp);
+            }
+            return;
+        }
+
+        // Pixels of 16-bit samples are whole bytes, so they are written one at a time.
+        let pixel_len = usize::from(bits_pp / 8);
+        let height = (self.len() / stride) as u32;
+        for (idx, pixel) in row.chunks_exact(pixel_len).enumerate() {
+            let (x, y) = adam7info.image_position(idx as u32);
+            let (columns, lines) = if splat {
+                (
+                    adam7info.splat_pixel_repeat(idx),
+                    adam7info.splat_line_repeat(height),
+                )
+            } else {
+                (1, 1)
+            };
+            for line in y..y + u32::from(lines) {
+                for column in x..x + u32::from(columns) {
+                    self.write(line as usize * stride + column as usize * pixel_len, pixel);
+                }
+            }
+        }
+    }
+
+    /// Reports the progress of `next_frame_progressive`, the only caller that observes it.
+    fn progress(
+        &self,
+        progress: &mut dyn FnMut(FrameProgress<'_>),
+        rows: Range<u32>,
+        pass: Option<u8>,
+    ) {
+        if let FrameBuffer::Bytes(buffer) = self {
+            progress(FrameProgress { buffer, rows, pass });
+        }
+    }
 }
-*/
 
 /// Output info.
 ///
@@ -60,6 +217,24 @@ impl OutputInfo {
     }
 }
 
+/// Composited frame info.
+///
+/// This describes a frame rendered onto the full image canvas by
+/// [`Reader::next_composited_frame`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct CompositedFrameInfo {
+    /// The layout of the canvas that was written into the output buffer.
+    pub output_info: OutputInfo,
+    /// Frame delay fraction numerator, see [`FrameControl::delay_num`].
+    ///
+    /// This is 0 for images that are not animated.
+    pub delay_num: u16,
+    /// Frame delay fraction denominator, see [`FrameControl::delay_den`].
+    ///
+    /// This is 0 for images that are not animated.
+    pub delay_den: u16,
+}
+
 #[derive(Clone, Copy, Debug)]
 /// Limits on the resources the `Decoder` is allowed too use
 pub struct Limits {
@@ -87,10 +262,20 @@ impl Default for Limits {
 }
 
 /// PNG Decoder
-pub struct Decoder<R: BufRead + Seek> {
+///
+/// The input is read sequentially, so any [`BufRead`] can be used, including sockets, pipes or
+/// decompression streams. Wrap other sources implementing only [`Read`](std::io::Read) in a
+/// [`BufReader`](std::io::BufReader).
+pub struct Decoder<R: BufRead> {
     read_decoder: ReadDecoder<R>,
     /// Output transformations
     transform: Transformations,
+    /// Parameters of the output transformations
+    transform_options: TransformOptions,
+    /// Resolution of the frames
+    scale: Scale,
+    /// Arrangement of interlaced rows
+    interlace_handling: InterlaceHandling,
 }
 
 /// A row of data with interlace information attached.
@@ -122,7 +307,7 @@ impl<'data> Row<'data> {
     }
 }
 
-impl<R: BufRead + Seek> Decoder<R> {
+impl<R: BufRead> Decoder<R> {
     /// Create a new decoder configuration with default limits.
     pub fn new(r: R) -> Decoder<R> {
         Decoder::new_with_limits(r, Limits::default())
@@ -136,6 +321,9 @@ impl<R: BufRead + Seek> Decoder<R> {
         Decoder {
             read_decoder,
             transform: Transformations::IDENTITY,
+            transform_options: TransformOptions::default(),
+            scale: Scale::Full,
+            interlace_handling: InterlaceHandling::Sparkle,
         }
     }
 
@@ -147,6 +335,9 @@ impl<R: BufRead + Seek> Decoder<R> {
         Decoder {
             read_decoder,
             transform: Transformations::IDENTITY,
+            transform_options: TransformOptions::default(),
+            scale: Scale::Full,
+            interlace_handling: InterlaceHandling::Sparkle,
         }
     }
 
@@ -190,54 +381,9 @@ impl<R: BufRead + Seek> Decoder<R> {
 
     /// Reads all meta data until the first IDAT chunk
     pub fn read_info(mut self) -> Result<Reader<R>, DecodingError> {
-        let info = self.read_header_info()?;
-        let unfiltering_buffer = UnfilteringBuffer::new(info);
-
-        let mut reader = Reader {
-            decoder: self.read_decoder,
-            bpp: BytesPerPixel::One,
-            subframe: SubframeInfo::not_yet_init(),
-            remaining_frames: 0, // Temporary value - fixed below after reading `acTL` and `fcTL`.
-            unfiltering_buffer,
-            transform: self.transform,
-            transform_fn: None,
-            scratch_buffer: Vec::new(),
-            finished: false,
-        };
-
-        // Check if the decoding buffer of a single raw line has a valid size.
-        //
-        // FIXME: this check and the next can be delayed until processing image data. This would
-        // allow usage where only the metadata is processes, or where the image is processed
-        // line-by-line even on targets that can not fit the whole image into their address space.
-        // We should strive for a balance between implementation complexity (still ensure that the
-        // no-overflow preconditions are met for internal calculation) and use possibilities.
-        if reader.info().checked_raw_row_length().is_none() {
-            return Err(DecodingError::LimitsExceeded);
-        }
-
-        // Check if the output buffer has a valid size.
-        //
-        // FIXME: see above and
-        // <https://github.com/image-rs/image-png/pull/608#issuecomment-3003576956>
-        if reader.output_buffer_size().is_none() {
-            return Err(DecodingError::LimitsExceeded);
-        }
-
-        reader.read_until_image_data()?;
-
-        reader.remaining_frames = match reader.info().animation_control.as_ref() {
-            None => 1, // No `acTL` => only expecting `IDAT` frame.
-            Some(animation) => {
-                let mut num_frames = animation.num_frames as usize;
-                if reader.info().frame_control.is_none() {
-                    // No `fcTL` before `IDAT` => `IDAT` is not part of the animation, but
-                    // represents an *extra*, default frame for non-APNG-aware decoders.
-                    num_frames += 1;
-                }
-                num_frames
-            }
-        };
+        self.read_header_info()?;
+        let mut reader = Reader::new(self)?;
+        reader.read_until_first_frame()?;
         Ok(reader)
     }
 
@@ -249,6 +395,108 @@ impl<R: BufRead + Seek> Decoder<R> {
         self.transform = transform;
     }
 
+    /// Set the exponent of the display's transfer function, for [`Transformations::GAMMA`].
+    ///
+    /// The default of 2.2 suits typical displays and the sRGB color space.
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use std::io::BufReader;
+    /// use png::{Decoder, ScaledFloat, Transformations};
+    /// // The image has a gamma of 1.0, i.e. linear samples.
+    /// let mut decoder = Decoder::new(BufReader::new(File::open("tests/pngsuite/g10n0g16.png").unwrap()));
+    /// decoder.set_transformations(Transformations::GAMMA);
+    /// decoder.set_display_gamma(ScaledFloat::new(2.2));
+    /// let reader = decoder.read_info().unwrap();
+    /// ```
+    pub fn set_display_gamma(&mut self, display_gamma: ScaledFloat) {
+        self.transform_options.display_gamma = display_gamma;
+    }
+
+    /// Set the value of the samples added by [`Transformations::FILLER`].
+    ///
+    /// For 8-bit samples only the low byte is used. The default is `0xffff`, i.e. opaque.
+    pub fn set_filler(&mut self, filler: u16) {
+        self.transform_options.filler = filler;
+    }
+
+    /// Set the weights of the colors in the gray value of [`Transformations::RGB_TO_GRAY`].
+    ///
+    /// The default is [`GrayCoefficients::REC_709`], which matches sRGB images.
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use std::io::BufReader;
+    /// use png::{ColorType, Decoder, GrayCoefficients, Transformations};
+    /// let mut decoder = Decoder::new(BufReader::new(File::open("tests/pngsuite/basn2c08.png").unwrap()));
+    /// decoder.set_transformations(Transformations::RGB_TO_GRAY);
+    /// decoder.set_gray_coefficients(GrayCoefficients::REC_601);
+    /// let reader = decoder.read_info().unwrap();
+    /// assert_eq!(reader.output_color_type().0, ColorType::Grayscale);
+    /// ```
+    pub fn set_gray_coefficients(&mut self, coefficients: GrayCoefficients) {
+        self.transform_options.gray_coefficients = coefficients;
+    }
+
+    /// Set the color that [`Transformations::BACKGROUND`] composites images onto, instead of the
+    /// color of their `bKGD` chunk.
+    ///
+    /// The red, green and blue samples have 16 bits and are scaled to the bit depth of the
+    /// output. Grayscale images use the gray value of the color, weighted like
+    /// [`Transformations::RGB_TO_GRAY`].
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use std::io::BufReader;
+    /// use png::{ColorType, Decoder, Transformations};
+    /// // The image has an alpha channel but no `bKGD` chunk.
+    /// let mut decoder = Decoder::new(BufReader::new(File::open("tests/pngsuite/bgan6a08.png").unwrap()));
+    /// decoder.set_transformations(Transformations::BACKGROUND);
+    /// decoder.set_background_color([0xffff, 0xffff, 0xffff]);
+    /// let reader = decoder.read_info().unwrap();
+    /// assert_eq!(reader.output_color_type().0, ColorType::Rgb);
+    /// ```
+    pub fn set_background_color(&mut self, color: [u16; 3]) {
+        self.transform_options.background = Some(color);
+    }
+
+    /// Set the resolution at which [`Reader::next_frame`] decodes frames, e.g. for thumbnails.
+    ///
+    /// Each pixel of a reduced frame is the average of a block of pixels of the full frame, with
+    /// each sample averaged independently. For Adam7 interlaced images the decoder instead only
+    /// decodes the first passes, which contain exactly one pixel of each block, and skips the
+    /// remaining image data without decompressing it.
+    ///
+    /// When the scale is not [`Scale::Full`], `next_frame` expands indexed images and images with
+    /// fewer than 8 bits per sample as with [`Transformations::EXPAND`], and
+    /// [`Reader::output_buffer_size`] accounts for the expanded samples. The other methods of the
+    /// [`Reader`], such as [`Reader::next_row`], [`Reader::next_frame_region`] or
+    /// [`Reader::next_composited_frame`], ignore the scale and only apply the transformations that
+    /// were set.
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use std::io::BufReader;
+    /// use png::{Decoder, Scale};
+    /// let mut decoder = Decoder::new(BufReader::new(File::open("tests/pngsuite/basn2c08.png").unwrap()));
+    /// decoder.set_scale(Scale::Quarter);
+    /// let mut reader = decoder.read_info().unwrap();
+    /// let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+    /// let info = reader.next_frame(&mut buf).unwrap();
+    /// assert_eq!((info.width, info.height), (8, 8));
+    /// ```
+    pub fn set_scale(&mut self, scale: Scale) {
+        self.scale = scale;
+    }
+
+    /// Set how [`Reader::next_frame`] arranges the rows of interlaced frames, see
+    /// [`InterlaceHandling`].
+    ///
+    /// The handling does not apply when a [`Scale`] is set.
+    pub fn set_interlace_handling(&mut self, interlace_handling: InterlaceHandling) {
+        self.interlace_handling = interlace_handling;
+    }
+
     /// Set the decoder to ignore all text chunks while parsing.
     ///
     /// eg.
@@ -279,6 +527,81 @@ impl<R: BufRead + Seek> Decoder<R> {
         self.read_decoder.set_ignore_iccp_chunk(ignore_iccp_chunk);
     }
 
+    /// Set which unrecognized ancillary chunks to keep in [`Info::unknown_chunks`]. By default
+    /// they are discarded.
+    ///
+    /// Chunks after the image data are only read by [`Reader::finish`].
+    ///
+    /// eg.
+    /// ```
+    /// use std::fs::File;
+    /// use std::io::BufReader;
+    /// use png::{chunk::ChunkType, Decoder, UnknownChunkPolicy};
+    /// let mut decoder = Decoder::new(BufReader::new(File::open("tests/pngsuite/basn0g01.png").unwrap()));
+    /// decoder.set_unknown_chunk_policy(UnknownChunkPolicy::Listed(vec![ChunkType(*b"prVt")]));
+    /// let reader = decoder.read_info().unwrap();
+    /// assert!(reader.info().unknown_chunks.is_empty());
+    /// ```
+    pub fn set_unknown_chunk_policy(&mut self, unknown_chunk_policy: UnknownChunkPolicy) {
+        self.read_decoder
+            .set_unknown_chunk_policy(unknown_chunk_policy);
+    }
+
+    /// Set whether to report errors in ancillary chunks instead of skipping those chunks. By
+    /// default they are skipped, as are ancillary chunks with a wrong CRC.
+    ///
+    /// In [`Strictness::Strict`] mode, malformed, misplaced and duplicated chunks are errors, and
+    /// [`Reader::finish`] fails if there is data after the `IEND` chunk.
+    ///
+    /// eg.
+    /// ```
+    /// use std::fs::File;
+    /// use std::io::BufReader;
+    /// use png::{Decoder, Strictness};
+    /// let mut decoder = Decoder::new(BufReader::new(File::open("tests/pngsuite/basn0g01.png").unwrap()));
+    /// decoder.set_strictness(Strictness::Strict);
+    /// let mut reader = decoder.read_info().unwrap();
+    /// let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+    /// reader.next_frame(&mut buf).unwrap();
+    /// assert!(reader.finish().is_ok());
+    /// ```
+    pub fn set_strictness(&mut self, strictness: Strictness) {
+        self.read_decoder.set_strictness(strictness);
+    }
+
+    /// Set the handler of chunks of type `type_`, replacing any previous handler of that type.
+    ///
+    /// The handler is called with the type and the data of each such chunk while it is decoded,
+    /// and decides whether the chunk is processed, skipped or aborts decoding with an error. See
+    /// [`StreamingDecoder::set_chunk_handler`](crate::StreamingDecoder::set_chunk_handler) for
+    /// details.
+    ///
+    /// eg.
+    /// ```
+    /// use std::fs::File;
+    /// use std::io::BufReader;
+    /// use png::{chunk, ChunkHandling, Decoder};
+    /// let mut decoder = Decoder::new(BufReader::new(File::open("tests/pngsuite/ctzn0g04.png").unwrap()));
+    /// decoder.set_chunk_handler(chunk::zTXt, |_, _| ChunkHandling::Abort);
+    /// assert!(decoder.read_info().is_err());
+    /// ```
+    pub fn set_chunk_handler(
+        &mut self,
+        type_: ChunkType,
+        handler: impl FnMut(ChunkType, &[u8]) -> ChunkHandling + Send + Sync + 'static,
+    ) {
+        self.read_decoder.set_chunk_handler(type_, handler);
+    }
+
+    /// Set the handler of all chunks without a handler for their type, see
+    /// [`Decoder::set_chunk_handler`].
+    pub fn set_default_chunk_handler(
+        &mut self,
+        handler: impl FnMut(ChunkType, &[u8]) -> ChunkHandling + Send + Sync + 'static,
+    ) {
+        self.read_decoder.set_default_chunk_handler(handler);
+    }
+
     /// Set the decoder to ignore and not verify the Adler-32 checksum
     /// and CRC code.
     pub fn ignore_checksums(&mut self, ignore_checksums: bool) {
@@ -289,7 +612,7 @@ impl<R: BufRead + Seek> Decoder<R> {
 /// PNG reader (mostly high-level interface)
 ///
 /// Provides a high level that iterates over lines or whole images.
-pub struct Reader<R: BufRead + Seek> {
+pub struct Reader<R: BufRead> {
     decoder: ReadDecoder<R>,
     bpp: BytesPerPixel,
     subframe: SubframeInfo,
@@ -299,6 +622,14 @@ pub struct Reader<R: BufRead + Seek> {
     unfiltering_buffer: UnfilteringBuffer,
     /// Output transformations
     transform: Transformations,
+    /// Parameters of the output transformations
+    transform_options: TransformOptions,
+    /// Resolution of the frames returned by `next_frame`.
+    scale: Scale,
+    /// Averages the rows of a frame that is decoded at a reduced scale.
+    box_filter: Option<BoxFilter>,
+    /// Arrangement of interlaced rows in `next_frame`.
+    interlace_handling: InterlaceHandling,
     /// Function that can transform decompressed, unfiltered rows into final output.
     /// See the `transform.rs` module for more details.
     transform_fn: Option<TransformFn>,
@@ -306,6 +637,11 @@ pub struct Reader<R: BufRead + Seek> {
     /// to a byte slice. In a future version of this library, this buffer will be removed and
     /// `next_row` and `next_interlaced_row` will write directly into a user provided output buffer.
     scratch_buffer: Vec<u8>,
+    /// The rendered animation, created by the first call to `next_composited_frame`.
+    canvas: Option<Canvas>,
+    /// The frame decoded by `next_composited_frame` before it is drawn onto the canvas, reused
+    /// for all frames of the animation.
+    composited_frame: Vec<u8>,
     /// Whether `ImageEnd` was already reached by `fn finish`.
     finished: bool,
 }
@@ -323,9 +659,85 @@ struct SubframeInfo {
     current_interlace_info: Option<InterlaceInfo>,
     interlace_info_iter: InterlaceInfoIter,
     consumed_and_flushed: bool,
+    /// Whether decoding stopped before the last row, e.g. in `next_frame_region`, so that the rest
+    /// of the image data needs to be skipped before the next frame.
+    stopped_early: bool,
 }
 
-impl<R: BufRead + Seek> Reader<R> {
+impl<R: BufRead> Reader<R> {
+    /// Creates a reader from a decoder that has already read the image header.
+    fn new(decoder: Decoder<R>) -> Result<Self, DecodingError> {
+        let Decoder {
+            read_decoder: decoder,
+            transform,
+            transform_options,
+            scale,
+            interlace_handling,
+        } = decoder;
+        let info = decoder.info().unwrap();
+        let unfiltering_buffer = UnfilteringBuffer::new(info);
+
+        let reader = Reader {
+            decoder,
+            bpp: BytesPerPixel::One,
+            subframe: SubframeInfo::not_yet_init(),
+            remaining_frames: 0, // Temporary value - fixed by `read_until_first_frame`.
+            unfiltering_buffer,
+            transform,
+            transform_options,
+            scale,
+            box_filter: None,
+            interlace_handling,
+            transform_fn: None,
+            scratch_buffer: Vec::new(),
+            canvas: None,
+            composited_frame: Vec::new(),
+            finished: false,
+        };
+
+        // Check if the decoding buffer of a single raw line has a valid size.
+        //
+        // FIXME: this check and the next can be delayed until processing image data. This would
+        // allow usage where only the metadata is processes, or where the image is processed
+        // line-by-line even on targets that can not fit the whole image into their address space.
+        // We should strive for a balance between implementation complexity (still ensure that the
+        // no-overflow preconditions are met for internal calculation) and use possibilities.
+        if reader.info().checked_raw_row_length().is_none() {
+            return Err(DecodingError::LimitsExceeded);
+        }
+
+        // Check if the output buffer has a valid size.
+        //
+        // FIXME: see above and
+        // <https://github.com/image-rs/image-png/pull/608#issuecomment-3003576956>
+        if reader.output_buffer_size().is_none() {
+            return Err(DecodingError::LimitsExceeded);
+        }
+
+        Ok(reader)
+    }
+
+    /// Reads all meta data until the first IDAT chunk and determines the number of frames.
+    ///
+    /// Like the other reading methods, this can be retried after an `UnexpectedEof`.
+    fn read_until_first_frame(&mut self) -> Result<(), DecodingError> {
+        self.read_until_image_data()?;
+
+        self.remaining_frames = match self.info().animation_control.as_ref() {
+            None => 1, // No `acTL` => only expecting `IDAT` frame.
+            Some(animation) => {
+                let mut num_frames = animation.num_frames as usize;
+                if self.info().frame_control.is_none() {
+                    // No `fcTL` before `IDAT` => `IDAT` is not part of the animation, but
+                    // represents an *extra*, default frame for non-APNG-aware decoders.
+                    num_frames += 1;
+                }
+                num_frames
+            }
+        };
+        Ok(())
+    }
+
     /// Advances to the start of the next animation frame and
     /// returns a reference to the [`FrameControl`] info that describes it.
     /// Skips and discards the image data of the previous frame if necessary.
@@ -396,18 +808,108 @@ impl<R: BufRead + Seek> Reader<R> {
     /// `png`.
     ///
     /// Output lines will be written in row-major, packed matrix with width and height of the read
-    /// frame (or subframe), all samples are in big endian byte order where this matters.
+    /// frame (or subframe), all samples are in big endian byte order where this matters. Rows of
+    /// interlaced frames are arranged according to the [`InterlaceHandling`] of the [`Decoder`].
     pub fn next_frame(&mut self, buf: &mut [u8]) -> Result<OutputInfo, DecodingError> {
-        if self.remaining_frames == 0 {
+        self.decode_frame(FrameBuffer::Bytes(buf), &mut |_| {})
+    }
+
+    /// Decodes the next frame into `buf` like [`Reader::next_frame`], with one element for each
+    /// 16-bit sample.
+    ///
+    /// The output must have 16 bits per sample, e.g. because the image has 16-bit samples or
+    /// [`Transformations::EXPAND_16`] is set, otherwise this returns an error. `buf` must hold at
+    /// least half of [`Reader::output_buffer_size`] samples. The sizes in the returned
+    /// [`OutputInfo`] are in bytes, like those of `next_frame`, and the samples hold their values
+    /// regardless of [`Transformations::SWAP_ENDIAN`].
+    ///
+    /// As this crate does not reinterpret memory with `unsafe` code, each row is decoded into a
+    /// byte buffer of the reader first and then converted into `buf`. Decoding can be resumed
+    /// like that of `next_frame`.
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use std::io::BufReader;
+    /// use png::{Decoder, Transformations};
+    /// let mut decoder = Decoder::new(BufReader::new(File::open("tests/pngsuite/basn0g08.png").unwrap()));
+    /// decoder.set_transformations(Transformations::EXPAND_16);
+    /// let mut reader = decoder.read_info().unwrap();
+    /// let mut buf = vec![0; reader.output_buffer_size().unwrap() / 2];
+    /// reader.next_frame_u16(&mut buf).unwrap();
+    /// // The 8-bit samples of the image are widened to multiples of 257.
+    /// assert!(buf.iter().all(|&sample| sample % 257 == 0));
+    /// ```
+    pub fn next_frame_u16(&mut self, buf: &mut [u16]) -> Result<OutputInfo, DecodingError> {
+        let (_, bit_depth) = self.output_color_type();
+        if bit_depth != BitDepth::Sixteen {
             return Err(DecodingError::Parameter(
-                ParameterErrorKind::PolledAfterEndOfImage.into(),
+                ParameterErrorKind::SampleBitDepth(bit_depth).into(),
+            ));
+        }
+
+        let required_len = self
+            .output_buffer_size()
+            .ok_or(DecodingError::LimitsExceeded)?;
+        if buf.len() < required_len / 2 {
+            return Err(DecodingError::Parameter(
+                ParameterErrorKind::ImageBufferSize {
+                    expected: required_len / 2,
+                    actual: buf.len(),
+                }
+                .into(),
             ));
-        } else if self.subframe.consumed_and_flushed {
-            // Advance until the next `fdAT`
-            // (along the way we should encounter the fcTL for this frame).
-            self.read_until_image_data()?;
         }
 
+        let little_endian = self.transform.contains(Transformations::SWAP_ENDIAN);
+        self.decode_frame(FrameBuffer::Samples { buf, little_endian }, &mut |_| {})
+    }
+
+    /// Decodes the next frame into `buf` like [`Reader::next_frame`], calling `progress` each time
+    /// rows of the frame have been written.
+    ///
+    /// This lets a viewer paint the frame while its image data is still streaming in. With
+    /// [`InterlaceHandling::Rectangle`] each Adam7 pass refines a complete, blocky version of the
+    /// frame, with [`InterlaceHandling::Sparkle`] the pixels of each pass appear in the gaps
+    /// between those of the previous passes.
+    ///
+    /// If decoding fails with a recoverable error, such as
+    /// [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) for input that is not yet available,
+    /// calling this again with the same buffer continues the frame and only reports the rows
+    /// decoded from then on.
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use std::io::BufReader;
+    /// use png::{Decoder, InterlaceHandling};
+    /// let mut decoder = Decoder::new(BufReader::new(File::open("tests/pngsuite/basi0g08.png").unwrap()));
+    /// decoder.set_interlace_handling(InterlaceHandling::Rectangle);
+    /// let mut reader = decoder.read_info().unwrap();
+    /// let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+    /// let mut complete_passes = 0;
+    /// reader.next_frame_progressive(&mut buf, |progress| {
+    ///     // Each pass ends with a row that reaches the bottom of the image.
+    ///     if progress.rows.end == 32 {
+    ///         complete_passes += 1;
+    ///     }
+    /// }).unwrap();
+    /// assert_eq!(complete_passes, 7);
+    /// ```
+    pub fn next_frame_progressive(
+        &mut self,
+        buf: &mut [u8],
+        mut progress: impl FnMut(FrameProgress<'_>),
+    ) -> Result<OutputInfo, DecodingError> {
+        self.decode_frame(FrameBuffer::Bytes(buf), &mut progress)
+    }
+
+    /// Implementation of [`Reader::next_frame_progressive`] and [`Reader::next_frame_u16`].
+    fn decode_frame(
+        &mut self,
+        mut buf: FrameBuffer<'_>,
+        progress: &mut dyn FnMut(FrameProgress<'_>),
+    ) -> Result<OutputInfo, DecodingError> {
+        self.start_frame()?;
+
         // Note that we only check if the buffer size calculation holds in a call to decoding the
         // frame. Consequently, we can represent the `Info` and frameless decoding even when the
         // target architecture's address space is too small for a frame. However reading the actual
@@ -425,20 +927,19 @@ impl<R: BufRead + Seek> Reader<R> {
             ));
         }
 
-        let (color_type, bit_depth) = self.output_color_type();
-        let output_info = OutputInfo {
-            width: self.subframe.width,
-            height: self.subframe.height,
-            color_type,
-            bit_depth,
-            line_size: self.unguarded_output_line_size(self.subframe.width),
-        };
+        if self.scale != Scale::Full {
+            return self.next_scaled_frame(&mut buf, progress);
+        }
+
+        let output_info = self.frame_output_info();
+        let height = self.subframe.height;
+        buf.truncate(output_info.buffer_size());
 
         if self.info().interlaced {
-            let stride = self.unguarded_output_line_size(self.info().width);
-            let samples = color_type.samples() as u8;
-            let bits_pp = samples * (bit_depth as u8);
-            let expand = crate::adam7::expand_pass;
+            let stride = output_info.line_size;
+            let samples = output_info.color_type.samples() as u8;
+            let bits_pp = samples * (output_info.bit_depth as u8);
+            let interlace_handling = self.interlace_handling;
 
             while let Some(InterlacedRow {
                 data: row,
@@ -448,7 +949,24 @@ impl<R: BufRead + Seek> Reader<R> {
             {
                 // `unwrap` won't panic, because we checked `self.info().interlaced` above.
                 let adam7info = interlace.get_adam7_info().unwrap();
-                expand(buf, stride, row, adam7info, bits_pp);
+                let (_, y) = adam7info.image_position(0);
+                let rows = match interlace_handling {
+                    InterlaceHandling::RawRows => {
+                        let idx = adam7info.row_index(height);
+                        buf.write(idx as usize * stride, row);
+                        idx..idx + 1
+                    }
+                    InterlaceHandling::Rectangle => {
+                        buf.expand_pass(stride, row, adam7info, bits_pp, true);
+                        y..y + u32::from(adam7info.splat_line_repeat(height))
+                    }
+                    InterlaceHandling::Sparkle => {
+                        buf.expand_pass(stride, row, adam7info, bits_pp, false);
+                        y..y + 1
+                    }
+                };
+
+                buf.progress(progress, rows, Some(adam7info.pass));
             }
         } else {
             let current_interlace_info = self.subframe.current_interlace_info.as_ref();
@@ -456,12 +974,23 @@ impl<R: BufRead + Seek> Reader<R> {
                 .map(|info| info.line_number())
                 .unwrap_or(self.subframe.height);
 
-            for row in buf
-                .chunks_exact_mut(output_info.line_size)
-                .take(self.subframe.height as usize)
-                .skip(already_done_rows as usize)
-            {
-                self.next_interlaced_row_impl(self.subframe.rowlen, row)?;
+            let line_size = output_info.line_size;
+            for y in already_done_rows..self.subframe.height {
+                let offset = y as usize * line_size;
+                if let Some(row) = buf.bytes_mut(offset, line_size) {
+                    self.next_interlaced_row_impl(self.subframe.rowlen, row)?;
+                } else {
+                    // Samples are converted from the bytes of the row in the scratch buffer.
+                    let mut row = mem::take(&mut self.scratch_buffer);
+                    row.resize(line_size, 0);
+                    let result = self.next_interlaced_row_impl(self.subframe.rowlen, &mut row);
+                    if result.is_ok() {
+                        buf.write(offset, &row);
+                    }
+                    self.scratch_buffer = row;
+                    result?;
+                }
+                buf.progress(progress, y..y + 1, None);
             }
         }
 
@@ -471,6 +1000,483 @@ impl<R: BufRead + Seek> Reader<R> {
         Ok(output_info)
     }
 
+    /// Whether `next_frame` stacks the raw rows of an interlaced frame, see
+    /// [`InterlaceHandling::RawRows`].
+    fn raw_interlaced_rows(&self) -> bool {
+        self.interlace_handling == InterlaceHandling::RawRows
+            && self.scale == Scale::Full
+            && self.info().interlaced
+    }
+
+    /// Returns the [`OutputInfo`] of the current frame as decoded by [`Reader::next_frame`].
+    fn frame_output_info(&self) -> OutputInfo {
+        let (color_type, bit_depth) = self.output_color_type();
+        let (width, height) = self.scale.apply(self.subframe.width, self.subframe.height);
+        let output_height = if self.raw_interlaced_rows() {
+            adam7::count_interlaced_rows(width, height)
+        } else {
+            height
+        };
+        OutputInfo {
+            width,
+            height: output_height,
+            color_type,
+            bit_depth,
+            line_size: self.unguarded_output_line_size(width),
+        }
+    }
+
+    /// The transformations of a frame decoded at a reduced scale.
+    ///
+    /// Palette indices and packed samples can not be averaged, so they are expanded.
+    fn scaled_transform(&self) -> Transformations {
+        let info = self.info();
+        if self.scale != Scale::Full
+            && (info.color_type == ColorType::Indexed || (info.bit_depth as u8) < 8)
+        {
+            self.transform | Transformations::EXPAND
+        } else {
+            self.transform
+        }
+    }
+
+    /// Decodes the current frame at the reduced resolution of `self.scale` into `buf`.
+    fn next_scaled_frame(
+        &mut self,
+        buf: &mut FrameBuffer<'_>,
+        progress: &mut dyn FnMut(FrameProgress<'_>),
+    ) -> Result<OutputInfo, DecodingError> {
+        let scaled_transform = self.scaled_transform();
+        let transform = mem::replace(&mut self.transform, scaled_transform);
+        let transform_fn = self.transform_fn.take();
+        let output_info = self.frame_output_info();
+        buf.truncate(output_info.buffer_size());
+        let result = self.decode_scaled_frame(buf, &output_info, progress);
+        self.transform = transform;
+        self.transform_fn = transform_fn;
+        result.map(|()| output_info)
+    }
+
+    /// Implementation of [`Reader::next_scaled_frame`] with the transformations of the frame.
+    fn decode_scaled_frame(
+        &mut self,
+        buf: &mut FrameBuffer<'_>,
+        output_info: &OutputInfo,
+        progress: &mut dyn FnMut(FrameProgress<'_>),
+    ) -> Result<(), DecodingError> {
+        let denominator = self.scale.denominator();
+        let line_size = output_info.line_size;
+
+        if self.info().interlaced {
+            let last_pass = self.scale.last_adam7_pass();
+            let pixel_len = output_info
+                .color_type
+                .bytes_per_pixel(output_info.bit_depth);
+
+            while let Some(interlace) = self.subframe.current_interlace_info {
+                // `unwrap` won't panic, because we checked `self.info().interlaced` above.
+                let adam7info = *interlace.get_adam7_info().unwrap();
+                if adam7info.pass > last_pass {
+                    // The later passes are not needed, skip them in `start_frame` or `finish`.
+                    self.subframe.current_interlace_info = None;
+                    self.subframe.stopped_early = true;
+                    return Ok(());
+                }
+
+                let Some(row) = self.next_interlaced_row()? else {
+                    break;
+                };
+                for (idx, pixel) in row.data.chunks_exact(pixel_len).enumerate() {
+                    // Pixels of the early passes lie on the corners of the blocks.
+                    let (x, y) = adam7info.image_position(idx as u32);
+                    let pos = (y / denominator) as usize * line_size
+                        + (x / denominator) as usize * pixel_len;
+                    buf.write(pos, pixel);
+                }
+
+                let (_, y) = adam7info.image_position(0);
+                let rows = y / denominator..y / denominator + 1;
+                buf.progress(progress, rows, Some(adam7info.pass));
+            }
+        } else {
+            let already_done_rows = self
+                .subframe
+                .current_interlace_info
+                .as_ref()
+                .map(|info| info.line_number())
+                .unwrap_or(self.subframe.height);
+            if already_done_rows == 0 || self.box_filter.is_none() {
+                let samples = output_info.color_type.samples();
+                let sixteen = output_info.bit_depth == BitDepth::Sixteen;
+                let little_endian = self.transform.contains(Transformations::SWAP_ENDIAN);
+                let filter = BoxFilter::new(
+                    self.subframe.width,
+                    samples,
+                    sixteen,
+                    little_endian,
+                    self.scale,
+                );
+                self.box_filter = Some(filter);
+            }
+
+            let mut row = mem::take(&mut self.scratch_buffer);
+            row.resize(self.unguarded_output_line_size(self.subframe.width), 0);
+            let result = self.next_box_filtered_rows(buf, line_size, &mut row, progress);
+            self.scratch_buffer = row;
+            result?;
+            self.box_filter = None;
+        }
+
+        self.finish_decoding()
+    }
+
+    /// Decodes the remaining rows of the frame and writes the average of each block of rows into
+    /// `buf`.
+    fn next_box_filtered_rows(
+        &mut self,
+        buf: &mut FrameBuffer<'_>,
+        line_size: usize,
+        row: &mut [u8],
+        progress: &mut dyn FnMut(FrameProgress<'_>),
+    ) -> Result<(), DecodingError> {
+        let denominator = self.scale.denominator();
+        // The averages of a row of samples, before they are converted.
+        let mut output_row = Vec::new();
+        while let Some(interlace) = self.subframe.current_interlace_info {
+            let y = interlace.line_number();
+            self.next_interlaced_row_impl(self.subframe.rowlen, row)?;
+
+            // `unwrap` won't panic, the filter is created by `next_scaled_frame`.
+            let filter = self.box_filter.as_mut().unwrap();
+            filter.add_row(row);
+            if (y + 1) % denominator == 0 || y + 1 == self.subframe.height {
+                let output_y = y / denominator;
+                let offset = output_y as usize * line_size;
+                if let Some(output) = buf.bytes_mut(offset, line_size) {
+                    filter.write_row(output);
+                } else {
+                    output_row.resize(line_size, 0);
+                    filter.write_row(&mut output_row);
+                    buf.write(offset, &output_row);
+                }
+                buf.progress(progress, output_y..output_y + 1, None);
+            }
+        }
+        Ok(())
+    }
+
+    /// Advances to the image data of the next frame, unless the current frame is partially read.
+    fn start_frame(&mut self) -> Result<(), DecodingError> {
+        if self.subframe.stopped_early {
+            self.finish_decoding()?;
+        }
+
+        if self.remaining_frames == 0 {
+            return Err(DecodingError::Parameter(
+                ParameterErrorKind::PolledAfterEndOfImage.into(),
+            ));
+        } else if self.subframe.consumed_and_flushed {
+            // Advance until the next `fdAT`
+            // (along the way we should encounter the fcTL for this frame).
+            self.read_until_image_data()?;
+        }
+        Ok(())
+    }
+
+    /// Decodes the pixels of the next frame that lie within `region` into `buf`.
+    ///
+    /// This works like [`Reader::next_frame`] but `buf` only needs to hold the region, that is
+    /// `height` rows of [`Reader::output_line_size`] for its `width`. The region is relative to
+    /// the frame, which is a subframe for APNG, and must lie within it. The returned
+    /// [`OutputInfo`] describes the region.
+    ///
+    /// All rows above the region still need to be decompressed and unfiltered, but only the
+    /// pixels of the region are transformed. For images that are not interlaced the decoder stops
+    /// after the last row of the region, the remaining image data is only skipped when the next
+    /// frame is requested or in [`Reader::finish`].
+    pub fn next_frame_region(
+        &mut self,
+        region: Region,
+        buf: &mut [u8],
+    ) -> Result<OutputInfo, DecodingError> {
+        self.start_frame()?;
+
+        if !region.fits(self.subframe.width, self.subframe.height) {
+            return Err(DecodingError::Parameter(
+                ParameterErrorKind::RegionOutOfBounds.into(),
+            ));
+        }
+
+        let required_len = self
+            .output_line_size(region.width)
+            .and_then(|line_size| line_size.checked_mul(region.height as usize))
+            .ok_or(DecodingError::LimitsExceeded)?;
+        if buf.len() < required_len {
+            return Err(DecodingError::Parameter(
+                ParameterErrorKind::ImageBufferSize {
+                    expected: required_len,
+                    actual: buf.len(),
+                }
+                .into(),
+            ));
+        }
+
+        let (color_type, bit_depth) = self.output_color_type();
+        let output_info = OutputInfo {
+            width: region.width,
+            height: region.height,
+            color_type,
+            bit_depth,
+            line_size: self.unguarded_output_line_size(region.width),
+        };
+        let bits_pp = color_type.samples() as u8 * bit_depth as u8;
+
+        if self.info().interlaced {
+            self.next_interlaced_region(&region, buf, bits_pp)?;
+            self.finish_decoding()?;
+        } else {
+            let already_done_rows = self
+                .subframe
+                .current_interlace_info
+                .as_ref()
+                .map(|info| info.line_number())
+                .unwrap_or(self.subframe.height);
+
+            for y in already_done_rows..region.y + region.height {
+                if y < region.y {
+                    self.skip_interlaced_row(self.subframe.rowlen)?;
+                } else {
+                    let row = &mut buf[(y - region.y) as usize * output_info.line_size..]
+                        [..output_info.line_size];
+                    let skip = self.next_row_pixels(self.subframe.rowlen, region.columns())?;
+                    let width = region.width as usize;
+                    region::copy_pixels(row, &self.scratch_buffer, skip, width, bits_pp);
+                }
+            }
+
+            if region.y + region.height == self.subframe.height {
+                self.finish_decoding()?;
+            } else {
+                // Stop here, the remaining rows are skipped by `start_frame` or `finish`.
+                self.subframe.current_interlace_info = None;
+                self.subframe.stopped_early = true;
+            }
+        }
+
+        Ok(output_info)
+    }
+
+    /// Decodes the remaining Adam7 passes, copying the pixels within `region` into `buf`.
+    fn next_interlaced_region(
+        &mut self,
+        region: &Region,
+        buf: &mut [u8],
+        bits_pp: u8,
+    ) -> Result<(), DecodingError> {
+        let stride = self.unguarded_output_line_size(region.width);
+        while let Some(interlace) = self.subframe.current_interlace_info {
+            // `unwrap` won't panic, because the caller checked `self.info().interlaced`.
+            let adam7info = *interlace.get_adam7_info().unwrap();
+            if interlace.line_number() == 0 {
+                self.unfiltering_buffer.reset_prev_row();
+            }
+
+            let rowlen = self.info().raw_row_length_from_width(adam7info.samples);
+            let pixels = adam7info.samples_within(region.columns());
+            if region.rows().contains(&adam7info.image_position(0).1) && !pixels.is_empty() {
+                let first = pixels.start - self.next_row_pixels(rowlen, pixels)? as u32;
+                let row = &self.scratch_buffer;
+                region::expand_pass_region(buf, stride, row, first, &adam7info, bits_pp, region);
+            } else {
+                self.skip_interlaced_row(rowlen)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Decodes the next row, which has `rowlen` raw bytes, and transforms only the pixels with
+    /// the indices `pixels` into `scratch_buffer`.
+    ///
+    /// Returns the number of pixels at the start of `scratch_buffer` that precede the first of
+    /// `pixels`. With fewer than 8 bits per pixel, the first and last raw bytes of the pixels may
+    /// contain pixels on either side of them, which are transformed along.
+    fn next_row_pixels(
+        &mut self,
+        rowlen: usize,
+        pixels: Range<u32>,
+    ) -> Result<usize, DecodingError> {
+        self.next_raw_interlaced_row(rowlen)?;
+
+        let raw_bits_pp = self.info().bits_per_pixel();
+        let start = pixels.start as usize * raw_bits_pp / 8;
+        let end = (pixels.end as usize * raw_bits_pp).div_ceil(8);
+        let skip = pixels.start as usize - start * 8 / raw_bits_pp;
+        let samples = ((end - start) * 8 / raw_bits_pp) as u32;
+
+        let mut transformed = mem::take(&mut self.scratch_buffer);
+        transformed.resize(self.unguarded_output_line_size(samples), 0);
+        if self.transform_fn.is_none() {
+            self.transform_fn = Some(create_transform_fn(
+                self.info(),
+                self.transform,
+                &self.transform_options,
+            )?);
+        }
+        let transform_fn = self.transform_fn.as_deref_mut().unwrap();
+        let row = &self.unfiltering_buffer.prev_row()[start..end];
+        transform_fn(row, &mut transformed, self.decoder.info().unwrap());
+        self.scratch_buffer = transformed;
+
+        self.subframe.current_interlace_info = self.subframe.interlace_info_iter.next();
+        Ok(skip)
+    }
+
+    /// Unfilters the next interlaced row without transforming it.
+    fn skip_interlaced_row(&mut self, rowlen: usize) -> Result<(), DecodingError> {
+        self.next_raw_interlaced_row(rowlen)?;
+        self.subframe.current_interlace_info = self.subframe.interlace_info_iter.next();
+        Ok(())
+    }
+
+    /// Decodes the next frame and renders it onto the canvas of the animation, which is written
+    /// into `buf`.
+    ///
+    /// In contrast to [`Reader::next_frame`], which returns the raw subframe, this disposes of the
+    /// previous frame according to its [`DisposeOp`] and then draws the new frame according to its
+    /// [`BlendOp`], in the way that an APNG viewer would. The canvas always covers the whole image
+    /// and `buf` must hold at least [`Reader::composited_buffer_size`] bytes. A default image that
+    /// is not part of the animation is skipped. For images that are not animated, the only frame
+    /// is returned as-is.
+    ///
+    /// The canvas has an alpha channel regardless of the image's color type, see
+    /// [`Reader::composited_color_type`]. Apart from [`Transformations::STRIP_16`] and
+    /// [`Transformations::SCALE_16`] the transformations set on the [`Decoder`] are not applied to
+    /// the canvas.
+    ///
+    /// Frames consumed by other methods, such as [`Reader::next_frame`] or [`Reader::next_row`],
+    /// are not drawn on the canvas, so calls should not be mixed within one animation.
+    pub fn next_composited_frame(
+        &mut self,
+        buf: &mut [u8],
+    ) -> Result<CompositedFrameInfo, DecodingError> {
+        let required_len = self
+            .composited_buffer_size()
+            .ok_or(DecodingError::LimitsExceeded)?;
+        if buf.len() < required_len {
+            return Err(DecodingError::Parameter(
+                ParameterErrorKind::ImageBufferSize {
+                    expected: required_len,
+                    actual: buf.len(),
+                }
+                .into(),
+            ));
+        }
+
+        if self.remaining_frames == 0 {
+            return Err(DecodingError::Parameter(
+                ParameterErrorKind::PolledAfterEndOfImage.into(),
+            ));
+        } else if self.subframe.consumed_and_flushed {
+            self.read_until_image_data()?;
+        }
+
+        // No `fcTL` before `IDAT` => `IDAT` is not part of the animation.
+        if self.info().animation_control.is_some() && self.info().frame_control.is_none() {
+            self.next_frame_info()?;
+        }
+
+        let (width, height) = self.info().size();
+        let (color_type, bit_depth) = self.composited_color_type();
+        let fctl = self.info().frame_control.unwrap_or(FrameControl {
+            sequence_number: 0,
+            width,
+            height,
+            x_offset: 0,
+            y_offset: 0,
+            delay_num: 0,
+            delay_den: 0,
+            dispose_op: DisposeOp::None,
+            blend_op: BlendOp::Source,
+        });
+
+        if self.canvas.is_none() {
+            // One buffer for the canvas and one for the frames that are drawn onto it.
+            self.decoder.reserve_bytes(required_len)?;
+            self.decoder.reserve_bytes(required_len)?;
+            self.canvas = Some(Canvas::new(width, height, color_type, bit_depth));
+            self.composited_frame = vec![0; required_len];
+        }
+
+        // Decode the subframe with an alpha channel, in the format of the canvas.
+        let mut frame = mem::take(&mut self.composited_frame);
+        let composited_transform = self.composited_transform();
+        let transform = mem::replace(&mut self.transform, composited_transform);
+        let transform_fn = self.transform_fn.take();
+        let scale = mem::replace(&mut self.scale, Scale::Full);
+        let interlace_handling = mem::take(&mut self.interlace_handling);
+        let result = self.next_frame(&mut frame);
+        self.transform = transform;
+        self.transform_fn = transform_fn;
+        self.scale = scale;
+        self.interlace_handling = interlace_handling;
+        self.composited_frame = frame;
+        let frame_info = result?;
+
+        let canvas = self.canvas.as_mut().unwrap();
+        canvas.compose(&self.composited_frame[..frame_info.buffer_size()], &fctl);
+        buf[..required_len].copy_from_slice(canvas.buffer());
+
+        Ok(CompositedFrameInfo {
+            output_info: OutputInfo {
+                width,
+                height,
+                color_type,
+                bit_depth,
+                line_size: color_type.raw_row_length_from_width(bit_depth, width) - 1,
+            },
+            delay_num: fctl.delay_num,
+            delay_den: fctl.delay_den,
+        })
+    }
+
+    /// The transformations that decode frames into the format of the canvas.
+    fn composited_transform(&self) -> Transformations {
+        Transformations::EXPAND
+            | Transformations::ALPHA
+            | (self.transform & (Transformations::STRIP_16 | Transformations::SCALE_16))
+    }
+
+    /// Returns the color type and the number of bits per sample of the canvas returned by
+    /// [`Reader::next_composited_frame`].
+    ///
+    /// This is [`ColorType::GrayscaleAlpha`] for grayscale images and [`ColorType::Rgba`]
+    /// otherwise. Samples have 16 bits for 16-bit images, unless [`Transformations::STRIP_16`] or
+    /// [`Transformations::SCALE_16`] is set, and 8 bits in all other cases.
+    pub fn composited_color_type(&self) -> (ColorType, BitDepth) {
+        let color_type = match self.info().color_type {
+            ColorType::Grayscale | ColorType::GrayscaleAlpha => ColorType::GrayscaleAlpha,
+            _ => ColorType::Rgba,
+        };
+        let reduce16 = Transformations::STRIP_16 | Transformations::SCALE_16;
+        let bit_depth = match self.info().bit_depth {
+            BitDepth::Sixteen if !self.transform.intersects(reduce16) => BitDepth::Sixteen,
+            _ => BitDepth::Eight,
+        };
+        (color_type, bit_depth)
+    }
+
+    /// Return the number of bytes required to hold the canvas returned by
+    /// [`Reader::next_composited_frame`].
+    ///
+    /// Returns `None` if the buffer does not fit into the memory space of the machine.
+    pub fn composited_buffer_size(&self) -> Option<usize> {
+        let (width, height) = self.info().size();
+        let (color, depth) = self.composited_color_type();
+        let linelen = color.checked_raw_row_length(depth, width)?.checked_sub(1)?;
+        let imglen = linelen.checked_mul(usize::try_from(height).ok()?)?;
+        (imglen <= isize::MAX as usize).then_some(imglen)
+    }
+
     fn mark_subframe_as_consumed_and_flushed(&mut self) {
         assert!(self.remaining_frames > 0);
         self.remaining_frames -= 1;
@@ -490,6 +1496,7 @@ impl<R: BufRead + Seek> Reader<R> {
             self.decoder.finish_decoding_image_data()?;
             self.mark_subframe_as_consumed_and_flushed();
         }
+        self.subframe.stopped_early = false;
 
         Ok(())
     }
@@ -506,6 +1513,12 @@ impl<R: BufRead + Seek> Reader<R> {
     ///
     /// See also [`Reader::read_row`], which reads into a caller-provided buffer.
     pub fn next_interlaced_row(&mut self) -> Result<Option<InterlacedRow<'_>>, DecodingError> {
+        let interlace = self.read_row_into_scratch_buffer()?;
+        Ok(interlace.map(move |interlace| self.scratch_row(interlace)))
+    }
+
+    /// Reads the next row into `scratch_buffer`, see [`Reader::scratch_row`].
+    fn read_row_into_scratch_buffer(&mut self) -> Result<Option<InterlaceInfo>, DecodingError> {
         let mut output_buffer = mem::take(&mut self.scratch_buffer);
         let max_line_size = self
             .output_line_size(self.info().width)
@@ -513,15 +1526,16 @@ impl<R: BufRead + Seek> Reader<R> {
         output_buffer.resize(max_line_size, 0u8);
         let result = self.read_row(&mut output_buffer);
         self.scratch_buffer = output_buffer;
-        result.map(move |option| {
-            option.map(move |interlace| {
-                let output_line_size = self.output_line_size_for_interlace_info(&interlace);
-                InterlacedRow {
-                    data: &self.scratch_buffer[..output_line_size],
-                    interlace,
-                }
-            })
-        })
+        result
+    }
+
+    /// The row last read by [`Reader::read_row_into_scratch_buffer`].
+    fn scratch_row(&self, interlace: InterlaceInfo) -> InterlacedRow<'_> {
+        let output_line_size = self.output_line_size_for_interlace_info(&interlace);
+        InterlacedRow {
+            data: &self.scratch_buffer[..output_line_size],
+            interlace,
+        }
     }
 
     /// Reads the next row of the image into the provided `output_buffer`.
@@ -599,11 +1613,15 @@ impl<R: BufRead + Seek> Reader<R> {
         // Apply transformations and write resulting data to buffer.
         let transform_fn = {
             if self.transform_fn.is_none() {
-                self.transform_fn = Some(create_transform_fn(self.info(), self.transform)?);
+                self.transform_fn = Some(create_transform_fn(
+                    self.info(),
+                    self.transform,
+                    &self.transform_options,
+                )?);
             }
-            self.transform_fn.as_deref().unwrap()
+            self.transform_fn.as_deref_mut().unwrap()
         };
-        transform_fn(row, output_buffer, self.info());
+        transform_fn(row, output_buffer, self.decoder.info().unwrap());
 
         self.subframe.current_interlace_info = self.subframe.interlace_info_iter.next();
         Ok(())
@@ -611,38 +1629,11 @@ impl<R: BufRead + Seek> Reader<R> {
 
     /// Returns the color type and the number of bits per sample
     /// of the data returned by [`Reader::next_row`] and [`Reader::next_frame`].
+    ///
+    /// At a reduced [`Scale`], `next_frame` may expand the samples further, see
+    /// [`Decoder::set_scale`]. The [`OutputInfo`] it returns has the color type of the frame.
     pub fn output_color_type(&self) -> (ColorType, BitDepth) {
-        use crate::common::ColorType::*;
-        let t = self.transform;
-        let info = self.info();
-        if t == Transformations::IDENTITY {
-            (info.color_type, info.bit_depth)
-        } else {
-            let bits = match info.bit_depth as u8 {
-                16 if t.intersects(Transformations::STRIP_16) => 8,
-                n if n < 8
-                    && (t.contains(Transformations::EXPAND)
-                        || t.contains(Transformations::ALPHA)) =>
-                {
-                    8
-                }
-                n => n,
-            };
-            let color_type =
-                if t.contains(Transformations::EXPAND) || t.contains(Transformations::ALPHA) {
-                    let has_trns = info.trns.is_some() || t.contains(Transformations::ALPHA);
-                    match info.color_type {
-                        Grayscale if has_trns => GrayscaleAlpha,
-                        Rgb if has_trns => Rgba,
-                        Indexed if has_trns => Rgba,
-                        Indexed => Rgb,
-                        ct => ct,
-                    }
-                } else {
-                    info.color_type
-                };
-            (color_type, BitDepth::from_u8(bits).unwrap())
-        }
+        transform::output_color_type(self.info(), self.transform, &self.transform_options)
     }
 
     /// Return the number of bytes required to hold a deinterlaced image frame that is decoded
@@ -652,7 +1643,17 @@ impl<R: BufRead + Seek> Reader<R> {
     /// otherwise returns the byte length in `Some`. The length is smaller than [`isize::MAX`].
     pub fn output_buffer_size(&self) -> Option<usize> {
         let (width, height) = self.info().size();
-        let (color, depth) = self.output_color_type();
+        let (width, height) = self.scale.apply(width, height);
+        let height = if self.raw_interlaced_rows() {
+            adam7::count_interlaced_rows(width, height)
+        } else {
+            height
+        };
+        let (color, depth) = transform::output_color_type(
+            self.info(),
+            self.scaled_transform(),
+            &self.transform_options,
+        );
         // The subtraction should always work, but we do this for consistency. Also note that by
         // calling `checked_raw_row_length` the row buffer is guaranteed to work whereas if we
         // ran other function that didn't include the filter byte that could later fail on an image
@@ -711,6 +1712,7 @@ impl SubframeInfo {
             current_interlace_info: None,
             interlace_info_iter: InterlaceInfoIter::empty(),
             consumed_and_flushed: false,
+            stopped_early: false,
         }
     }
 
@@ -732,6 +1734,7 @@ impl SubframeInfo {
             current_interlace_info,
             interlace_info_iter,
             consumed_and_flushed: false,
+            stopped_early: false,
         }
     }
 }
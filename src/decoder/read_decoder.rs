@@ -1,8 +1,11 @@
-use super::stream::{DecodeOptions, Decoded, DecodingError, FormatErrorInner, StreamingDecoder};
+use super::stream::{
+    ChunkHandling, DecodeOptions, Decoded, DecodingError, FormatErrorInner, StreamingDecoder,
+    Strictness, UnknownChunkPolicy,
+};
 use super::zlib::UnfilterBuf;
 use super::Limits;
 
-use std::io::{BufRead, ErrorKind, Read, Seek};
+use std::io::{BufRead, ErrorKind, Read};
 
 use crate::chunk;
 use crate::common::Info;
@@ -19,13 +22,16 @@ use crate::common::Info;
 pub(crate) struct ReadDecoder<R: Read> {
     reader: R,
     decoder: StreamingDecoder,
+    /// Whether the `IEND` chunk was read.
+    at_end: bool,
 }
 
-impl<R: BufRead + Seek> ReadDecoder<R> {
+impl<R: BufRead> ReadDecoder<R> {
     pub fn new(r: R) -> Self {
         Self {
             reader: r,
             decoder: StreamingDecoder::new(),
+            at_end: false,
         }
     }
 
@@ -33,7 +39,15 @@ impl<R: BufRead + Seek> ReadDecoder<R> {
         let mut decoder = StreamingDecoder::new_with_options(options);
         decoder.limits = Limits::default();
 
-        Self { reader: r, decoder }
+        Self {
+            reader: r,
+            decoder,
+            at_end: false,
+        }
+    }
+
+    pub fn reader_mut(&mut self) -> &mut R {
+        &mut self.reader
     }
 
     pub fn set_limits(&mut self, limits: Limits) {
@@ -52,6 +66,29 @@ impl<R: BufRead + Seek> ReadDecoder<R> {
         self.decoder.set_ignore_iccp_chunk(ignore_iccp_chunk);
     }
 
+    pub fn set_unknown_chunk_policy(&mut self, unknown_chunk_policy: UnknownChunkPolicy) {
+        self.decoder.set_unknown_chunk_policy(unknown_chunk_policy);
+    }
+
+    pub fn set_strictness(&mut self, strictness: Strictness) {
+        self.decoder.set_strictness(strictness);
+    }
+
+    pub fn set_chunk_handler(
+        &mut self,
+        type_: chunk::ChunkType,
+        handler: impl FnMut(chunk::ChunkType, &[u8]) -> ChunkHandling + Send + Sync + 'static,
+    ) {
+        self.decoder.set_chunk_handler(type_, handler);
+    }
+
+    pub fn set_default_chunk_handler(
+        &mut self,
+        handler: impl FnMut(chunk::ChunkType, &[u8]) -> ChunkHandling + Send + Sync + 'static,
+    ) {
+        self.decoder.set_default_chunk_handler(handler);
+    }
+
     pub fn ignore_checksums(&mut self, ignore_checksums: bool) {
         self.decoder.set_ignore_adler32(ignore_checksums);
         self.decoder.set_ignore_crc(ignore_checksums);
@@ -133,11 +170,16 @@ impl<R: BufRead + Seek> ReadDecoder<R> {
         }
     }
 
-    /// Reads until the `IEND` chunk.
+    /// Reads until the `IEND` chunk. In strict mode, also checks that there is no data after it.
     ///
-    /// Prerequisite: `IEND` chunk hasn't been reached yet.
+    /// Prerequisite: None (idempotent once the `IEND` chunk is reached).
     pub fn read_until_end_of_input(&mut self) -> Result<(), DecodingError> {
-        while !matches!(self.decode_next(None)?, Decoded::ChunkComplete(chunk::IEND)) {}
+        while !self.at_end {
+            self.at_end = matches!(self.decode_next(None)?, Decoded::ChunkComplete(chunk::IEND));
+        }
+        if self.decoder.strictness() == Strictness::Strict && !self.reader.fill_buf()?.is_empty() {
+            return Err(DecodingError::Format(FormatErrorInner::TrailingData.into()));
+        }
         Ok(())
     }
 
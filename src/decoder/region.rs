@@ -0,0 +1,140 @@
+//! Decoding of a rectangular part of a frame.
+//!
+//! See [`Reader::next_frame_region`](super::Reader::next_frame_region).
+
+use std::ops::Range;
+
+use crate::adam7::Adam7Info;
+
+/// A rectangle within a frame, in pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    /// The left edge of the region.
+    pub x: u32,
+    /// The top edge of the region.
+    pub y: u32,
+    /// The width of the region.
+    pub width: u32,
+    /// The height of the region.
+    pub height: u32,
+}
+
+impl Region {
+    /// Whether the region is not empty and lies within a frame of the given size.
+    pub(crate) fn fits(&self, width: u32, height: u32) -> bool {
+        self.width > 0
+            && self.height > 0
+            && self
+                .x
+                .checked_add(self.width)
+                .is_some_and(|end| end <= width)
+            && self
+                .y
+                .checked_add(self.height)
+                .is_some_and(|end| end <= height)
+    }
+
+    pub(crate) fn columns(&self) -> Range<u32> {
+        self.x..self.x + self.width
+    }
+
+    pub(crate) fn rows(&self) -> Range<u32> {
+        self.y..self.y + self.height
+    }
+}
+
+/// Copies the pixel with index `src_idx` in `src` to the pixel with index `dst_idx` in `dst`.
+///
+/// Sub-byte pixels are masked into place, the other bits of `dst` are preserved.
+fn copy_pixel(dst: &mut [u8], dst_idx: usize, src: &[u8], src_idx: usize, bits_pp: u8) {
+    if bits_pp >= 8 {
+        let len = usize::from(bits_pp / 8);
+        dst[dst_idx * len..][..len].copy_from_slice(&src[src_idx * len..][..len]);
+    } else {
+        let bits = usize::from(bits_pp);
+        let mask = (1u8 << bits_pp) - 1;
+        let src_shift = 8 - bits - (src_idx * bits) % 8;
+        let px = (src[src_idx * bits / 8] >> src_shift) & mask;
+        let dst_shift = 8 - bits - (dst_idx * bits) % 8;
+        let byte = &mut dst[dst_idx * bits / 8];
+        *byte = (*byte & !(mask << dst_shift)) | (px << dst_shift);
+    }
+}
+
+/// Copies `count` pixels from `src`, starting with the pixel with index `skip`, to the start of
+/// `dst`.
+pub(crate) fn copy_pixels(dst: &mut [u8], src: &[u8], skip: usize, count: usize, bits_pp: u8) {
+    if bits_pp % 8 == 0 {
+        let bytes_pp = usize::from(bits_pp / 8);
+        dst[..count * bytes_pp].copy_from_slice(&src[skip * bytes_pp..][..count * bytes_pp]);
+    } else {
+        for idx in 0..count {
+            copy_pixel(dst, idx, src, skip + idx, bits_pp);
+        }
+    }
+}
+
+/// Copies the pixels of an interlaced row that lie within `region` into `img`, which holds just
+/// the pixels of the region.
+///
+/// The `interlaced_row` only needs to hold the pixels of the pass from the one with index `first`
+/// up to the last one within the region.
+///
+/// This is the equivalent of [`expand_pass`](crate::expand_interlaced_row) for regions.
+pub(crate) fn expand_pass_region(
+    img: &mut [u8],
+    img_row_stride: usize,
+    interlaced_row: &[u8],
+    first: u32,
+    interlace_info: &Adam7Info,
+    bits_per_pixel: u8,
+    region: &Region,
+) {
+    let (_, y) = interlace_info.image_position(0);
+    if !region.rows().contains(&y) {
+        return;
+    }
+
+    let img_row = &mut img[(y - region.y) as usize * img_row_stride..];
+    for idx in interlace_info.samples_within(region.columns()) {
+        let (x, _) = interlace_info.image_position(idx);
+        let (dst_idx, src_idx) = ((x - region.x) as usize, (idx - first) as usize);
+        copy_pixel(img_row, dst_idx, interlaced_row, src_idx, bits_per_pixel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copy_subbyte_pixels() {
+        let src = [0b1011_0001, 0b1100_0000];
+        let mut dst = [0b1111_1111];
+        copy_pixels(&mut dst, &src, 3, 4, 2);
+        assert_eq!(dst, [0b0111_0000]);
+
+        let mut dst = [0xff, 0xff];
+        copy_pixels(&mut dst, &src, 5, 3, 1);
+        assert_eq!(dst, [0b0011_1111, 0xff]);
+    }
+
+    #[test]
+    fn region_fits() {
+        let region = Region {
+            x: 2,
+            y: 3,
+            width: 4,
+            height: 5,
+        };
+        assert!(region.fits(6, 8));
+        assert!(!region.fits(5, 8));
+        assert!(!region.fits(6, 7));
+        assert!(!Region { width: 0, ..region }.fits(6, 8));
+        assert!(!Region {
+            x: u32::MAX,
+            ..region
+        }
+        .fits(u32::MAX, 8));
+    }
+}
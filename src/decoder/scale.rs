@@ -0,0 +1,295 @@
+//! Decoding frames at a reduced resolution.
+//!
+//! See [`Decoder::set_scale`](super::Decoder::set_scale).
+
+/// The resolution at which [`Reader::next_frame`](super::Reader::next_frame) decodes frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Scale {
+    /// Decode at the original resolution.
+    #[default]
+    Full,
+    /// Divide width and height by 2.
+    Half,
+    /// Divide width and height by 4.
+    Quarter,
+    /// Divide width and height by 8.
+    Eighth,
+}
+
+impl Scale {
+    /// The number by which the width and height are divided.
+    pub fn denominator(self) -> u32 {
+        match self {
+            Scale::Full => 1,
+            Scale::Half => 2,
+            Scale::Quarter => 4,
+            Scale::Eighth => 8,
+        }
+    }
+
+    /// The size of a frame of `width` × `height` pixels at this scale.
+    ///
+    /// Partial blocks of pixels at the right and bottom edges are kept, so the size is rounded up.
+    pub fn apply(self, width: u32, height: u32) -> (u32, u32) {
+        let denominator = self.denominator();
+        (width.div_ceil(denominator), height.div_ceil(denominator))
+    }
+
+    /// The last Adam7 pass that is needed to sample every pixel of the reduced image.
+    ///
+    /// Pass 1 contains every 8th pixel of every 8th line, passes 1 to 3 every 4th pixel of every
+    /// 4th line and passes 1 to 5 every 2nd pixel of every 2nd line.
+    pub(crate) fn last_adam7_pass(self) -> u8 {
+        match self {
+            Scale::Full => 7,
+            Scale::Half => 5,
+            Scale::Quarter => 3,
+            Scale::Eighth => 1,
+        }
+    }
+}
+
+/// Averages blocks of pixels of consecutive rows.
+///
+/// The rows are in the output format of the reader, with 8 or 16 bits per sample.
+pub(crate) struct BoxFilter {
+    /// The number of pixels of an input row.
+    width: usize,
+    samples: usize,
+    sixteen: bool,
+    /// Whether 16-bit samples are little-endian, see `Transformations::SWAP_ENDIAN`.
+    little_endian: bool,
+    denominator: usize,
+    /// The sum of each sample of each output pixel, over the rows added so far.
+    sums: Vec<u32>,
+    rows: usize,
+}
+
+impl BoxFilter {
+    pub(crate) fn new(
+        width: u32,
+        samples: usize,
+        sixteen: bool,
+        little_endian: bool,
+        scale: Scale,
+    ) -> Self {
+        let (out_width, _) = scale.apply(width, 1);
+        BoxFilter {
+            width: width as usize,
+            samples,
+            sixteen,
+            little_endian,
+            denominator: scale.denominator() as usize,
+            sums: vec![0; out_width as usize * samples],
+            rows: 0,
+        }
+    }
+
+    pub(crate) fn add_row(&mut self, row: &[u8]) {
+        let pixel_len = self.samples << usize::from(self.sixteen);
+        let pixels = row.chunks_exact(pixel_len).take(self.width);
+        for (x, pixel) in pixels.enumerate() {
+            let sums = &mut self.sums[x / self.denominator * self.samples..][..self.samples];
+            if self.sixteen {
+                for (sum, sample) in sums.iter_mut().zip(pixel.chunks_exact(2)) {
+                    let bytes = [sample[0], sample[1]];
+                    *sum += u32::from(if self.little_endian {
+                        u16::from_le_bytes(bytes)
+                    } else {
+                        u16::from_be_bytes(bytes)
+                    });
+                }
+            } else {
+                for (sum, &sample) in sums.iter_mut().zip(pixel) {
+                    *sum += u32::from(sample);
+                }
+            }
+        }
+        self.rows += 1;
+    }
+
+    /// Writes the averages of the rows added since the last call into `output` and starts over.
+    pub(crate) fn write_row(&mut self, output: &mut [u8]) {
+        let samples = self.samples;
+        for (x, sums) in self.sums.chunks_exact_mut(samples).enumerate() {
+            let columns = self.denominator.min(self.width - x * self.denominator);
+            let count = (columns * self.rows) as u32;
+            for (idx, sum) in sums.iter_mut().enumerate() {
+                let average = (*sum + count / 2) / count;
+                if self.sixteen {
+                    let pos = 2 * (x * samples + idx);
+                    let bytes = if self.little_endian {
+                        (average as u16).to_le_bytes()
+                    } else {
+                        (average as u16).to_be_bytes()
+                    };
+                    output[pos..][..2].copy_from_slice(&bytes);
+                } else {
+                    output[x * samples + idx] = average as u8;
+                }
+                *sum = 0;
+            }
+        }
+        self.rows = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn box_filter_partial_blocks() {
+        // 3 × 3 grayscale pixels, at half the size the right and bottom blocks are partial.
+        let mut filter = BoxFilter::new(3, 1, false, false, Scale::Half);
+        let mut output = [0; 2];
+        filter.add_row(&[0, 10, 20]);
+        filter.add_row(&[30, 40, 51]);
+        filter.write_row(&mut output);
+        assert_eq!(output, [20, 36]);
+        filter.add_row(&[60, 70, 80]);
+        filter.write_row(&mut output);
+        assert_eq!(output, [65, 80]);
+
+        let mut filter = BoxFilter::new(2, 1, true, false, Scale::Half);
+        let mut output = [0; 2];
+        filter.add_row(&[0xff, 0xff, 0x00, 0x00]);
+        filter.write_row(&mut output);
+        assert_eq!(output, [0x80, 0x00]);
+
+        let mut filter = BoxFilter::new(2, 1, true, true, Scale::Half);
+        filter.add_row(&[0x00, 0x01, 0x00, 0x00]);
+        filter.write_row(&mut output);
+        assert_eq!(output, [0x80, 0x00]);
+    }
+
+    #[test]
+    fn scale_apply() {
+        assert_eq!(Scale::Full.apply(5, 3), (5, 3));
+        assert_eq!(Scale::Half.apply(5, 3), (3, 2));
+        assert_eq!(Scale::Eighth.apply(17, 8), (3, 1));
+    }
+
+    #[test]
+    fn scaled_frame_box_filter() {
+        use crate::decoder::test_utils::{decode_pngsuite, decode_pngsuite_with, samples};
+        use crate::Transformations;
+
+        for name in [
+            "basn0g01", "basn0g16", "basn2c08", "basn3p02", "basn4a08", "basn6a16", "s07n3p02",
+            "s35n3p04",
+        ] {
+            let (full, full_info) = decode_pngsuite(name, Transformations::EXPAND);
+            let full = samples(&full, &full_info);
+            let channels = full_info.color_type.samples();
+            let width = full_info.width as usize;
+
+            for scale in [Scale::Half, Scale::Quarter, Scale::Eighth] {
+                let d = scale.denominator();
+                let (scaled, info) =
+                    decode_pngsuite_with(name, Transformations::EXPAND, |decoder| {
+                        decoder.set_scale(scale)
+                    });
+                assert_eq!(
+                    (info.width, info.height),
+                    scale.apply(full_info.width, full_info.height)
+                );
+                assert_eq!(
+                    (info.color_type, info.bit_depth),
+                    (full_info.color_type, full_info.bit_depth)
+                );
+
+                let scaled = samples(&scaled, &info);
+                for (oy, ox, c) in (0..info.height).flat_map(|oy| {
+                    (0..info.width).flat_map(move |ox| (0..channels).map(move |c| (oy, ox, c)))
+                }) {
+                    let (xs, ys) = (
+                        ox * d..(ox * d + d).min(full_info.width),
+                        oy * d..(oy * d + d).min(full_info.height),
+                    );
+                    let count = xs.len() as u32 * ys.len() as u32;
+                    let sum: u32 = ys
+                        .flat_map(|y| xs.clone().map(move |x| (x, y)))
+                        .map(|(x, y)| full[(y as usize * width + x as usize) * channels + c])
+                        .sum();
+                    let pos = (oy * info.width + ox) as usize * channels + c;
+                    assert_eq!(
+                        scaled[pos],
+                        (sum + count / 2) / count,
+                        "{} {:?} ({}, {})",
+                        name,
+                        scale,
+                        ox,
+                        oy
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn scaled_frame_interlaced() {
+        use crate::decoder::test_utils::{decode_pngsuite, decode_pngsuite_with};
+        use crate::Transformations;
+
+        for name in [
+            "basi0g01", "basi0g16", "basi2c08", "basi3p02", "basi6a16", "s07i3p02", "s35i3p04",
+        ] {
+            let (full, full_info) = decode_pngsuite(name, Transformations::EXPAND);
+            let pixel_len = full_info.color_type.bytes_per_pixel(full_info.bit_depth);
+
+            for scale in [Scale::Half, Scale::Quarter, Scale::Eighth] {
+                let d = scale.denominator() as usize;
+                let (scaled, info) =
+                    decode_pngsuite_with(name, Transformations::EXPAND, |decoder| {
+                        decoder.set_scale(scale)
+                    });
+                assert_eq!(
+                    (info.width, info.height),
+                    scale.apply(full_info.width, full_info.height)
+                );
+
+                // The reduced image is an exact subsample of the full one.
+                for oy in 0..info.height as usize {
+                    for ox in 0..info.width as usize {
+                        let expected =
+                            &full[oy * d * full_info.line_size + ox * d * pixel_len..][..pixel_len];
+                        let actual = &scaled[oy * info.line_size + ox * pixel_len..][..pixel_len];
+                        assert_eq!(actual, expected, "{} {:?} ({}, {})", name, scale, ox, oy);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn scale_keeps_rows() {
+        use crate::decoder::test_utils::pngsuite_decoder;
+        use crate::ColorType;
+
+        // Only frames are expanded at a reduced scale, rows keep the palette indices.
+        let reader = |scale| {
+            let mut decoder = pngsuite_decoder("basn3p02");
+            decoder.set_scale(scale);
+            decoder.read_info().unwrap()
+        };
+        let decode_rows = |scale| {
+            let mut reader = reader(scale);
+            assert_eq!(reader.output_color_type().0, ColorType::Indexed);
+            let mut rows = Vec::new();
+            while let Some(row) = reader.next_row().unwrap() {
+                rows.extend_from_slice(row.data());
+            }
+            rows
+        };
+        let rows = decode_rows(Scale::Full);
+        assert_eq!(rows.len(), 32 * 32 / 4);
+        assert_eq!(decode_rows(Scale::Half), rows);
+
+        let mut reader = reader(Scale::Half);
+        let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!(info.color_type, ColorType::Rgb);
+        assert_eq!(buf.len(), info.buffer_size());
+    }
+}
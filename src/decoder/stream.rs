@@ -1,8 +1,9 @@
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::error;
 use std::fmt;
 use std::io;
-use std::{borrow::Cow, cmp::min};
+use std::{borrow::Cow, cmp::min, mem};
 
 use crc32fast::Hasher as Crc32;
 
@@ -11,11 +12,13 @@ use super::zlib::ZlibStream;
 use crate::chunk::is_critical;
 use crate::chunk::{self, ChunkType, IDAT, IEND, IHDR};
 use crate::common::{
-    AnimationControl, BitDepth, BlendOp, ColorType, ContentLightLevelInfo, DisposeOp, FrameControl,
-    Info, MasteringDisplayColorVolume, ParameterError, ParameterErrorKind, PixelDimensions,
-    ScaledFloat, SourceChromaticities, Unit,
+    AnimationControl, BitDepth, BlendOp, CalibrationEquation, ChunkLocation, ColorType,
+    ContentLightLevelInfo, DisposeOp, FrameControl, GifApplicationExtension, GifGraphicControl,
+    ImageOffset, Info, MasteringDisplayColorVolume, OffsetUnit, ParameterError, ParameterErrorKind,
+    PhysicalScale, PixelCalibration, PixelDimensions, ScaleUnit, ScaledFloat, SourceChromaticities,
+    StereoLayout, SuggestedPalette, SuggestedPaletteEntry, Time, Unit, UnknownChunk,
 };
-use crate::text_metadata::{ITXtChunk, TEXtChunk, TextDecodingError, ZTXtChunk};
+use crate::text_metadata::{decode_iso_8859_1, ITXtChunk, TEXtChunk, TextDecodingError, ZTXtChunk};
 use crate::traits::ReadBytesExt;
 use crate::{CodingIndependentCodePoints, Limits};
 
@@ -95,8 +98,8 @@ pub enum Decoded {
     /// an invalid CRC.
     BadAncillaryChunk(ChunkType),
 
-    /// Skipped an ancillary chunk because it was unrecognized or the decoder was configured to skip
-    /// this type of chunk.
+    /// Skipped an ancillary chunk because it was unrecognized, the decoder was configured to skip
+    /// this type of chunk, or its chunk handler returned [`ChunkHandling::Skip`].
     SkippedAncillaryChunk(ChunkType),
 
     /// Decoded raw image data.
@@ -238,10 +241,23 @@ pub(crate) enum FormatErrorInner {
     InvalidBitDepth(u8),
     InvalidColorType(u8),
     InvalidDisposeOp(u8),
+    /// The acTL chunk declares zero frames, only reported in strict mode.
+    ZeroAnimationFrames,
     InvalidBlendOp(u8),
     InvalidUnit(u8),
+    InvalidTime,
+    InvalidCalibrationEquation(u8),
+    /// The pCAL chunk has an empty original range or the wrong number of parameters.
+    InvalidCalibration,
+    InvalidStereoMode(u8),
+    /// A number of the pCAL or sCAL chunk is malformed, or out of range.
+    InvalidAsciiFloat {
+        kind: ChunkType,
+    },
     /// The rendering intent of the sRGB chunk is invalid.
     InvalidSrgbRenderingIntent(u8),
+    /// There is data after the IEND chunk, only reported in strict mode.
+    TrailingData,
     UnknownCompressionMethod(u8),
     UnknownFilterMethod(u8),
     UnknownInterlaceMethod(u8),
@@ -278,6 +294,10 @@ pub(crate) enum FormatErrorInner {
         /// The type of the unrecognized critical chunk.
         type_str: ChunkType,
     },
+    /// A chunk handler returned [`ChunkHandling::Abort`].
+    AbortedByChunkHandler {
+        kind: ChunkType,
+    },
     BadGammaValue,
 }
 
@@ -366,9 +386,23 @@ impl fmt::Display for FormatError {
             InvalidBitDepth(nr) => write!(fmt, "Invalid bit depth {}.", nr),
             InvalidColorType(nr) => write!(fmt, "Invalid color type {}.", nr),
             InvalidDisposeOp(nr) => write!(fmt, "Invalid dispose op {}.", nr),
+            ZeroAnimationFrames => write!(fmt, "The acTL chunk declares zero frames."),
             InvalidBlendOp(nr) => write!(fmt, "Invalid blend op {}.", nr),
             InvalidUnit(nr) => write!(fmt, "Invalid physical pixel size unit {}.", nr),
+            InvalidTime => write!(fmt, "Invalid date or time of day in tIME chunk."),
+            InvalidCalibrationEquation(nr) => {
+                write!(fmt, "Invalid pixel calibration equation type {}.", nr)
+            }
+            InvalidCalibration => write!(
+                fmt,
+                "Invalid original range or number of parameters in pCAL chunk."
+            ),
+            InvalidStereoMode(nr) => write!(fmt, "Invalid stereo mode {}.", nr),
+            InvalidAsciiFloat { kind } => {
+                write!(fmt, "Invalid floating-point number in {:?} chunk.", kind)
+            }
             InvalidSrgbRenderingIntent(nr) => write!(fmt, "Invalid sRGB rendering intent {}.", nr),
+            TrailingData => write!(fmt, "Unexpected data after IEND chunk."),
             UnknownCompressionMethod(nr) => write!(fmt, "Unknown compression method {}.", nr),
             UnknownFilterMethod(nr) => write!(fmt, "Unknown filter method {}.", nr),
             UnknownInterlaceMethod(nr) => write!(fmt, "Unknown interlace method {}.", nr),
@@ -421,6 +455,9 @@ impl fmt::Display for FormatError {
             UnrecognizedCriticalChunk { type_str } => {
                 write!(fmt, "Unrecognized critical chunk: {:?}", type_str)
             }
+            AbortedByChunkHandler { kind } => {
+                write!(fmt, "Decoding aborted by the handler of a {:?} chunk", kind)
+            }
             BadGammaValue => write!(fmt, "Bad gamma value."),
         }
     }
@@ -461,6 +498,62 @@ impl From<TextDecodingError> for DecodingError {
     }
 }
 
+/// Which unrecognized ancillary chunks the decoder keeps in [`Info::unknown_chunks`].
+///
+/// Kept chunks count towards the [`Limits`] of the decoder.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum UnknownChunkPolicy {
+    /// Skip all unknown chunks.
+    #[default]
+    Discard,
+    /// Keep unknown chunks that are safe to copy, see [`chunk::safe_to_copy`]. Other chunks may
+    /// depend on the image data and become invalid if it is modified.
+    SafeToCopy,
+    /// Keep all unknown chunks.
+    All,
+    /// Keep unknown chunks of the listed types.
+    Listed(Vec<ChunkType>),
+}
+
+impl UnknownChunkPolicy {
+    fn keeps(&self, type_str: ChunkType) -> bool {
+        match self {
+            UnknownChunkPolicy::Discard => false,
+            UnknownChunkPolicy::SafeToCopy => chunk::safe_to_copy(type_str),
+            UnknownChunkPolicy::All => true,
+            UnknownChunkPolicy::Listed(types) => types.contains(&type_str),
+        }
+    }
+}
+
+/// How the decoder treats violations of the specification that don't prevent decoding the image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Strictness {
+    /// Skip ancillary chunks that are malformed, misplaced, duplicated or have a wrong CRC, and
+    /// ignore data after the `IEND` chunk.
+    #[default]
+    Lenient,
+    /// Report all of these as errors. Use this to validate images rather than to display them.
+    Strict,
+}
+
+/// What the decoder does with a chunk after its chunk handler has seen it, see
+/// [`StreamingDecoder::set_chunk_handler`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChunkHandling {
+    /// Process the chunk as if there was no handler.
+    Continue,
+    /// Skip the chunk, as if it was unrecognized. Critical chunks and the `acTL` and `fcTL`
+    /// chunks that structure an animation can't be skipped, they are processed as with
+    /// `Continue`.
+    Skip,
+    /// Stop decoding with an error.
+    Abort,
+}
+
+/// A chunk handler, called with the type and the data of a chunk.
+type ChunkHandler = Box<dyn FnMut(ChunkType, &[u8]) -> ChunkHandling + Send + Sync>;
+
 /// Decoder configuration options
 #[derive(Clone)]
 pub struct DecodeOptions {
@@ -469,6 +562,8 @@ pub struct DecodeOptions {
     ignore_text_chunk: bool,
     ignore_iccp_chunk: bool,
     skip_ancillary_crc_failures: bool,
+    unknown_chunk_policy: UnknownChunkPolicy,
+    strictness: Strictness,
 }
 
 impl Default for DecodeOptions {
@@ -479,6 +574,8 @@ impl Default for DecodeOptions {
             ignore_text_chunk: false,
             ignore_iccp_chunk: false,
             skip_ancillary_crc_failures: true,
+            unknown_chunk_policy: UnknownChunkPolicy::Discard,
+            strictness: Strictness::Lenient,
         }
     }
 }
@@ -525,6 +622,22 @@ impl DecodeOptions {
     pub fn set_skip_ancillary_crc_failures(&mut self, skip_ancillary_crc_failures: bool) {
         self.skip_ancillary_crc_failures = skip_ancillary_crc_failures;
     }
+
+    /// Which unrecognized ancillary chunks to keep in [`Info::unknown_chunks`].
+    ///
+    /// Defaults to [`UnknownChunkPolicy::Discard`].
+    pub fn set_unknown_chunk_policy(&mut self, unknown_chunk_policy: UnknownChunkPolicy) {
+        self.unknown_chunk_policy = unknown_chunk_policy;
+    }
+
+    /// Whether to report errors in ancillary chunks instead of skipping those chunks.
+    ///
+    /// [`Strictness::Strict`] overrides [`DecodeOptions::set_skip_ancillary_crc_failures`].
+    ///
+    /// Defaults to [`Strictness::Lenient`].
+    pub fn set_strictness(&mut self, strictness: Strictness) {
+        self.strictness = strictness;
+    }
 }
 
 /// PNG StreamingDecoder (low-level interface)
@@ -555,6 +668,10 @@ pub struct StreamingDecoder {
     have_iccp: bool,
     decode_options: DecodeOptions,
     pub(crate) limits: Limits,
+    /// The handlers of chunks of a specific type.
+    chunk_handlers: HashMap<ChunkType, ChunkHandler>,
+    /// The handler of chunks without a handler for their type.
+    default_chunk_handler: Option<ChunkHandler>,
 }
 
 struct ChunkState {
@@ -578,6 +695,8 @@ struct ChunkState {
 #[derive(Debug, PartialEq)]
 enum ChunkAction {
     Process,
+    /// Read the chunk for its chunk handler only, then skip it.
+    Inspect,
     Skip,
     Reject,
 }
@@ -612,6 +731,8 @@ impl StreamingDecoder {
             ready_for_fdat_chunks: false,
             decode_options,
             limits: Limits { bytes: usize::MAX },
+            chunk_handlers: HashMap::new(),
+            default_chunk_handler: None,
         }
     }
 
@@ -672,6 +793,78 @@ impl StreamingDecoder {
             .set_skip_ancillary_crc_failures(skip_ancillary_crc_failures)
     }
 
+    /// Which unrecognized ancillary chunks to keep in [`Info::unknown_chunks`].
+    ///
+    /// Defaults to [`UnknownChunkPolicy::Discard`].
+    pub fn set_unknown_chunk_policy(&mut self, unknown_chunk_policy: UnknownChunkPolicy) {
+        self.decode_options
+            .set_unknown_chunk_policy(unknown_chunk_policy)
+    }
+
+    /// Return how strictly the decoder follows the specification.
+    pub fn strictness(&self) -> Strictness {
+        self.decode_options.strictness
+    }
+
+    /// Whether to report errors in ancillary chunks instead of skipping those chunks.
+    ///
+    /// Data after the `IEND` chunk is never passed to this decoder, so callers check for it
+    /// themselves.
+    ///
+    /// Defaults to [`Strictness::Lenient`].
+    pub fn set_strictness(&mut self, strictness: Strictness) {
+        self.decode_options.set_strictness(strictness)
+    }
+
+    /// Sets the handler of chunks of type `type_`, replacing any previous handler of that type.
+    ///
+    /// The handler is called with the type and the complete data of each such chunk once its CRC
+    /// has been checked, before the chunk is parsed and [`Decoded::ChunkComplete`] is returned.
+    /// Its [`ChunkHandling`] decides whether the chunk is processed, skipped or aborts decoding.
+    /// Unrecognized and ignored chunks are read for the handler as well, but `IDAT` and `fdAT`
+    /// chunks are streamed and never passed to handlers.
+    pub fn set_chunk_handler(
+        &mut self,
+        type_: ChunkType,
+        handler: impl FnMut(ChunkType, &[u8]) -> ChunkHandling + Send + Sync + 'static,
+    ) {
+        self.chunk_handlers.insert(type_, Box::new(handler));
+    }
+
+    /// Sets the handler of all chunks without a handler for their type, see
+    /// [`StreamingDecoder::set_chunk_handler`].
+    pub fn set_default_chunk_handler(
+        &mut self,
+        handler: impl FnMut(ChunkType, &[u8]) -> ChunkHandling + Send + Sync + 'static,
+    ) {
+        self.default_chunk_handler = Some(Box::new(handler));
+    }
+
+    fn has_chunk_handler(&self, type_str: ChunkType) -> bool {
+        self.default_chunk_handler.is_some() || self.chunk_handlers.contains_key(&type_str)
+    }
+
+    /// Passes the current chunk to its handler, if any.
+    fn handle_chunk(&mut self, type_str: ChunkType) -> Result<ChunkHandling, DecodingError> {
+        // Image data is streamed, its chunks are never complete.
+        if type_str == IDAT || type_str == chunk::fdAT {
+            return Ok(ChunkHandling::Continue);
+        }
+        let handler = match self.chunk_handlers.get_mut(&type_str) {
+            Some(handler) => handler,
+            None => match &mut self.default_chunk_handler {
+                Some(handler) => handler,
+                None => return Ok(ChunkHandling::Continue),
+            },
+        };
+        match handler(type_str, &self.current_chunk.raw_bytes) {
+            ChunkHandling::Abort => Err(DecodingError::Format(
+                FormatErrorInner::AbortedByChunkHandler { kind: type_str }.into(),
+            )),
+            handling => Ok(handling),
+        }
+    }
+
     /// Low level StreamingDecoder interface.
     ///
     /// Allows to stream partial data to the encoder. Returns a tuple containing the bytes that have
@@ -780,7 +973,7 @@ impl StreamingDecoder {
                         crc.update(buf);
                     }
 
-                    if *action == ChunkAction::Process {
+                    if matches!(action, ChunkAction::Process | ChunkAction::Inspect) {
                         if raw_bytes.len() == raw_bytes.capacity() {
                             if self.limits.bytes == 0 {
                                 return Err(DecodingError::LimitsExceeded);
@@ -951,13 +1144,28 @@ impl StreamingDecoder {
                             // A fatal error in chunk parsing leaves the decoder in state 'None' to enforce
                             // that parsing can't continue after an error.
                             debug_assert!(self.state.is_none());
-                            let decoded = self.parse_chunk(type_str)?;
+                            let decoded = match self.handle_chunk(type_str)? {
+                                ChunkHandling::Skip
+                                    if !is_critical(type_str)
+                                        && type_str != chunk::acTL
+                                        && type_str != chunk::fcTL =>
+                                {
+                                    Decoded::SkippedAncillaryChunk(type_str)
+                                }
+                                _ => self.parse_chunk(type_str)?,
+                            };
 
                             if type_str != IEND {
                                 self.state = Some(State::new_u32(U32ValueKind::Length));
                             }
                             Ok(decoded)
                         }
+                        ChunkAction::Inspect => {
+                            debug_assert!(self.state.is_none());
+                            self.handle_chunk(type_str)?;
+                            self.state = Some(State::new_u32(U32ValueKind::Length));
+                            Ok(Decoded::SkippedAncillaryChunk(type_str))
+                        }
                         ChunkAction::Skip => {
                             self.state = Some(State::new_u32(U32ValueKind::Length));
                             Ok(Decoded::SkippedAncillaryChunk(self.current_chunk.type_))
@@ -968,6 +1176,7 @@ impl StreamingDecoder {
                         }
                     }
                 } else if self.decode_options.skip_ancillary_crc_failures
+                    && self.decode_options.strictness == Strictness::Lenient
                     && !chunk::is_critical(type_str)
                 {
                     // Ignore ancillary chunk with invalid CRC
@@ -1035,9 +1244,18 @@ impl StreamingDecoder {
             chunk::mDCV => 24..=24,
             chunk::cLLI => 8..=8,
             chunk::bKGD => 1..=6,
+            chunk::tIME => 7..=7,
+            chunk::hIST => 2..=512,
+            chunk::oFFs => 9..=9,
+            chunk::sTER => 1..=1,
+            chunk::gIFg => 4..=4,
 
             // Unbounded size chunks
             chunk::eXIf => 0..=u32::MAX >> 1, // TODO: allow skipping.
+            chunk::sPLT => 0..=u32::MAX >> 1,
+            chunk::pCAL => 0..=u32::MAX >> 1,
+            chunk::sCAL => 0..=u32::MAX >> 1,
+            chunk::gIFx => 11..=u32::MAX >> 1,
             chunk::iCCP if !self.decode_options.ignore_iccp_chunk => 0..=u32::MAX >> 1,
             chunk::tEXt if !self.decode_options.ignore_text_chunk => 0..=u32::MAX >> 1,
             chunk::zTXt if !self.decode_options.ignore_text_chunk => 0..=u32::MAX >> 1,
@@ -1045,13 +1263,20 @@ impl StreamingDecoder {
 
             chunk::IDAT | chunk::fdAT => unreachable!(),
 
+            // Ignored chunks are recognized, so they are never kept as unknown chunks.
+            chunk::iCCP | chunk::tEXt | chunk::zTXt | chunk::iTXt => {
+                self.current_chunk.action = self.skip_action(type_str);
+                return Ok(State::ReadChunkData(type_str));
+            }
+
             _ if is_critical(type_str) => {
                 return Err(DecodingError::Format(
                     FormatErrorInner::UnrecognizedCriticalChunk { type_str }.into(),
                 ));
             }
+            _ if self.decode_options.unknown_chunk_policy.keeps(type_str) => 0..=u32::MAX >> 1,
             _ => {
-                self.current_chunk.action = ChunkAction::Skip;
+                self.current_chunk.action = self.skip_action(type_str);
                 return Ok(State::ReadChunkData(type_str));
             }
         };
@@ -1059,16 +1284,14 @@ impl StreamingDecoder {
         if !target_length.contains(&length) {
             // Uncomment to detect unexpected chunk lengths during testing.
             // panic!("chunk type_str={type_str:?} has length={length}, target_length={target_length:?}");
-            match type_str {
-                IHDR | chunk::PLTE | chunk::IEND | chunk::fcTL => {
-                    return Err(DecodingError::Format(
-                        FormatErrorInner::ChunkLengthWrong { kind: type_str }.into(),
-                    ));
-                }
-                _ => {
-                    self.current_chunk.action = ChunkAction::Reject;
-                }
+            let fatal = matches!(type_str, IHDR | chunk::PLTE | chunk::IEND | chunk::fcTL)
+                || self.decode_options.strictness == Strictness::Strict;
+            if fatal {
+                return Err(DecodingError::Format(
+                    FormatErrorInner::ChunkLengthWrong { kind: type_str }.into(),
+                ));
             }
+            self.current_chunk.action = ChunkAction::Reject;
         } else {
             self.current_chunk.action = ChunkAction::Process;
         }
@@ -1076,12 +1299,21 @@ impl StreamingDecoder {
         Ok(State::ReadChunkData(type_str))
     }
 
+    /// The action for a chunk that the decoder skips, which is still read for its chunk handler.
+    fn skip_action(&self, type_str: ChunkType) -> ChunkAction {
+        if self.has_chunk_handler(type_str) {
+            ChunkAction::Inspect
+        } else {
+            ChunkAction::Skip
+        }
+    }
+
     fn parse_chunk(&mut self, type_str: ChunkType) -> Result<Decoded, DecodingError> {
         let mut parse_result = match type_str {
             // Critical non-data chunks.
             IHDR => self.parse_ihdr(),
             chunk::PLTE => self.parse_plte(),
-            chunk::IEND => Ok(()), // TODO: Check chunk size.
+            chunk::IEND => Ok(()), // The length was checked by `start_chunk`.
 
             // Data chunks handled separately.
             chunk::IDAT => Ok(()),
@@ -1100,18 +1332,25 @@ impl StreamingDecoder {
             chunk::mDCV => self.parse_mdcv(),
             chunk::cLLI => self.parse_clli(),
             chunk::bKGD => self.parse_bkgd(),
+            chunk::tIME => self.parse_time(),
+            chunk::hIST => self.parse_hist(),
+            chunk::oFFs => self.parse_offs(),
+            chunk::sTER => self.parse_ster(),
+            chunk::gIFg => self.parse_gifg(),
 
             // Ancillary chunks with unbounded size.
             chunk::eXIf => self.parse_exif(),
+            chunk::sPLT => self.parse_splt(),
+            chunk::pCAL => self.parse_pcal(),
+            chunk::sCAL => self.parse_scal(),
+            chunk::gIFx => self.parse_gifx(),
             chunk::iCCP => self.parse_iccp(),
             chunk::tEXt => self.parse_text(),
             chunk::zTXt => self.parse_ztxt(),
             chunk::iTXt => self.parse_itxt(),
 
-            // Unrecognized chunks.
-            _ => unreachable!(
-                "Unrecognized chunk {type_str:?} should have been caught in start_chunk"
-            ),
+            // Unrecognized chunks that are kept.
+            _ => self.parse_unknown_chunk(type_str),
         };
 
         parse_result = parse_result.map_err(|e| {
@@ -1132,21 +1371,46 @@ impl StreamingDecoder {
         match parse_result {
             Ok(()) => Ok(Decoded::ChunkComplete(type_str)),
             Err(DecodingError::Format(_))
-                if type_str != chunk::fcTL && !chunk::is_critical(type_str) =>
+                if type_str != chunk::fcTL
+                    && !chunk::is_critical(type_str)
+                    && self.decode_options.strictness == Strictness::Lenient =>
             {
                 // Ignore benign errors in most auxiliary chunks. `LimitsExceeded`, `Parameter` and
                 // other error kinds are *not* treated as benign. We don't ignore errors in `fcTL`
                 // chunks because the fallback to the static/non-animated image has to be
-                // implemented *on top* of the `StreamingDecoder` API.
-                //
-                // TODO: Consider supporting a strict mode where even benign errors are reported up.
-                // See https://github.com/image-rs/image-png/pull/569#issuecomment-2642062285
+                // implemented *on top* of the `StreamingDecoder` API. In strict mode, all errors
+                // are reported.
                 Ok(Decoded::BadAncillaryChunk(type_str))
             }
             Err(e) => Err(e),
         }
     }
 
+    fn parse_unknown_chunk(&mut self, type_str: ChunkType) -> Result<(), DecodingError> {
+        debug_assert!(self.decode_options.unknown_chunk_policy.keeps(type_str));
+        // The bytes were reserved against the limits while reading the chunk, so they are moved
+        // into the `Info` rather than copied. Reading the next chunk starts from a small buffer.
+        let data = mem::take(&mut self.current_chunk.raw_bytes);
+        self.current_chunk
+            .raw_bytes
+            .reserve_exact(CHUNK_BUFFER_SIZE);
+
+        let info = self.info.as_mut().unwrap();
+        let location = if self.have_idat {
+            ChunkLocation::AfterIdat
+        } else if info.palette.is_some() {
+            ChunkLocation::BeforeIdat
+        } else {
+            ChunkLocation::BeforePlte
+        };
+        info.unknown_chunks.push(UnknownChunk {
+            type_: type_str,
+            data,
+            location,
+        });
+        Ok(())
+    }
+
     fn parse_fctl(&mut self) -> Result<(), DecodingError> {
         let mut buf = &self.current_chunk.raw_bytes[..];
         let next_seq_no = buf.read_be()?;
@@ -1233,8 +1497,13 @@ impl StreamingDecoder {
                 num_plays: buf.read_be()?,
             };
             // The spec says that "0 is not a valid value" for `num_frames`.
-            // So let's ignore such malformed `acTL` chunks.
+            // So let's ignore such malformed `acTL` chunks, unless decoding strictly.
             if actl.num_frames == 0 {
+                if self.decode_options.strictness == Strictness::Strict {
+                    return Err(DecodingError::Format(
+                        FormatErrorInner::ZeroAnimationFrames.into(),
+                    ));
+                }
                 return Ok(());
             }
             info.animation_control = Some(actl);
@@ -1249,6 +1518,10 @@ impl StreamingDecoder {
             Err(DecodingError::Format(
                 FormatErrorInner::DuplicateChunk { kind: chunk::PLTE }.into(),
             ))
+        } else if self.have_idat && self.decode_options.strictness == Strictness::Strict {
+            Err(DecodingError::Format(
+                FormatErrorInner::AfterIdat { kind: chunk::PLTE }.into(),
+            ))
         } else {
             info.palette = Some(Cow::Owned(self.current_chunk.raw_bytes.clone()));
             Ok(())
@@ -1410,6 +1683,26 @@ impl StreamingDecoder {
         }
     }
 
+    fn parse_time(&mut self) -> Result<(), DecodingError> {
+        let info = self.info.as_mut().unwrap();
+        if info.modification_time.is_some() {
+            return Err(DecodingError::Format(
+                FormatErrorInner::DuplicateChunk { kind: chunk::tIME }.into(),
+            ));
+        }
+
+        let mut buf = &self.current_chunk.raw_bytes[..];
+        let year = buf.read_be()?;
+        let [month, day, hour, minute, second]: [u8; 5] = buf.try_into().unwrap();
+        match Time::new(year, month, day, hour, minute, second) {
+            Some(time) => {
+                info.modification_time = Some(time);
+                Ok(())
+            }
+            None => Err(DecodingError::Format(FormatErrorInner::InvalidTime.into())),
+        }
+    }
+
     fn parse_chrm(&mut self) -> Result<(), DecodingError> {
         let info = self.info.as_mut().unwrap();
         if self.have_idat {
@@ -1644,6 +1937,270 @@ impl StreamingDecoder {
         Ok(())
     }
 
+    fn parse_hist(&mut self) -> Result<(), DecodingError> {
+        let info = self.info.as_mut().unwrap();
+        if info.histogram.is_some() {
+            return Err(DecodingError::Format(
+                FormatErrorInner::DuplicateChunk { kind: chunk::hIST }.into(),
+            ));
+        } else if self.have_idat {
+            return Err(DecodingError::Format(
+                FormatErrorInner::AfterIdat { kind: chunk::hIST }.into(),
+            ));
+        }
+        let Some(palette) = &info.palette else {
+            return Err(DecodingError::Format(
+                FormatErrorInner::BeforePlte { kind: chunk::hIST }.into(),
+            ));
+        };
+
+        // There is one frequency per palette entry.
+        let buf = &self.current_chunk.raw_bytes;
+        if buf.len() != palette.len() / 3 * 2 {
+            return Err(DecodingError::Format(
+                FormatErrorInner::ChunkLengthWrong { kind: chunk::hIST }.into(),
+            ));
+        }
+        let histogram = buf
+            .chunks_exact(2)
+            .map(|freq| u16::from_be_bytes([freq[0], freq[1]]))
+            .collect();
+        info.histogram = Some(histogram);
+        Ok(())
+    }
+
+    fn parse_splt(&mut self) -> Result<(), DecodingError> {
+        if self.have_idat {
+            return Err(DecodingError::Format(
+                FormatErrorInner::AfterIdat { kind: chunk::sPLT }.into(),
+            ));
+        }
+
+        let (name, buf) = Self::split_keyword(&self.current_chunk.raw_bytes)?;
+        let (&depth, buf) = buf.split_first().ok_or(DecodingError::Format(
+            FormatErrorInner::ChunkLengthWrong { kind: chunk::sPLT }.into(),
+        ))?;
+        let (sample_depth, entry_len) = match depth {
+            8 => (BitDepth::Eight, 6),
+            16 => (BitDepth::Sixteen, 10),
+            _ => {
+                return Err(DecodingError::Format(
+                    FormatErrorInner::InvalidBitDepth(depth).into(),
+                ))
+            }
+        };
+        if buf.len() % entry_len != 0 {
+            return Err(DecodingError::Format(
+                FormatErrorInner::ChunkLengthWrong { kind: chunk::sPLT }.into(),
+            ));
+        }
+
+        let info = self.info.as_mut().unwrap();
+        let name = decode_iso_8859_1(name);
+        if info.suggested_palettes.iter().any(|p| p.name == name) {
+            return Err(DecodingError::Format(
+                FormatErrorInner::DuplicateChunk { kind: chunk::sPLT }.into(),
+            ));
+        }
+
+        let entry_count = buf.len() / entry_len;
+        self.limits
+            .reserve_bytes(entry_count * std::mem::size_of::<SuggestedPaletteEntry>())?;
+        let entries = buf
+            .chunks_exact(entry_len)
+            .map(|entry| {
+                let sample = |idx: usize| match sample_depth {
+                    BitDepth::Sixteen => u16::from_be_bytes([entry[2 * idx], entry[2 * idx + 1]]),
+                    _ => u16::from(entry[idx]),
+                };
+                let frequency = &entry[entry_len - 2..];
+                SuggestedPaletteEntry {
+                    red: sample(0),
+                    green: sample(1),
+                    blue: sample(2),
+                    alpha: sample(3),
+                    frequency: u16::from_be_bytes([frequency[0], frequency[1]]),
+                }
+            })
+            .collect();
+        info.suggested_palettes.push(SuggestedPalette {
+            name,
+            sample_depth,
+            entries,
+        });
+        Ok(())
+    }
+
+    fn parse_offs(&mut self) -> Result<(), DecodingError> {
+        let info = self.info.as_mut().unwrap();
+        if self.have_idat {
+            return Err(DecodingError::Format(
+                FormatErrorInner::AfterIdat { kind: chunk::oFFs }.into(),
+            ));
+        } else if info.offset.is_some() {
+            return Err(DecodingError::Format(
+                FormatErrorInner::DuplicateChunk { kind: chunk::oFFs }.into(),
+            ));
+        }
+
+        let buf = &self.current_chunk.raw_bytes;
+        let x = i32::from_be_bytes(buf[..4].try_into().unwrap());
+        let y = i32::from_be_bytes(buf[4..8].try_into().unwrap());
+        let unit = OffsetUnit::from_u8(buf[8]).ok_or(DecodingError::Format(
+            FormatErrorInner::InvalidUnit(buf[8]).into(),
+        ))?;
+        info.offset = Some(ImageOffset { x, y, unit });
+        Ok(())
+    }
+
+    fn parse_pcal(&mut self) -> Result<(), DecodingError> {
+        if self.have_idat {
+            return Err(DecodingError::Format(
+                FormatErrorInner::AfterIdat { kind: chunk::pCAL }.into(),
+            ));
+        } else if self.info.as_ref().unwrap().pixel_calibration.is_some() {
+            return Err(DecodingError::Format(
+                FormatErrorInner::DuplicateChunk { kind: chunk::pCAL }.into(),
+            ));
+        }
+
+        let buf = &self.current_chunk.raw_bytes[..];
+        self.limits.reserve_bytes(buf.len())?;
+        let (name, buf) = Self::split_keyword(buf)?;
+        if buf.len() < 10 {
+            return Err(DecodingError::Format(
+                FormatErrorInner::ChunkLengthWrong { kind: chunk::pCAL }.into(),
+            ));
+        }
+        let x0 = i32::from_be_bytes(buf[..4].try_into().unwrap());
+        let x1 = i32::from_be_bytes(buf[4..8].try_into().unwrap());
+        let equation = CalibrationEquation::from_u8(buf[8]).ok_or(DecodingError::Format(
+            FormatErrorInner::InvalidCalibrationEquation(buf[8]).into(),
+        ))?;
+        let parameter_count = usize::from(buf[9]);
+
+        // The unit is followed by the parameters, all separated by null bytes.
+        let mut fields = buf[10..].split(|&b| b == 0);
+        let unit = fields.next().unwrap_or_default();
+        let parameters = fields
+            .map(|field| parse_ascii_float(field, chunk::pCAL))
+            .collect::<Result<Vec<_>, _>>()?;
+        if x0 == x1
+            || parameter_count != equation.parameter_count()
+            || parameters.len() != parameter_count
+        {
+            return Err(DecodingError::Format(
+                FormatErrorInner::InvalidCalibration.into(),
+            ));
+        }
+
+        self.info.as_mut().unwrap().pixel_calibration = Some(PixelCalibration {
+            name: decode_iso_8859_1(name),
+            x0,
+            x1,
+            equation,
+            unit: decode_iso_8859_1(unit),
+            parameters,
+        });
+        Ok(())
+    }
+
+    fn parse_scal(&mut self) -> Result<(), DecodingError> {
+        let info = self.info.as_mut().unwrap();
+        if self.have_idat {
+            return Err(DecodingError::Format(
+                FormatErrorInner::AfterIdat { kind: chunk::sCAL }.into(),
+            ));
+        } else if info.physical_scale.is_some() {
+            return Err(DecodingError::Format(
+                FormatErrorInner::DuplicateChunk { kind: chunk::sCAL }.into(),
+            ));
+        }
+
+        let buf = &self.current_chunk.raw_bytes[..];
+        let Some((&unit, buf)) = buf.split_first() else {
+            return Err(DecodingError::Format(
+                FormatErrorInner::ChunkLengthWrong { kind: chunk::sCAL }.into(),
+            ));
+        };
+        let unit = ScaleUnit::from_u8(unit).ok_or(DecodingError::Format(
+            FormatErrorInner::InvalidUnit(unit).into(),
+        ))?;
+
+        let mut fields = buf.split(|&b| b == 0);
+        let (Some(width), Some(height), None) = (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(DecodingError::Format(
+                FormatErrorInner::ChunkLengthWrong { kind: chunk::sCAL }.into(),
+            ));
+        };
+        let width = parse_ascii_float(width, chunk::sCAL)?;
+        let height = parse_ascii_float(height, chunk::sCAL)?;
+        if width <= 0.0 || height <= 0.0 {
+            return Err(DecodingError::Format(
+                FormatErrorInner::InvalidAsciiFloat { kind: chunk::sCAL }.into(),
+            ));
+        }
+
+        info.physical_scale = Some(PhysicalScale {
+            unit,
+            width,
+            height,
+        });
+        Ok(())
+    }
+
+    fn parse_ster(&mut self) -> Result<(), DecodingError> {
+        let info = self.info.as_mut().unwrap();
+        if self.have_idat {
+            return Err(DecodingError::Format(
+                FormatErrorInner::AfterIdat { kind: chunk::sTER }.into(),
+            ));
+        } else if info.stereo_layout.is_some() {
+            return Err(DecodingError::Format(
+                FormatErrorInner::DuplicateChunk { kind: chunk::sTER }.into(),
+            ));
+        }
+
+        let mode = self.current_chunk.raw_bytes[0];
+        let layout = StereoLayout::from_u8(mode).ok_or(DecodingError::Format(
+            FormatErrorInner::InvalidStereoMode(mode).into(),
+        ))?;
+        info.stereo_layout = Some(layout);
+        Ok(())
+    }
+
+    fn parse_gifg(&mut self) -> Result<(), DecodingError> {
+        let buf = &self.current_chunk.raw_bytes;
+        let control = GifGraphicControl {
+            disposal_method: buf[0],
+            user_input: buf[1] != 0,
+            delay_time: u16::from_be_bytes([buf[2], buf[3]]),
+        };
+        self.info
+            .as_mut()
+            .unwrap()
+            .gif_graphic_controls
+            .push(control);
+        Ok(())
+    }
+
+    fn parse_gifx(&mut self) -> Result<(), DecodingError> {
+        let buf = &self.current_chunk.raw_bytes;
+        self.limits.reserve_bytes(buf.len())?;
+        let extension = GifApplicationExtension {
+            identifier: buf[..8].try_into().unwrap(),
+            authentication_code: buf[8..11].try_into().unwrap(),
+            data: buf[11..].to_vec(),
+        };
+        self.info
+            .as_mut()
+            .unwrap()
+            .gif_application_extensions
+            .push(extension);
+        Ok(())
+    }
+
     fn parse_exif(&mut self) -> Result<(), DecodingError> {
         let info = self.info.as_mut().unwrap();
         if info.exif_metadata.is_some() {
@@ -1932,6 +2489,19 @@ impl StreamingDecoder {
     }
 }
 
+/// Parses a number of the `pCAL` or `sCAL` chunks, written in ASCII as an optional sign, digits
+/// with an optional decimal point, and an optional exponent.
+fn parse_ascii_float(buf: &[u8], kind: ChunkType) -> Result<f64, DecodingError> {
+    buf.iter()
+        .all(|b| b"0123456789+-.eE".contains(b))
+        .then(|| std::str::from_utf8(buf).ok()?.parse::<f64>().ok())
+        .flatten()
+        .filter(|value| value.is_finite())
+        .ok_or(DecodingError::Format(
+            FormatErrorInner::InvalidAsciiFloat { kind }.into(),
+        ))
+}
+
 impl Info<'_> {
     fn validate_default_image(&self, fc: &FrameControl) -> Result<(), DecodingError> {
         // https://www.w3.org/TR/png-3/#fcTL-chunk says that:
@@ -1997,7 +2567,6 @@ mod tests {
     use std::fs::File;
     use std::io::BufRead;
     use std::io::Cursor;
-    use std::io::Seek;
     use std::io::{BufReader, ErrorKind, Read, Write};
     use std::rc::Rc;
 
@@ -2384,6 +2953,74 @@ mod tests {
         assert_eq!(exif.len(), 90);
     }
 
+    /// Test handling of `tIME` chunk.
+    #[test]
+    fn test_time_chunk() {
+        use crate::Time;
+
+        for (name, time) in [
+            ("cm0n0g04", Time::new(2000, 1, 1, 12, 34, 56)),
+            ("cm7n0g04", Time::new(1970, 1, 1, 0, 0, 0)),
+            ("cm9n0g04", Time::new(1999, 12, 31, 23, 59, 59)),
+            ("basn0g01", None),
+        ] {
+            let path = format!("tests/pngsuite/{}.png", name);
+            let decoder = Decoder::new(BufReader::new(File::open(path).unwrap()));
+            let reader = decoder.read_info().unwrap();
+            assert_eq!(reader.info().modification_time, time, "{}", name);
+        }
+    }
+
+    /// Test handling of `hIST` and `sPLT` chunks.
+    #[test]
+    fn test_hist_and_splt_chunks() {
+        use crate::{BitDepth, SuggestedPaletteEntry};
+
+        let black = SuggestedPaletteEntry {
+            red: 0,
+            green: 0,
+            blue: 0,
+            alpha: 255,
+            frequency: 0,
+        };
+        for (name, histogram, palette_depth) in [
+            ("ch1n3p04", Some((15, [64, 112, 48, 96])), None),
+            ("ch2n3p08", Some((256, [4, 4, 4, 4])), None),
+            ("ps1n0g08", None, Some(BitDepth::Eight)),
+            ("ps2n2c16", None, Some(BitDepth::Sixteen)),
+            ("basn0g01", None, None),
+        ] {
+            let path = format!("tests/pngsuite/{}.png", name);
+            let decoder = Decoder::new(BufReader::new(File::open(path).unwrap()));
+            let reader = decoder.read_info().unwrap();
+            let info = reader.info();
+
+            let hist = info
+                .histogram
+                .as_ref()
+                .map(|hist| (hist.len(), [hist[0], hist[1], hist[2], hist[3]]));
+            assert_eq!(hist, histogram, "{}", name);
+            let palettes: Vec<_> = info
+                .suggested_palettes
+                .iter()
+                .map(|palette| {
+                    let name = palette.name.as_str();
+                    (
+                        name,
+                        palette.sample_depth,
+                        palette.entries.len(),
+                        palette.entries[0],
+                    )
+                })
+                .collect();
+            let expected: Vec<_> = palette_depth
+                .map(|depth| ("six-cube", depth, 216, black))
+                .into_iter()
+                .collect();
+            assert_eq!(palettes, expected, "{}", name);
+        }
+    }
+
     /// Tests what happens then [`Reader.finish`] is called twice.
     #[test]
     fn test_finishing_twice() {
@@ -2631,20 +3268,100 @@ mod tests {
         assert_eq!(first_frame, second_frame);
     }
 
-    #[test]
-    fn test_idat_bigger_than_image_size_from_ihdr() {
-        let png = {
-            let mut png = Vec::new();
-            write_png_sig(&mut png);
-            write_rgba8_ihdr_with_width(&mut png, 8);
+    /// Compresses an interlaced 8-bit grayscale image, whose pixels are given by `pixel`, into a
+    /// store-only zlib container.
+    fn generate_interlaced_gray8(
+        width: u32,
+        height: u32,
+        pixel: impl Fn(u32, u32) -> u8,
+    ) -> Vec<u8> {
+        const PASSES: [(u32, u32, u32, u32); 7] = [
+            (0, 0, 8, 8),
+            (4, 0, 8, 8),
+            (0, 4, 4, 8),
+            (2, 0, 4, 4),
+            (0, 2, 2, 4),
+            (1, 0, 2, 2),
+            (0, 1, 1, 2),
+        ];
+        let mut image_data = Vec::new();
+        for (x0, y0, dx, dy) in PASSES {
+            if x0 >= width {
+                continue;
+            }
+            for y in (y0..height).step_by(dy as usize) {
+                image_data.push(0); // filter = no filter
+                image_data.extend((x0..width).step_by(dx as usize).map(|x| pixel(x, y)));
+            }
+        }
 
-            // Here we want to test an invalid image where the `IDAT` chunk contains more data
-            // (data for 8x256 image) than declared in the `IHDR` chunk (which only describes an
-            // 8x8 image).
-            write_chunk(
-                &mut png,
-                b"IDAT",
-                &generate_rgba8_with_width_and_height(8, 256),
+        let mut zlib_data = Vec::new();
+        let mut compressor =
+            fdeflate::StoredOnlyCompressor::new(std::io::Cursor::new(&mut zlib_data)).unwrap();
+        compressor.write_data(&image_data).unwrap();
+        compressor.finish().unwrap();
+        zlib_data
+    }
+
+    #[test]
+    fn test_interlaced_subframe_smaller_than_image() {
+        let mut png = Vec::new();
+        write_png_sig(&mut png);
+        let mut ihdr = Vec::new();
+        ihdr.write_u32::<byteorder::BigEndian>(4).unwrap();
+        ihdr.write_u32::<byteorder::BigEndian>(4).unwrap();
+        ihdr.write_all(&[8, 0, 0, 0, 1]).unwrap(); // 8-bit grayscale, Adam7 interlaced
+        write_chunk(&mut png, b"IHDR", &ihdr);
+        write_actl(
+            &mut png,
+            &crate::AnimationControl {
+                num_frames: 2,
+                num_plays: 0,
+            },
+        );
+        let mut fctl = crate::FrameControl {
+            width: 4,
+            height: 4,
+            ..Default::default()
+        };
+        write_fctl(&mut png, &fctl);
+        write_chunk(
+            &mut png,
+            b"IDAT",
+            &generate_interlaced_gray8(4, 4, |_, _| 0),
+        );
+        fctl.sequence_number = 1;
+        fctl.width = 2;
+        fctl.height = 2;
+        write_fctl(&mut png, &fctl);
+        let pixel = |x, y| (10 * y + x) as u8;
+        write_fdat(&mut png, 2, &generate_interlaced_gray8(2, 2, pixel));
+        write_iend(&mut png);
+
+        let mut reader = Decoder::new(Cursor::new(png)).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut buf).unwrap();
+
+        // The rows of the subframe are packed with the stride of the subframe, not the image.
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width, info.height, info.line_size), (2, 2, 2));
+        assert_eq!(buf[..info.buffer_size()], [0, 1, 10, 11]);
+    }
+
+    #[test]
+    fn test_idat_bigger_than_image_size_from_ihdr() {
+        let png = {
+            let mut png = Vec::new();
+            write_png_sig(&mut png);
+            write_rgba8_ihdr_with_width(&mut png, 8);
+
+            // Here we want to test an invalid image where the `IDAT` chunk contains more data
+            // (data for 8x256 image) than declared in the `IHDR` chunk (which only describes an
+            // 8x8 image).
+            write_chunk(
+                &mut png,
+                b"IDAT",
+                &generate_rgba8_with_width_and_height(8, 256),
             );
 
             write_iend(&mut png);
@@ -2766,19 +3483,23 @@ mod tests {
             assert!(state.current_pos <= state.available_len);
         }
     }
-    impl Seek for StreamingInput {
-        fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
-            let mut state = self.state.borrow_mut();
-            state.current_pos = match pos {
-                std::io::SeekFrom::Start(n) => n as usize,
-                std::io::SeekFrom::End(n) => (self.full_input.len() as i64 + n) as usize,
-                std::io::SeekFrom::Current(n) => (state.current_pos as i64 + n) as usize,
-            } as usize;
-            Ok(state.current_pos as u64)
-        }
-        fn stream_position(&mut self) -> std::io::Result<u64> {
-            Ok(self.state.borrow().current_pos as u64)
-        }
+
+    #[test]
+    fn test_decoding_without_seek() {
+        let path = "tests/pngsuite/basi2c16.png";
+        let mut reader = Decoder::new(BufReader::new(File::open(path).unwrap()))
+            .read_info()
+            .unwrap();
+        let mut expected = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut expected).unwrap();
+
+        // `Take` does not implement `Seek`, like sockets, pipes or decompression streams.
+        let input = BufReader::new(File::open(path).unwrap().take(u64::MAX));
+        let mut reader = Decoder::new(input).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut buf).unwrap();
+        reader.finish().unwrap();
+        assert_eq!(buf, expected);
     }
 
     /// Test resuming/retrying `Reader.next_frame` after `UnexpectedEof`.
@@ -2966,7 +3687,7 @@ mod tests {
         Decoder::new(Cursor::new(png)).read_info().unwrap()
     }
 
-    fn get_fctl_sequence_number(reader: &Reader<impl BufRead + Seek>) -> u32 {
+    fn get_fctl_sequence_number(reader: &Reader<impl BufRead>) -> u32 {
         reader
             .info()
             .frame_control
@@ -3086,7 +3807,7 @@ mod tests {
         let mut buf = vec![0; reader.output_buffer_size().unwrap()];
 
         assert_eq!(get_fctl_sequence_number(&reader), 0);
-        while let Some(_) = reader.next_row().unwrap() {}
+        while reader.next_row().unwrap().is_some() {}
         assert_eq!(get_fctl_sequence_number(&reader), 0);
 
         buf.fill(0x0f);
@@ -3108,7 +3829,7 @@ mod tests {
 
         let mut rows_of_frame1 = 0;
         assert_eq!(get_fctl_sequence_number(&reader), 0);
-        while let Some(_) = reader.next_row().unwrap() {
+        while reader.next_row().unwrap().is_some() {
             rows_of_frame1 += 1;
         }
         assert_eq!(rows_of_frame1, 16);
@@ -3117,7 +3838,7 @@ mod tests {
         let mut rows_of_frame2 = 0;
         assert_eq!(reader.next_frame_info().unwrap().sequence_number, 1);
         assert_eq!(get_fctl_sequence_number(&reader), 1);
-        while let Some(_) = reader.next_row().unwrap() {
+        while reader.next_row().unwrap().is_some() {
             rows_of_frame2 += 1;
         }
         assert_eq!(rows_of_frame2, 16);
@@ -3369,4 +4090,895 @@ mod tests {
         assert_eq!(actl.num_frames, 2);
         assert_eq!(actl.num_plays, 123);
     }
+
+    /// Encodes an animation with the frames given as `(FrameControl, data)` pairs. The sequence
+    /// numbers and the frame delays of the `FrameControl`s are not used.
+    fn encode_apng(
+        width: u32,
+        height: u32,
+        color_type: crate::ColorType,
+        bit_depth: crate::BitDepth,
+        palette: Option<(&[u8], &[u8])>,
+        frames: &[(crate::FrameControl, Vec<u8>)],
+    ) -> Vec<u8> {
+        let mut png = Vec::new();
+        let mut encoder = crate::Encoder::new(&mut png, width, height);
+        encoder.set_color(color_type);
+        encoder.set_depth(bit_depth);
+        if let Some((plte, trns)) = palette {
+            encoder.set_palette(plte);
+            encoder.set_trns(trns);
+        }
+        encoder.set_animated(frames.len() as u32, 0).unwrap();
+        let mut writer = encoder.write_header().unwrap();
+        for (fctl, data) in frames {
+            writer.reset_frame_position().unwrap();
+            writer.set_frame_dimension(fctl.width, fctl.height).unwrap();
+            writer
+                .set_frame_position(fctl.x_offset, fctl.y_offset)
+                .unwrap();
+            writer.set_blend_op(fctl.blend_op).unwrap();
+            writer.set_dispose_op(fctl.dispose_op).unwrap();
+            writer.write_image_data(data).unwrap();
+        }
+        writer.finish().unwrap();
+        png
+    }
+
+    fn frame_control(
+        (x_offset, y_offset): (u32, u32),
+        (width, height): (u32, u32),
+        blend_op: crate::BlendOp,
+        dispose_op: crate::DisposeOp,
+    ) -> crate::FrameControl {
+        crate::FrameControl {
+            width,
+            height,
+            x_offset,
+            y_offset,
+            blend_op,
+            dispose_op,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_composited_frames_rgba8() {
+        use crate::{BlendOp, DisposeOp};
+
+        const RED: [u8; 4] = [255, 0, 0, 255];
+        const BLANK: [u8; 4] = [0; 4];
+        let frames = [
+            (
+                frame_control((0, 0), (4, 4), BlendOp::Source, DisposeOp::None),
+                RED.repeat(16),
+            ),
+            (
+                frame_control((1, 1), (2, 2), BlendOp::Over, DisposeOp::Background),
+                [0, 0, 255, 128].repeat(4),
+            ),
+            (
+                frame_control((3, 3), (1, 1), BlendOp::Source, DisposeOp::Previous),
+                vec![0, 255, 0, 255],
+            ),
+            (
+                frame_control((0, 0), (1, 1), BlendOp::Over, DisposeOp::None),
+                vec![0, 0, 0, 0],
+            ),
+        ];
+        let png = encode_apng(
+            4,
+            4,
+            crate::ColorType::Rgba,
+            crate::BitDepth::Eight,
+            None,
+            &frames,
+        );
+
+        let canvas = |px: &dyn Fn(usize, usize) -> [u8; 4]| -> Vec<u8> {
+            (0..16).flat_map(|i| px(i % 4, i / 4)).collect()
+        };
+        let center = |x, y| (1..3).contains(&x) && (1..3).contains(&y);
+        let expected = [
+            canvas(&|_, _| RED),
+            canvas(&|x, y| {
+                if center(x, y) {
+                    [127, 0, 128, 255]
+                } else {
+                    RED
+                }
+            }),
+            canvas(&|x, y| match (x, y) {
+                (3, 3) => [0, 255, 0, 255],
+                _ if center(x, y) => BLANK,
+                _ => RED,
+            }),
+            canvas(&|x, y| if center(x, y) { BLANK } else { RED }),
+        ];
+
+        let mut reader = Decoder::new(Cursor::new(png)).read_info().unwrap();
+        assert_eq!(
+            reader.composited_color_type(),
+            (crate::ColorType::Rgba, crate::BitDepth::Eight)
+        );
+        let mut buf = vec![0; reader.composited_buffer_size().unwrap()];
+        for expected in &expected {
+            let info = reader.next_composited_frame(&mut buf).unwrap();
+            assert_eq!((info.output_info.width, info.output_info.height), (4, 4));
+            assert_eq!(&buf, expected);
+        }
+        assert!(reader.next_composited_frame(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_composited_frames_palette_trns() {
+        use crate::{BlendOp, DisposeOp};
+
+        let plte = [255, 0, 0, 0, 255, 0, 0, 0, 255];
+        let trns = [255, 0, 128];
+        // Two 2-bit indices per frame.
+        let frames = [
+            (
+                frame_control((0, 0), (2, 1), BlendOp::Source, DisposeOp::None),
+                vec![0b0000_0000],
+            ),
+            (
+                frame_control((0, 0), (2, 1), BlendOp::Over, DisposeOp::None),
+                vec![0b0110_0000],
+            ),
+        ];
+        let png = encode_apng(
+            2,
+            1,
+            crate::ColorType::Indexed,
+            crate::BitDepth::Two,
+            Some((&plte, &trns)),
+            &frames,
+        );
+
+        let mut reader = Decoder::new(Cursor::new(png)).read_info().unwrap();
+        let mut buf = vec![0; reader.composited_buffer_size().unwrap()];
+        reader.next_composited_frame(&mut buf).unwrap();
+        assert_eq!(buf, [255, 0, 0, 255, 255, 0, 0, 255]);
+        reader.next_composited_frame(&mut buf).unwrap();
+        // The transparent green keeps the red, the half transparent blue is blended with it.
+        assert_eq!(buf, [255, 0, 0, 255, 127, 0, 128, 255]);
+    }
+
+    #[test]
+    fn test_composited_frames_gray16() {
+        use crate::{BlendOp, DisposeOp};
+
+        let frames = [
+            (
+                frame_control((0, 0), (2, 1), BlendOp::Source, DisposeOp::Background),
+                vec![0x12, 0x34, 0xff, 0xff],
+            ),
+            (
+                frame_control((1, 0), (1, 1), BlendOp::Over, DisposeOp::None),
+                vec![0x01, 0x02],
+            ),
+        ];
+        let png = encode_apng(
+            2,
+            1,
+            crate::ColorType::Grayscale,
+            crate::BitDepth::Sixteen,
+            None,
+            &frames,
+        );
+
+        let mut reader = Decoder::new(Cursor::new(png)).read_info().unwrap();
+        assert_eq!(
+            reader.composited_color_type(),
+            (crate::ColorType::GrayscaleAlpha, crate::BitDepth::Sixteen)
+        );
+        let mut buf = vec![0; reader.composited_buffer_size().unwrap()];
+        reader.next_composited_frame(&mut buf).unwrap();
+        assert_eq!(buf, [0x12, 0x34, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        reader.next_composited_frame(&mut buf).unwrap();
+        assert_eq!(buf, [0, 0, 0, 0, 0x01, 0x02, 0xff, 0xff]);
+    }
+
+    #[test]
+    fn test_composited_frames_skip_default_image() {
+        let mut png = Vec::new();
+        {
+            let mut encoder = crate::Encoder::new(&mut png, 1, 1);
+            encoder.set_color(crate::ColorType::Grayscale);
+            encoder.set_animated(1, 0).unwrap();
+            encoder.set_sep_def_img(true).unwrap();
+            encoder.set_frame_delay(3, 40).unwrap();
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[1]).unwrap();
+            writer.write_image_data(&[2]).unwrap();
+            writer.finish().unwrap();
+        }
+
+        let mut reader = Decoder::new(Cursor::new(png)).read_info().unwrap();
+        let mut buf = vec![0; reader.composited_buffer_size().unwrap()];
+        let info = reader.next_composited_frame(&mut buf).unwrap();
+        assert_eq!(buf, [2, 255]);
+        assert_eq!((info.delay_num, info.delay_den), (3, 40));
+        assert!(reader.next_composited_frame(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_composited_frame_of_static_image() {
+        let decoder = Decoder::new(BufReader::new(
+            File::open("tests/pngsuite/basn6a08.png").unwrap(),
+        ));
+        let mut reader = decoder.read_info().unwrap();
+        let mut expected = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut expected).unwrap();
+
+        let decoder = Decoder::new(BufReader::new(
+            File::open("tests/pngsuite/basn6a08.png").unwrap(),
+        ));
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.composited_buffer_size().unwrap()];
+        let info = reader.next_composited_frame(&mut buf).unwrap();
+        assert_eq!(buf, expected);
+        assert_eq!((info.delay_num, info.delay_den), (0, 0));
+    }
+
+    /// Copies `region` out of a frame with `line_size` bytes per row, bit by bit.
+    fn crop(frame: &[u8], line_size: usize, bits_pp: usize, region: crate::Region) -> Vec<u8> {
+        let region_line_size = (region.width as usize * bits_pp).div_ceil(8);
+        let mut cropped = vec![0; region_line_size * region.height as usize];
+        for y in 0..region.height as usize {
+            let row = &frame[(region.y as usize + y) * line_size..];
+            for bit in 0..region.width as usize * bits_pp {
+                let src_bit = region.x as usize * bits_pp + bit;
+                let value = (row[src_bit / 8] >> (7 - src_bit % 8)) & 1;
+                cropped[y * region_line_size + bit / 8] |= value << (7 - bit % 8);
+            }
+        }
+        cropped
+    }
+
+    #[test]
+    fn test_next_frame_region() {
+        use crate::{Region, Transformations};
+
+        let regions = [
+            (0, 0, 32, 32),
+            (3, 5, 7, 9),
+            (31, 0, 1, 32),
+            (9, 30, 13, 2),
+            (0, 0, 5, 1),
+        ];
+        let transforms = [
+            Transformations::IDENTITY,
+            Transformations::EXPAND,
+            Transformations::EXPAND | Transformations::STRIP_16,
+        ];
+
+        for path in glob::glob("tests/pngsuite/bas*.png").unwrap() {
+            let path = path.unwrap();
+            for transform in transforms {
+                let mut decoder = Decoder::new(BufReader::new(File::open(&path).unwrap()));
+                decoder.set_transformations(transform);
+                let mut reader = decoder.read_info().unwrap();
+                let mut frame = vec![0; reader.output_buffer_size().unwrap()];
+                let frame_info = reader.next_frame(&mut frame).unwrap();
+                let (color_type, bit_depth) = reader.output_color_type();
+                let bits_pp = color_type.samples() * bit_depth as usize;
+
+                for (x, y, width, height) in regions {
+                    let region = Region {
+                        x,
+                        y,
+                        width,
+                        height,
+                    };
+                    let mut decoder = Decoder::new(BufReader::new(File::open(&path).unwrap()));
+                    decoder.set_transformations(transform);
+                    let mut reader = decoder.read_info().unwrap();
+                    let line_size = reader.output_line_size(width).unwrap();
+                    let mut buf = vec![0; line_size * height as usize];
+                    let info = reader.next_frame_region(region, &mut buf).unwrap();
+                    assert_eq!((info.width, info.height), (width, height));
+                    assert_eq!(info.line_size, line_size);
+                    assert_eq!(
+                        buf,
+                        crop(&frame, frame_info.line_size, bits_pp, region),
+                        "{:?} {:?} {:?}",
+                        path,
+                        transform,
+                        region
+                    );
+                    reader.finish().unwrap();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_next_frame_region_transforms_region_only() {
+        use crate::{Region, Transformations};
+
+        for name in ["basn2c08", "basi2c08", "basi3p04"] {
+            let path = format!("tests/pngsuite/{}.png", name);
+            let mut decoder = Decoder::new(BufReader::new(File::open(path).unwrap()));
+            decoder.set_transformations(Transformations::EXPAND);
+            let mut reader = decoder.read_info().unwrap();
+            let region = Region {
+                x: 9,
+                y: 4,
+                width: 3,
+                height: 5,
+            };
+            let line_size = reader.output_line_size(region.width).unwrap();
+            let mut buf = vec![0; line_size * region.height as usize];
+            reader.next_frame_region(region, &mut buf).unwrap();
+            // The scratch buffer holds the transformed pixels of the last row that was decoded.
+            // Those are the pixels in the columns of the region, and with 4 bits per pixel up to
+            // one more on either side that shares a byte with them.
+            let max_len = reader.output_line_size(region.width + 2).unwrap();
+            assert!(reader.scratch_buffer.len() <= max_len, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_next_frame_region_then_next_frame() {
+        use crate::{BitDepth, BlendOp, ColorType, DisposeOp, Region};
+
+        let frames = [
+            (
+                frame_control((0, 0), (16, 16), BlendOp::Source, DisposeOp::None),
+                (0..=255).collect::<Vec<u8>>(),
+            ),
+            (
+                frame_control((0, 0), (16, 16), BlendOp::Source, DisposeOp::None),
+                (0..=255).rev().collect(),
+            ),
+        ];
+        let png = encode_apng(16, 16, ColorType::Grayscale, BitDepth::Eight, None, &frames);
+
+        let mut reader = Decoder::new(Cursor::new(&png)).read_info().unwrap();
+        let mut region = vec![0; 4];
+        let region_info = Region {
+            x: 2,
+            y: 1,
+            width: 2,
+            height: 2,
+        };
+        reader.next_frame_region(region_info, &mut region).unwrap();
+        assert_eq!(region, [18, 19, 34, 35]);
+
+        let mut buf = vec![0; 256];
+        reader.next_frame(&mut buf).unwrap();
+        assert_eq!(buf, frames[1].1);
+        assert!(matches!(
+            reader.next_frame_region(region_info, &mut region),
+            Err(DecodingError::Parameter(_))
+        ));
+        reader.finish().unwrap();
+
+        let mut reader = Decoder::new(Cursor::new(&png)).read_info().unwrap();
+        let outside = Region {
+            x: 15,
+            y: 0,
+            width: 2,
+            height: 1,
+        };
+        assert!(matches!(
+            reader.next_frame_region(outside, &mut region),
+            Err(DecodingError::Parameter(_))
+        ));
+    }
+
+    #[test]
+    fn test_scaled_frames_of_animation() {
+        use crate::{BitDepth, BlendOp, ColorType, DisposeOp, Scale};
+
+        let frames = [
+            (
+                frame_control((0, 0), (4, 4), BlendOp::Source, DisposeOp::None),
+                (0..16).map(|v| v * 4).collect::<Vec<u8>>(),
+            ),
+            (
+                frame_control((1, 1), (3, 2), BlendOp::Source, DisposeOp::None),
+                vec![10, 20, 30, 40, 50, 61],
+            ),
+        ];
+        let png = encode_apng(4, 4, ColorType::Grayscale, BitDepth::Eight, None, &frames);
+
+        let mut decoder = Decoder::new(Cursor::new(&png));
+        decoder.set_scale(Scale::Half);
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+        assert_eq!(buf.len(), 4);
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width, info.height), (2, 2));
+        assert_eq!(buf, [10, 18, 42, 50]);
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width, info.height), (2, 1));
+        assert_eq!(buf[..2], [30, 46]);
+    }
+
+    #[test]
+    fn test_interlace_handling() {
+        use crate::InterlaceHandling;
+
+        for name in [
+            "basi0g01", "basi0g08", "basi2c16", "basi3p04", "basi6a08", "s07i3p02",
+        ] {
+            let path = format!("tests/pngsuite/{}.png", name);
+            let data = std::fs::read(&path).unwrap();
+            let decode = |interlace_handling| {
+                let mut decoder = Decoder::new(data.as_slice());
+                decoder.set_interlace_handling(interlace_handling);
+                let mut reader = decoder.read_info().unwrap();
+                let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+                let info = reader.next_frame(&mut buf).unwrap();
+                assert_eq!(buf.len(), info.buffer_size());
+                (buf, info)
+            };
+
+            let (expected, expected_info) = decode(InterlaceHandling::Sparkle);
+            assert_eq!(
+                decode(InterlaceHandling::Rectangle),
+                (expected, expected_info)
+            );
+
+            let mut reader = Decoder::new(data.as_slice()).read_info().unwrap();
+            let mut rows = Vec::new();
+            while let Some(row) = reader.next_interlaced_row().unwrap() {
+                rows.push(row.data().to_vec());
+            }
+            let (raw, raw_info) = decode(InterlaceHandling::RawRows);
+            assert_eq!(raw_info.height as usize, rows.len(), "{}", name);
+            for (line, row) in raw.chunks_exact(raw_info.line_size).zip(&rows) {
+                assert_eq!(&line[..row.len()], row.as_slice(), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_next_frame_progressive_first_pass() {
+        use crate::InterlaceHandling;
+
+        let data = std::fs::read("tests/pngsuite/basi0g08.png").unwrap();
+        let mut expected = vec![0; 32 * 32];
+        let mut reader = Decoder::new(data.as_slice()).read_info().unwrap();
+        reader.next_frame(&mut expected).unwrap();
+
+        for interlace_handling in [InterlaceHandling::Rectangle, InterlaceHandling::Sparkle] {
+            let mut decoder = Decoder::new(data.as_slice());
+            decoder.set_interlace_handling(interlace_handling);
+            let mut reader = decoder.read_info().unwrap();
+            let mut buf = vec![0; 32 * 32];
+            let mut first_pass = Vec::new();
+            reader
+                .next_frame_progressive(&mut buf, |progress| {
+                    if progress.pass == Some(1) {
+                        first_pass = progress.buffer.to_vec();
+                        let block_height = match interlace_handling {
+                            InterlaceHandling::Rectangle => 8,
+                            _ => 1,
+                        };
+                        assert_eq!(progress.rows.len(), block_height);
+                    }
+                })
+                .unwrap();
+            assert_eq!(buf, expected);
+
+            for (y, x) in (0..32).flat_map(|y| (0..32).map(move |x| (y, x))) {
+                let corner = expected[(y & !7) * 32 + (x & !7)];
+                let pixel = match interlace_handling {
+                    InterlaceHandling::Rectangle => corner,
+                    _ if x % 8 == 0 && y % 8 == 0 => corner,
+                    _ => 0,
+                };
+                assert_eq!(first_pass[y * 32 + x], pixel, "{:?}", interlace_handling);
+            }
+        }
+    }
+
+    /// Test resuming `Reader.next_frame_progressive` after `UnexpectedEof`.
+    #[test]
+    fn test_streaming_input_and_decoding_via_next_frame_progressive() {
+        let streaming_input =
+            StreamingInput::new(std::fs::read("tests/pngsuite/basi0g08.png").unwrap());
+
+        let (expected, expected_progress) = streaming_input.decode_full_input(|mut r| {
+            let mut buf = vec![0; r.output_buffer_size().unwrap()];
+            let mut progress = Vec::new();
+            r.next_frame_progressive(&mut buf, |p| progress.push((p.pass, p.rows)))
+                .unwrap();
+            (buf, progress)
+        });
+        assert_eq!(expected_progress.len(), 60);
+
+        let mut png_reader = streaming_input.stream_input_until_reader_is_available();
+        let mut buf = vec![0; png_reader.output_buffer_size().unwrap()];
+        let mut progress = Vec::new();
+        loop {
+            match png_reader.next_frame_progressive(&mut buf, |p| progress.push((p.pass, p.rows))) {
+                Ok(_) => break,
+                Err(DecodingError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => {
+                    streaming_input.expose_next_byte()
+                }
+                e => panic!("Unexpected error: {:?}", e),
+            }
+        }
+        assert_eq!(buf, expected);
+        assert_eq!(progress, expected_progress);
+    }
+
+    #[test]
+    fn test_unknown_chunk_policy() {
+        use crate::chunk::ChunkType;
+        use crate::{ChunkLocation, Limits, UnknownChunkPolicy};
+
+        let large = vec![0; 0x10000];
+        let png = {
+            let mut png = Vec::new();
+            write_png_sig(&mut png);
+            write_rgba8_ihdr_with_width(&mut png, 8);
+            write_chunk(&mut png, b"prVt", b"safe");
+            write_chunk(&mut png, b"prVT", b"unsafe");
+            write_rgba8_idats(&mut png, 8, 0x100);
+            write_chunk(&mut png, b"prVt", b"after");
+            write_chunk(&mut png, b"lrGe", &large);
+            write_iend(&mut png);
+            png
+        };
+        let decode = |policy: UnknownChunkPolicy, limits: Limits| {
+            let mut decoder = Decoder::new(Cursor::new(&png));
+            decoder.set_unknown_chunk_policy(policy);
+            decoder.set_limits(limits);
+            let mut reader = decoder.read_info()?;
+            let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+            reader.next_frame(&mut buf)?;
+            reader.finish()?;
+            Ok::<_, DecodingError>(
+                reader
+                    .info()
+                    .unknown_chunks
+                    .iter()
+                    .map(|chunk| (chunk.type_, chunk.data.clone(), chunk.location))
+                    .collect::<Vec<_>>(),
+            )
+        };
+
+        let safe = (
+            ChunkType(*b"prVt"),
+            b"safe".to_vec(),
+            ChunkLocation::BeforePlte,
+        );
+        let not_safe = (
+            ChunkType(*b"prVT"),
+            b"unsafe".to_vec(),
+            ChunkLocation::BeforePlte,
+        );
+        let after = (
+            ChunkType(*b"prVt"),
+            b"after".to_vec(),
+            ChunkLocation::AfterIdat,
+        );
+        let limits = Limits { bytes: 0x8000 };
+        assert_eq!(decode(UnknownChunkPolicy::Discard, limits).unwrap(), []);
+        // Kept chunks count towards the limits, but only once.
+        let decoded = decode(UnknownChunkPolicy::SafeToCopy, limits);
+        assert!(matches!(decoded, Err(DecodingError::LimitsExceeded)));
+        let limits = Limits { bytes: 0x10000 };
+        assert!(decode(UnknownChunkPolicy::SafeToCopy, limits).is_ok());
+
+        let limits = Limits::default();
+        let large = (ChunkType(*b"lrGe"), large, ChunkLocation::AfterIdat);
+        assert_eq!(
+            decode(UnknownChunkPolicy::SafeToCopy, limits).unwrap(),
+            [safe.clone(), after.clone(), large.clone()]
+        );
+        assert_eq!(
+            decode(UnknownChunkPolicy::All, limits).unwrap(),
+            [safe, not_safe.clone(), after, large]
+        );
+        let listed = UnknownChunkPolicy::Listed(vec![ChunkType(*b"prVT")]);
+        assert_eq!(decode(listed, limits).unwrap(), [not_safe]);
+    }
+
+    #[test]
+    fn test_chunk_handlers() {
+        use crate::chunk::{self, ChunkType};
+        use crate::{ChunkHandling, Decoded, StreamingDecoder};
+        use std::sync::{Arc, Mutex};
+
+        let png = {
+            let mut png = Vec::new();
+            write_png_sig(&mut png);
+            write_rgba8_ihdr_with_width(&mut png, 8);
+            write_chunk(&mut png, b"prVt", b"private");
+            write_chunk(&mut png, b"tEXt", b"key\0value");
+            write_rgba8_idats(&mut png, 8, 0x100);
+            write_iend(&mut png);
+            png
+        };
+
+        // The default handler sees all chunks except the image data.
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut decoder = Decoder::new(Cursor::new(&png));
+        decoder.set_default_chunk_handler({
+            let seen = seen.clone();
+            move |type_, data| {
+                seen.lock().unwrap().push((type_, data.len()));
+                ChunkHandling::Continue
+            }
+        });
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut buf).unwrap();
+        reader.finish().unwrap();
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [
+                (chunk::IHDR, 13),
+                (ChunkType(*b"prVt"), 7),
+                (chunk::tEXt, 9),
+                (chunk::IEND, 0)
+            ]
+        );
+        assert_eq!(reader.info().uncompressed_latin1_text.len(), 1);
+
+        // Handlers of a type take precedence and can skip recognized ancillary chunks, but not
+        // critical ones.
+        let private = Arc::new(Mutex::new(Vec::new()));
+        let mut decoder = StreamingDecoder::new();
+        decoder.set_default_chunk_handler(|_, _| ChunkHandling::Skip);
+        decoder.set_chunk_handler(ChunkType(*b"prVt"), {
+            let private = private.clone();
+            move |_, data| {
+                private.lock().unwrap().extend_from_slice(data);
+                ChunkHandling::Continue
+            }
+        });
+        let mut input = &png[..];
+        let mut decoded = Vec::new();
+        loop {
+            let (consumed, result) = decoder.update(input, None).unwrap();
+            input = &input[consumed..];
+            match result {
+                Decoded::ChunkComplete(type_) => decoded.push((type_, true)),
+                Decoded::SkippedAncillaryChunk(type_) => decoded.push((type_, false)),
+                Decoded::ImageData => break,
+                _ => {}
+            }
+        }
+        assert_eq!(
+            decoded,
+            [
+                (chunk::IHDR, true),
+                (ChunkType(*b"prVt"), false),
+                (chunk::tEXt, false),
+            ]
+        );
+        assert_eq!(*private.lock().unwrap(), b"private");
+        assert!(decoder.info().unwrap().uncompressed_latin1_text.is_empty());
+
+        let mut decoder = Decoder::new(Cursor::new(&png));
+        decoder.set_chunk_handler(ChunkType(*b"prVt"), |_, _| ChunkHandling::Abort);
+        let Err(err) = decoder.read_info() else {
+            panic!("Expected an error")
+        };
+        assert!(matches!(err, DecodingError::Format(_)));
+    }
+
+    #[test]
+    fn test_chunk_handlers_dont_skip_animation_chunks() {
+        use crate::ChunkHandling;
+
+        let decode = |skip: bool| {
+            let file = File::open("tests/animated/basic_f20.png").unwrap();
+            let mut decoder = Decoder::new(BufReader::new(file));
+            if skip {
+                decoder.set_default_chunk_handler(|_, _| ChunkHandling::Skip);
+            }
+            let mut reader = decoder.read_info().unwrap();
+            let actl = reader.info().animation_control.unwrap();
+            let mut frames = Vec::new();
+            for _ in 0..actl.num_frames {
+                let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+                reader.next_frame(&mut buf).unwrap();
+                let fctl = reader.info().frame_control.unwrap();
+                frames.push((fctl.sequence_number, buf));
+            }
+            reader.finish().unwrap();
+            frames
+        };
+
+        let frames = decode(true);
+        assert!(frames.len() > 1);
+        assert_eq!(frames, decode(false));
+    }
+
+    #[test]
+    fn test_extension_chunks() {
+        use crate::{
+            CalibrationEquation, GifApplicationExtension, GifGraphicControl, ImageOffset, Info,
+            OffsetUnit, PhysicalScale, PixelCalibration, ScaleUnit, StereoLayout,
+        };
+
+        let png = |before_idat: &[(&[u8; 4], &[u8])], after_idat: &[(&[u8; 4], &[u8])]| {
+            let mut png = Vec::new();
+            write_png_sig(&mut png);
+            write_rgba8_ihdr_with_width(&mut png, 8);
+            for (type_, data) in before_idat {
+                write_chunk(&mut png, *type_, data);
+            }
+            write_rgba8_idats(&mut png, 8, 0x100);
+            for (type_, data) in after_idat {
+                write_chunk(&mut png, *type_, data);
+            }
+            write_iend(&mut png);
+            png
+        };
+        let extensions = |info: &Info| {
+            (
+                info.offset,
+                info.pixel_calibration.clone(),
+                info.physical_scale,
+                info.stereo_layout,
+                info.gif_graphic_controls.clone(),
+                info.gif_application_extensions.clone(),
+            )
+        };
+
+        let offs: &[u8] = &[0xff, 0xff, 0xff, 0xfe, 0, 0, 0, 3, 1];
+        let pcal: &[u8] = b"depth\0\0\0\0\0\0\0\x03\xe8\0\x02m\x000\0-1.5e1";
+        let gifg: &[u8] = &[2, 1, 0, 10];
+        let cases = [
+            (
+                png(&[(b"oFFs", offs)], &[]),
+                Info {
+                    offset: Some(ImageOffset {
+                        x: -2,
+                        y: 3,
+                        unit: OffsetUnit::Micrometer,
+                    }),
+                    ..Info::default()
+                },
+            ),
+            // The oFFs chunk must come before the image data.
+            (png(&[], &[(b"oFFs", offs)]), Info::default()),
+            // A second pCAL chunk is ignored.
+            (
+                png(
+                    &[
+                        (b"pCAL", pcal),
+                        (b"pCAL", b"other\0\0\0\0\0\0\0\0\x01\0\x02\x001\x002"),
+                    ],
+                    &[],
+                ),
+                Info {
+                    pixel_calibration: Some(PixelCalibration {
+                        name: "depth".to_string(),
+                        x0: 0,
+                        x1: 1000,
+                        equation: CalibrationEquation::Linear,
+                        unit: "m".to_string(),
+                        parameters: vec![0.0, -15.0],
+                    }),
+                    ..Info::default()
+                },
+            ),
+            (
+                png(&[(b"sCAL", b"\x021.5\x002")], &[]),
+                Info {
+                    physical_scale: Some(PhysicalScale {
+                        unit: ScaleUnit::Radian,
+                        width: 1.5,
+                        height: 2.0,
+                    }),
+                    ..Info::default()
+                },
+            ),
+            // An sCAL chunk with a negative height is ignored.
+            (png(&[(b"sCAL", b"\x011.5\0-2")], &[]), Info::default()),
+            (
+                png(&[(b"sTER", &[1])], &[]),
+                Info {
+                    stereo_layout: Some(StereoLayout::DivergingFuse),
+                    ..Info::default()
+                },
+            ),
+            // The gIFg chunks are collected from before and after the image data.
+            (
+                png(&[(b"gIFg", gifg)], &[(b"gIFg", &[0; 4])]),
+                Info {
+                    gif_graphic_controls: vec![
+                        GifGraphicControl {
+                            disposal_method: 2,
+                            user_input: true,
+                            delay_time: 10,
+                        },
+                        GifGraphicControl {
+                            disposal_method: 0,
+                            user_input: false,
+                            delay_time: 0,
+                        },
+                    ],
+                    ..Info::default()
+                },
+            ),
+            (
+                png(&[(b"gIFx", b"NETSCAPE2.0\x01\0\0")], &[]),
+                Info {
+                    gif_application_extensions: vec![GifApplicationExtension {
+                        identifier: *b"NETSCAPE",
+                        authentication_code: *b"2.0",
+                        data: vec![1, 0, 0],
+                    }],
+                    ..Info::default()
+                },
+            ),
+        ];
+        for (idx, (png, expected)) in cases.iter().enumerate() {
+            let mut reader = Decoder::new(Cursor::new(png)).read_info().unwrap();
+            let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+            reader.next_frame(&mut buf).unwrap();
+            reader.finish().unwrap();
+            assert_eq!(extensions(reader.info()), extensions(expected), "{}", idx);
+        }
+    }
+
+    #[test]
+    fn test_strict_decoding() {
+        use crate::Strictness;
+
+        let png = |before_idat: &[(&[u8; 4], &[u8])], after_iend: &[u8]| {
+            let mut png = Vec::new();
+            write_png_sig(&mut png);
+            write_rgba8_ihdr_with_width(&mut png, 8);
+            for (type_, data) in before_idat {
+                write_chunk(&mut png, *type_, data);
+            }
+            write_rgba8_idats(&mut png, 8, 0x100);
+            write_iend(&mut png);
+            png.extend_from_slice(after_iend);
+            png
+        };
+        let decode = |png: &[u8], strictness: Strictness| {
+            let mut decoder = Decoder::new(Cursor::new(png));
+            decoder.set_strictness(strictness);
+            let mut reader = decoder.read_info()?;
+            let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+            reader.next_frame(&mut buf)?;
+            reader.finish()
+        };
+
+        let phys: &[u8] = &[0, 0, 0, 1, 0, 0, 0, 1, 0];
+        let cases = [
+            // A gamma of zero.
+            png(&[(b"gAMA", &[0; 4])], &[]),
+            // A chunk of the wrong length.
+            png(&[(b"gAMA", &[0; 3])], &[]),
+            png(&[(b"pHYs", phys), (b"pHYs", phys)], &[]),
+            // A chunk that must come before PLTE.
+            png(&[(b"PLTE", &[0; 3]), (b"cICP", &[1, 13, 0, 1])], &[]),
+            // An animation without frames.
+            png(&[(b"acTL", &[0; 8])], &[]),
+            png(&[], b"trailing"),
+        ];
+        for (idx, png) in cases.iter().enumerate() {
+            assert!(decode(png, Strictness::Lenient).is_ok(), "{}", idx);
+            assert!(
+                matches!(
+                    decode(png, Strictness::Strict),
+                    Err(DecodingError::Format(_))
+                ),
+                "{}",
+                idx
+            );
+        }
+
+        // A valid image passes, and an IEND chunk with data is always an error.
+        assert!(decode(&png(&[(b"pHYs", phys)], &[]), Strictness::Strict).is_ok());
+        let mut bad_iend = png(&[], &[]);
+        bad_iend.truncate(bad_iend.len() - 12);
+        write_chunk(&mut bad_iend, b"IEND", b"data");
+        assert!(decode(&bad_iend, Strictness::Lenient).is_err());
+    }
 }
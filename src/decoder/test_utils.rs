@@ -0,0 +1,55 @@
+//! Fixtures for the tests of the decoder and its transformations.
+
+use std::fs::File;
+use std::io::BufReader;
+
+use crate::{BitDepth, Decoder, OutputInfo, Transformations};
+
+/// Returns a decoder for the image `name` of the PNG suite.
+pub(crate) fn pngsuite_decoder(name: &str) -> Decoder<BufReader<File>> {
+    let path = format!("tests/pngsuite/{}.png", name);
+    Decoder::new(BufReader::new(File::open(path).unwrap()))
+}
+
+/// Decodes the first frame of the image `name` of the PNG suite with `transform`, after
+/// `configure` has set the other options of the decoder.
+///
+/// The returned buffer holds exactly the frame described by the [`OutputInfo`], whose color type
+/// is checked against [`Reader::output_color_type`](crate::Reader::output_color_type). The rest of
+/// the image is read with [`Reader::finish`](crate::Reader::finish).
+pub(crate) fn decode_pngsuite_with(
+    name: &str,
+    transform: Transformations,
+    configure: impl FnOnce(&mut Decoder<BufReader<File>>),
+) -> (Vec<u8>, OutputInfo) {
+    let mut decoder = pngsuite_decoder(name);
+    decoder.set_transformations(transform);
+    configure(&mut decoder);
+    let mut reader = decoder.read_info().unwrap();
+    let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+    let info = reader.next_frame(&mut buf).unwrap();
+    assert_eq!(
+        reader.output_color_type(),
+        (info.color_type, info.bit_depth)
+    );
+    reader.finish().unwrap();
+    buf.truncate(info.buffer_size());
+    (buf, info)
+}
+
+/// Decodes the first frame of the image `name` of the PNG suite with `transform`, see
+/// [`decode_pngsuite_with`].
+pub(crate) fn decode_pngsuite(name: &str, transform: Transformations) -> (Vec<u8>, OutputInfo) {
+    decode_pngsuite_with(name, transform, |_| ())
+}
+
+/// Returns the samples of a frame with 8 or 16 bits per sample.
+pub(crate) fn samples(buf: &[u8], info: &OutputInfo) -> Vec<u32> {
+    match info.bit_depth {
+        BitDepth::Sixteen => buf
+            .chunks_exact(2)
+            .map(|s| u16::from_be_bytes([s[0], s[1]]).into())
+            .collect(),
+        _ => buf.iter().map(|&s| s.into()).collect(),
+    }
+}
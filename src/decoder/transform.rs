@@ -1,18 +1,159 @@
 //! Transforming a decompressed, unfiltered row into the final output.
 
+mod alpha;
+mod background;
+mod channels;
+mod gamma;
+mod gray;
 mod palette;
+mod sbit;
 
-use crate::{BitDepth, ColorType, DecodingError, Info, Transformations};
+pub use self::gray::GrayCoefficients;
+
+use crate::{BitDepth, ColorType, DecodingError, Info, ScaledFloat, Transformations};
 
 use super::stream::FormatErrorInner;
 
+/// Parameters of the transformations that are configured on the [`Decoder`](super::Decoder) in
+/// addition to the [`Transformations`] flags.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct TransformOptions {
+    /// The exponent of the display's transfer function, for [`Transformations::GAMMA`].
+    pub(crate) display_gamma: ScaledFloat,
+    /// The value of the samples added by [`Transformations::FILLER`].
+    pub(crate) filler: u16,
+    /// The weights of the colors for [`Transformations::RGB_TO_GRAY`].
+    pub(crate) gray_coefficients: GrayCoefficients,
+    /// The 16-bit RGB color for [`Transformations::BACKGROUND`], instead of the `bKGD` chunk.
+    pub(crate) background: Option<[u16; 3]>,
+}
+
+impl Default for TransformOptions {
+    fn default() -> Self {
+        TransformOptions {
+            // The exponent of a typical display, also used by sRGB.
+            display_gamma: ScaledFloat::from_scaled(220_000),
+            filler: 0xffff,
+            gray_coefficients: GrayCoefficients::default(),
+            background: None,
+        }
+    }
+}
+
 /// Type of a function that can transform a decompressed, unfiltered row (the
 /// 1st argument) into the final pixels (the 2nd argument), optionally using
 /// image metadata (e.g. PLTE data can be accessed using the 3rd argument).
 ///
-/// TODO: If some precomputed state is needed (e.g. to make `expand_paletted...`
-/// faster) then consider changing this into `Box<dyn Fn(...)>`.
-pub type TransformFn = Box<dyn Fn(&[u8], &mut [u8], &Info) + Send + Sync>;
+/// Precomputed state, such as the lookup table of `expand_paletted...` or a
+/// buffer for intermediate rows, is owned by the boxed closure.
+pub type TransformFn = Box<dyn FnMut(&[u8], &mut [u8], &Info) + Send + Sync>;
+
+/// Type of a function that converts the pixels of a row in place, see [`Step`].
+type InPlaceFn = Box<dyn Fn(&mut [u8]) + Send + Sync>;
+
+/// A conversion of the pixels of an expanded row, which may change the size of the pixels.
+struct Step {
+    /// The number of bytes of each pixel before the step.
+    in_len: usize,
+    /// The number of bytes of each pixel after the step.
+    out_len: usize,
+    /// Converts the pixels of the row it is called with, which has room for the larger of the two
+    /// sizes of all its pixels. Steps that grow the pixels work backwards, the others forwards.
+    apply: InPlaceFn,
+}
+
+/// The steps that follow the expansion, along with the format of the row after them.
+struct Pipeline {
+    color_type: ColorType,
+    bit_depth: BitDepth,
+    /// The bit depth of the values of the samples, which is lower than `bit_depth` for samples
+    /// unpacked by [`Transformations::PACKING`].
+    value_depth: BitDepth,
+    steps: Vec<Step>,
+}
+
+impl Pipeline {
+    fn sample_len(&self) -> usize {
+        if self.bit_depth == BitDepth::Sixteen {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// The size of a pixel, which is meaningless for packed pixels. Those are only transformed by
+    /// steps that keep the size.
+    fn pixel_len(&self) -> usize {
+        self.color_type.samples() * self.sample_len()
+    }
+
+    /// Adds a step that converts the row into `color_type`.
+    fn push(&mut self, color_type: ColorType, apply: InPlaceFn) {
+        self.push_with_depth(color_type, self.bit_depth, apply);
+    }
+
+    /// Adds a step that converts the row into `color_type` with samples of `bit_depth`.
+    fn push_with_depth(&mut self, color_type: ColorType, bit_depth: BitDepth, apply: InPlaceFn) {
+        let in_len = self.pixel_len();
+        self.color_type = color_type;
+        self.bit_depth = bit_depth;
+        self.value_depth = bit_depth;
+        let out_len = self.pixel_len();
+        self.steps.push(Step {
+            in_len,
+            out_len,
+            apply,
+        });
+    }
+
+    fn into_transform_fn(self, mut expansion: TransformFn) -> TransformFn {
+        let steps = self.steps;
+        let (Some(first), Some(last)) = (steps.first(), steps.last()) else {
+            return expansion;
+        };
+
+        if steps.iter().all(|step| step.in_len == step.out_len) {
+            return Box::new(move |input, output, info| {
+                expansion(input, output, info);
+                for step in &steps {
+                    (step.apply)(output);
+                }
+            });
+        }
+
+        // Intermediate rows that are larger than the output are converted in a separate buffer.
+        let out_len = last.out_len;
+        let max_len = steps
+            .iter()
+            .map(|step| step.out_len)
+            .fold(first.in_len, usize::max);
+        let mut buffer = Vec::new();
+        Box::new(move |input, output, info| {
+            let pixels = output.len() / out_len;
+            if max_len > out_len {
+                buffer.resize(pixels * max_len, 0);
+                apply_steps(&mut expansion, &steps, input, &mut buffer, info, pixels);
+                output.copy_from_slice(&buffer[..output.len()]);
+            } else {
+                apply_steps(&mut expansion, &steps, input, output, info, pixels);
+            }
+        })
+    }
+}
+
+fn apply_steps(
+    expansion: &mut TransformFn,
+    steps: &[Step],
+    input: &[u8],
+    buffer: &mut [u8],
+    info: &Info,
+    pixels: usize,
+) {
+    expansion(input, &mut buffer[..pixels * steps[0].in_len], info);
+    for step in steps {
+        (step.apply)(&mut buffer[..pixels * step.in_len.max(step.out_len)]);
+    }
+}
 
 /// Returns a transformation function that should be applied to image rows based
 /// on 1) decoded image metadata (`info`) and 2) the transformations requested
@@ -20,12 +161,269 @@ pub type TransformFn = Box<dyn Fn(&[u8], &mut [u8], &Info) + Send + Sync>;
 pub fn create_transform_fn(
     info: &Info,
     transform: Transformations,
+    options: &TransformOptions,
+) -> Result<TransformFn, DecodingError> {
+    let gamma = if transform.contains(Transformations::GAMMA) {
+        gamma::correction_exponent(info, options.display_gamma)
+    } else {
+        None
+    };
+
+    let expansion = create_expansion_fn(info, transform, gamma)?;
+    let (color_type, bit_depth) = expanded_color_type(info, transform);
+    let value_depth = if unpacks(info, transform) {
+        info.bit_depth
+    } else {
+        bit_depth
+    };
+    let mut pipeline = Pipeline {
+        color_type,
+        bit_depth,
+        value_depth,
+        steps: Vec::new(),
+    };
+
+    if widens(transform, pipeline.bit_depth) {
+        pipeline.push_with_depth(
+            pipeline.color_type,
+            BitDepth::Sixteen,
+            Box::new(widen_to_16),
+        );
+    }
+    if transform.contains(Transformations::RESCALE_SBIT) {
+        if let Some(bits) = sbit::significant_bits(info, pipeline.color_type, pipeline.value_depth)
+        {
+            let rescale = sbit::create_rescale(bits, pipeline.sample_len());
+            pipeline.push(pipeline.color_type, rescale);
+        }
+    }
+    if let Some(background) = composite_background(
+        info,
+        transform,
+        options,
+        pipeline.color_type,
+        pipeline.bit_depth,
+    ) {
+        let compositing =
+            background::create_compositing(pipeline.color_type, pipeline.sample_len(), background);
+        pipeline.push(without_alpha(pipeline.color_type), compositing);
+    }
+    if converts_to_gray(transform, pipeline.color_type) {
+        let rgb_to_gray = gray::create_rgb_to_gray(
+            pipeline.color_type,
+            pipeline.sample_len(),
+            options.gray_coefficients,
+        );
+        pipeline.push(gray::gray_color_type(pipeline.color_type), rgb_to_gray);
+    }
+    match gamma {
+        // The palette is already corrected by the expansion, indices can't be corrected.
+        Some(exponent) if info.color_type != ColorType::Indexed => {
+            let correction =
+                gamma::create_correction(exponent, pipeline.color_type, pipeline.value_depth);
+            pipeline.push(pipeline.color_type, correction);
+        }
+        _ => {}
+    }
+    if converts_to_rgb(transform, pipeline.color_type, pipeline.bit_depth) {
+        let gray_to_rgb = gray::create_gray_to_rgb(pipeline.color_type, pipeline.sample_len());
+        pipeline.push(gray::rgb_color_type(pipeline.color_type), gray_to_rgb);
+    }
+
+    let (pixel_len, sample_len) = (pipeline.pixel_len(), pipeline.sample_len());
+    let has_alpha = matches!(
+        pipeline.color_type,
+        ColorType::GrayscaleAlpha | ColorType::Rgba
+    );
+    if transform.contains(Transformations::PREMULTIPLY_ALPHA) && has_alpha {
+        let premultiplication = alpha::create_premultiplication(pixel_len, sample_len);
+        pipeline.push(pipeline.color_type, premultiplication);
+    }
+    if transform.contains(Transformations::INVERT_ALPHA) && has_alpha {
+        let inversion = channels::create_alpha_inversion(pixel_len, sample_len);
+        pipeline.push(pipeline.color_type, inversion);
+    }
+    // Samples are shifted once their values have been transformed, before they are reordered.
+    if transform.contains(Transformations::SHIFT) {
+        if let Some(bits) = sbit::significant_bits(info, pipeline.color_type, pipeline.value_depth)
+        {
+            pipeline.push(pipeline.color_type, sbit::create_shift(bits, sample_len));
+        }
+    }
+    if transform.contains(Transformations::BGR)
+        && matches!(pipeline.color_type, ColorType::Rgb | ColorType::Rgba)
+    {
+        let swap = channels::create_bgr_swap(pixel_len, sample_len);
+        pipeline.push(pipeline.color_type, swap);
+    }
+    if transform.contains(Transformations::SWAP_ALPHA) && has_alpha {
+        let swap = channels::create_alpha_swap(pixel_len, sample_len);
+        pipeline.push(pipeline.color_type, swap);
+    }
+
+    // The filler is added last, in the position that the alpha sample would be swapped to.
+    if adds_filler(transform, pipeline.color_type, pipeline.bit_depth) {
+        let filler = &options.filler.to_be_bytes()[2 - sample_len..];
+        let before = transform.contains(Transformations::SWAP_ALPHA);
+        let insertion = channels::create_filler_insertion(pixel_len, filler, before);
+        let color_type = match pipeline.color_type {
+            ColorType::Grayscale => ColorType::GrayscaleAlpha,
+            _ => ColorType::Rgba,
+        };
+        pipeline.push(color_type, insertion);
+    }
+    if transform.contains(Transformations::SWAP_ENDIAN) && pipeline.bit_depth == BitDepth::Sixteen {
+        pipeline.push(pipeline.color_type, Box::new(swap_endian));
+    }
+
+    Ok(pipeline.into_transform_fn(expansion))
+}
+
+/// Whether [`Transformations::PACKING`] unpacks the samples of the image, as they are not expanded
+/// otherwise.
+fn unpacks(info: &Info, t: Transformations) -> bool {
+    let gray_to_rgb =
+        t.contains(Transformations::GRAY_TO_RGB) && info.color_type == ColorType::Grayscale;
+    t.contains(Transformations::PACKING)
+        && (info.bit_depth as u8) < 8
+        && !expands(t)
+        && !gray_to_rgb
+}
+
+/// Whether the samples of the image are expanded, see [`Transformations::EXPAND`].
+fn expands(t: Transformations) -> bool {
+    t.intersects(Transformations::EXPAND | Transformations::ALPHA | Transformations::EXPAND_16)
+}
+
+/// Whether [`Transformations::EXPAND_16`] widens expanded rows of `bit_depth`.
+fn widens(t: Transformations, bit_depth: BitDepth) -> bool {
+    t.contains(Transformations::EXPAND_16) && bit_depth == BitDepth::Eight
+}
+
+/// The color that [`Transformations::BACKGROUND`] composites rows of the given format onto, if
+/// it applies to them.
+fn composite_background(
+    info: &Info,
+    t: Transformations,
+    options: &TransformOptions,
+    color_type: ColorType,
+    bit_depth: BitDepth,
+) -> Option<[u16; 3]> {
+    let gray = match color_type {
+        ColorType::GrayscaleAlpha => true,
+        ColorType::Rgba => false,
+        _ => return None,
+    };
+    if !t.contains(Transformations::BACKGROUND) {
+        return None;
+    }
+    background::background_color(info, options, gray, bit_depth)
+}
+
+fn without_alpha(color_type: ColorType) -> ColorType {
+    match color_type {
+        ColorType::GrayscaleAlpha => ColorType::Grayscale,
+        ColorType::Rgba => ColorType::Rgb,
+        color_type => color_type,
+    }
+}
+
+/// Whether [`Transformations::RGB_TO_GRAY`] applies to rows of the given format.
+fn converts_to_gray(t: Transformations, color_type: ColorType) -> bool {
+    t.contains(Transformations::RGB_TO_GRAY)
+        && matches!(color_type, ColorType::Rgb | ColorType::Rgba)
+}
+
+/// Whether [`Transformations::GRAY_TO_RGB`] applies to rows of the given format.
+fn converts_to_rgb(t: Transformations, color_type: ColorType, bit_depth: BitDepth) -> bool {
+    t.contains(Transformations::GRAY_TO_RGB)
+        && matches!(color_type, ColorType::Grayscale | ColorType::GrayscaleAlpha)
+        && bit_depth as u8 >= 8
+}
+
+/// Whether [`Transformations::FILLER`] applies to rows of the given format.
+fn adds_filler(t: Transformations, color_type: ColorType, bit_depth: BitDepth) -> bool {
+    t.contains(Transformations::FILLER)
+        && matches!(color_type, ColorType::Grayscale | ColorType::Rgb)
+        && bit_depth as u8 >= 8
+}
+
+/// Returns the color type and bit depth of rows after the `transform`.
+pub(crate) fn output_color_type(
+    info: &Info,
+    t: Transformations,
+    options: &TransformOptions,
+) -> (ColorType, BitDepth) {
+    let (mut color_type, mut bit_depth) = expanded_color_type(info, t);
+    if widens(t, bit_depth) {
+        bit_depth = BitDepth::Sixteen;
+    }
+    if composite_background(info, t, options, color_type, bit_depth).is_some() {
+        color_type = without_alpha(color_type);
+    }
+    if converts_to_gray(t, color_type) {
+        color_type = gray::gray_color_type(color_type);
+    }
+    if converts_to_rgb(t, color_type, bit_depth) {
+        color_type = gray::rgb_color_type(color_type);
+    }
+    color_type = match color_type {
+        ColorType::Grayscale if adds_filler(t, color_type, bit_depth) => ColorType::GrayscaleAlpha,
+        ColorType::Rgb if adds_filler(t, color_type, bit_depth) => ColorType::Rgba,
+        color_type => color_type,
+    };
+    (color_type, bit_depth)
+}
+
+/// Returns the color type and bit depth of rows after their expansion, before the samples are
+/// reordered or filler is added.
+fn expanded_color_type(info: &Info, t: Transformations) -> (ColorType, BitDepth) {
+    use crate::common::ColorType::*;
+    if t == Transformations::IDENTITY {
+        (info.color_type, info.bit_depth)
+    } else {
+        let bits = match info.bit_depth as u8 {
+            16 if t.intersects(Transformations::STRIP_16 | Transformations::SCALE_16) => 8,
+            n if n < 8 && expands(t) => 8,
+            // `GRAY_TO_RGB` needs whole bytes.
+            n if n < 8
+                && t.contains(Transformations::GRAY_TO_RGB)
+                && info.color_type == Grayscale =>
+            {
+                8
+            }
+            n if n < 8 && unpacks(info, t) => 8,
+            n => n,
+        };
+        let color_type = if expands(t) {
+            let has_trns = info.trns.is_some() || t.contains(Transformations::ALPHA);
+            match info.color_type {
+                Grayscale if has_trns => GrayscaleAlpha,
+                Rgb if has_trns => Rgba,
+                Indexed if has_trns => Rgba,
+                Indexed => Rgb,
+                ct => ct,
+            }
+        } else {
+            info.color_type
+        };
+        (color_type, BitDepth::from_u8(bits).unwrap())
+    }
+}
+
+/// Returns the function that expands the samples of a row, or reduces them to 8 bits.
+///
+/// `gamma` is the exponent of the gamma correction that is applied to the palette.
+fn create_expansion_fn(
+    info: &Info,
+    transform: Transformations,
+    gamma: Option<f64>,
 ) -> Result<TransformFn, DecodingError> {
     let color_type = info.color_type;
     let bit_depth = info.bit_depth as u8;
     let trns = info.trns.is_some() || transform.contains(Transformations::ALPHA);
-    let expand =
-        transform.contains(Transformations::EXPAND) || transform.contains(Transformations::ALPHA);
+    let expand = expands(transform);
+    let scale16 = bit_depth == 16 && transform.contains(Transformations::SCALE_16);
     let strip16 = bit_depth == 16 && transform.contains(Transformations::STRIP_16);
     match color_type {
         ColorType::Indexed if expand => {
@@ -44,9 +442,9 @@ pub fn create_transform_fn(
                 ))
             } else {
                 Ok(if trns {
-                    palette::create_expansion_into_rgba8(info)
+                    palette::create_expansion_into_rgba8(info, gamma)
                 } else {
-                    palette::create_expansion_into_rgb8(info)
+                    palette::create_expansion_into_rgb8(info, gamma)
                 })
             }
         }
@@ -57,9 +455,17 @@ pub fn create_transform_fn(
                 expand_gray_u8
             }))
         }
+        ColorType::Grayscale
+            if bit_depth < 8 && transform.contains(Transformations::GRAY_TO_RGB) =>
+        {
+            Ok(Box::new(expand_gray_u8))
+        }
+        _ if unpacks(info, transform) => Ok(Box::new(unpack_u8)),
         ColorType::Grayscale | ColorType::Rgb if expand && trns => {
             Ok(Box::new(if bit_depth == 8 {
                 expand_trns_line
+            } else if scale16 {
+                expand_trns_and_scale_line16
             } else if strip16 {
                 expand_trns_and_strip_line16
             } else {
@@ -67,6 +473,11 @@ pub fn create_transform_fn(
                 expand_trns_line16
             }))
         }
+        ColorType::Grayscale | ColorType::GrayscaleAlpha | ColorType::Rgb | ColorType::Rgba
+            if scale16 =>
+        {
+            Ok(Box::new(transform_row_scale16))
+        }
         ColorType::Grayscale | ColorType::GrayscaleAlpha | ColorType::Rgb | ColorType::Rgba
             if strip16 =>
         {
@@ -86,6 +497,35 @@ fn transform_row_strip16(row: &[u8], output_buffer: &mut [u8], _: &Info) {
     }
 }
 
+/// Rounds a big-endian 16-bit sample to the nearest 8-bit sample, like libpng's
+/// `png_set_scale_16`.
+#[inline(always)]
+fn scale_sample16(sample: &[u8]) -> u8 {
+    let value = u32::from(u16::from_be_bytes([sample[0], sample[1]]));
+    ((value * 255 + 32895) >> 16) as u8
+}
+
+fn transform_row_scale16(row: &[u8], output_buffer: &mut [u8], _: &Info) {
+    for (output, sample) in output_buffer.iter_mut().zip(row.chunks_exact(2)) {
+        *output = scale_sample16(sample);
+    }
+}
+
+fn swap_endian(row: &mut [u8]) {
+    for sample in row.chunks_exact_mut(2) {
+        sample.swap(0, 1);
+    }
+}
+
+/// Widens 8-bit samples at the start of `row` to 16 bits, i.e. multiplies them by 257.
+fn widen_to_16(row: &mut [u8]) {
+    // Going backwards, the samples are only moved onto bytes that have already been moved.
+    for idx in (0..row.len() / 2).rev() {
+        let sample = row[idx];
+        row[2 * idx..][..2].copy_from_slice(&[sample; 2]);
+    }
+}
+
 #[inline(always)]
 fn unpack_bits<F>(input: &[u8], output: &mut [u8], channels: usize, bit_depth: u8, func: F)
 where
@@ -178,6 +618,26 @@ fn expand_trns_and_strip_line16(input: &[u8], output: &mut [u8], info: &Info) {
     }
 }
 
+fn expand_trns_and_scale_line16(input: &[u8], output: &mut [u8], info: &Info) {
+    let channels = info.color_type.samples();
+    let trns = info.trns.as_deref();
+    for (input, output) in input
+        .chunks_exact(channels * 2)
+        .zip(output.chunks_exact_mut(channels + 1))
+    {
+        for i in 0..channels {
+            output[i] = scale_sample16(&input[i * 2..]);
+        }
+        output[channels] = if Some(input) == trns { 0 } else { 0xFF };
+    }
+}
+
+fn unpack_u8(row: &[u8], buffer: &mut [u8], info: &Info) {
+    unpack_bits(row, buffer, 1, info.bit_depth as u8, |val, chunk| {
+        chunk[0] = val
+    });
+}
+
 fn expand_gray_u8(row: &[u8], buffer: &mut [u8], info: &Info) {
     let scaling_factor = (255) / ((1u16 << info.bit_depth as u8) - 1) as u8;
     unpack_bits(row, buffer, 1, info.bit_depth as u8, |val, chunk| {
@@ -201,3 +661,136 @@ fn expand_gray_u8_with_trns(row: &[u8], buffer: &mut [u8], info: &Info) {
         chunk[0] = pixel * scaling_factor
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::test_utils::{decode_pngsuite, decode_pngsuite_with, pngsuite_decoder};
+
+    #[test]
+    fn sixteen_bit_transformations() {
+        use Transformations as T;
+
+        // `SCALE_16` rounds to the nearest 8-bit value where `STRIP_16` truncates.
+        for (name, base) in [
+            ("basn6a16", T::IDENTITY),
+            ("basn0g16", T::IDENTITY),
+            ("tbbn2c16", T::EXPAND),
+        ] {
+            let (expected, _) = decode_pngsuite(name, base);
+            let (actual, info) = decode_pngsuite(name, base | T::SCALE_16);
+            let (stripped, _) = decode_pngsuite(name, base | T::SCALE_16 | T::STRIP_16);
+            assert_eq!(info.bit_depth, BitDepth::Eight);
+            assert_eq!(actual, stripped, "SCALE_16 takes precedence");
+
+            let oracle: Vec<u8> = expected
+                .chunks_exact(2)
+                .map(|s| (f64::from(u16::from_be_bytes([s[0], s[1]])) / 257.0).round() as u8)
+                .collect();
+            assert_eq!(actual, oracle, "{}", name);
+        }
+
+        // `EXPAND_16` widens the expanded samples, `v` becomes `v * 257`.
+        for name in ["basn3p04", "tbrn2c08", "basn0g02", "basn4a08"] {
+            let (expected, expected_info) = decode_pngsuite(name, T::EXPAND);
+            let (actual, info) = decode_pngsuite(name, T::EXPAND_16);
+            assert_eq!(info.color_type, expected_info.color_type);
+            assert_eq!(info.bit_depth, BitDepth::Sixteen);
+
+            let oracle: Vec<u8> = expected.iter().flat_map(|&v| [v, v]).collect();
+            assert_eq!(actual, oracle, "{}", name);
+        }
+
+        let (expected, _) = decode_pngsuite("basn2c16", T::IDENTITY);
+        assert_eq!(decode_pngsuite("basn2c16", T::EXPAND_16).0, expected);
+    }
+
+    #[test]
+    fn swap_endian_and_next_frame_u16() {
+        use crate::{InterlaceHandling as IH, Scale, Transformations as T};
+
+        let swap = |samples: Vec<u8>| -> Vec<u8> {
+            samples.chunks_exact(2).flat_map(|s| [s[1], s[0]]).collect()
+        };
+
+        for name in ["basn6a16", "basi0g16", "tbbn2c16"] {
+            // The samples are swapped for every arrangement of the rows, and converted to `u16`
+            // row by row.
+            let handlings = [IH::Sparkle, IH::Rectangle, IH::RawRows];
+            for (handling, scale) in handlings
+                .into_iter()
+                .flat_map(|h| [(h, Scale::Full), (h, Scale::Half)])
+            {
+                let configure = |decoder: &mut crate::Decoder<_>| {
+                    decoder.set_interlace_handling(handling);
+                    decoder.set_scale(scale);
+                };
+                let (expected, _) = decode_pngsuite_with(name, T::EXPAND, configure);
+                let (swapped, _) =
+                    decode_pngsuite_with(name, T::EXPAND | T::SWAP_ENDIAN, configure);
+                assert_eq!(swapped, swap(expected.clone()), "{} {:?}", name, scale);
+
+                let samples: Vec<u16> = expected
+                    .chunks_exact(2)
+                    .map(|s| u16::from_be_bytes([s[0], s[1]]))
+                    .collect();
+                for transform in [T::EXPAND, T::EXPAND | T::SWAP_ENDIAN] {
+                    let mut decoder = pngsuite_decoder(name);
+                    decoder.set_transformations(transform);
+                    configure(&mut decoder);
+                    let mut reader = decoder.read_info().unwrap();
+                    let mut buf = vec![0; reader.output_buffer_size().unwrap() / 2];
+                    let info = reader.next_frame_u16(&mut buf).unwrap();
+                    assert_eq!(
+                        &buf[..info.buffer_size() / 2],
+                        samples,
+                        "{} {:?} {:?}",
+                        name,
+                        handling,
+                        scale
+                    );
+                }
+            }
+        }
+
+        // 8-bit samples are not swapped, and can't be decoded into `u16`.
+        let (expected, _) = decode_pngsuite("basn2c08", T::IDENTITY);
+        assert_eq!(decode_pngsuite("basn2c08", T::SWAP_ENDIAN).0, expected);
+        let mut reader = pngsuite_decoder("basn2c08").read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+        assert!(matches!(
+            reader.next_frame_u16(&mut buf),
+            Err(DecodingError::Parameter(_))
+        ));
+    }
+
+    #[test]
+    fn packing_transformation() {
+        use Transformations as T;
+
+        for name in ["basn3p01", "basn3p02", "basn3p04", "basn0g01", "basn0g04"] {
+            let (packed, info) = decode_pngsuite(name, T::IDENTITY);
+            let depth = info.bit_depth as usize;
+            let mask = (1u8 << depth) - 1;
+            let oracle: Vec<u8> = packed
+                .chunks_exact(info.line_size)
+                .flat_map(|line| {
+                    (0..info.width as usize).map(move |x| {
+                        let shift = 8 - depth - (x * depth) % 8;
+                        (line[x * depth / 8] >> shift) & mask
+                    })
+                })
+                .collect();
+
+            let (unpacked, unpacked_info) = decode_pngsuite(name, T::PACKING);
+            assert_eq!(unpacked_info.color_type, info.color_type, "{}", name);
+            assert_eq!(unpacked_info.bit_depth, BitDepth::Eight, "{}", name);
+            assert_eq!(unpacked, oracle, "{}", name);
+
+            // Expansion takes precedence, as it unpacks the samples as well.
+            let (expanded, expanded_info) = decode_pngsuite(name, T::EXPAND | T::PACKING);
+            assert_eq!(expanded, decode_pngsuite(name, T::EXPAND).0, "{}", name);
+            assert_ne!(expanded_info.color_type, ColorType::Indexed, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Premultiplying the color samples of each pixel with its alpha sample.
+//!
+//! See [`Encoder::set_premultiplied_alpha`](crate::Encoder::set_premultiplied_alpha) for the
+//! reverse conversion.
+
+use super::InPlaceFn;
+
+/// Multiplies the color samples of each pixel of `pixel_len` bytes with the alpha sample at its
+/// end, rounded to the nearest value.
+pub(super) fn create_premultiplication(pixel_len: usize, sample_len: usize) -> InPlaceFn {
+    if sample_len == 2 {
+        Box::new(move |row| {
+            for pixel in row.chunks_exact_mut(pixel_len) {
+                let (colors, alpha) = pixel.split_at_mut(pixel_len - 2);
+                let alpha = u32::from(u16::from_be_bytes([alpha[0], alpha[1]]));
+                for sample in colors.chunks_exact_mut(2) {
+                    let value = u32::from(u16::from_be_bytes([sample[0], sample[1]]));
+                    let value = (value * alpha + 32767) / 65535;
+                    sample.copy_from_slice(&(value as u16).to_be_bytes());
+                }
+            }
+        })
+    } else {
+        Box::new(move |row| {
+            for pixel in row.chunks_exact_mut(pixel_len) {
+                let (colors, alpha) = pixel.split_at_mut(pixel_len - 1);
+                let alpha = u32::from(alpha[0]);
+                for sample in colors {
+                    *sample = ((u32::from(*sample) * alpha + 127) / 255) as u8;
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn premultiply() {
+        let mut row = [255, 128, 0, 128, 200, 100, 50, 0, 7, 255];
+        create_premultiplication(4, 1)(&mut row[..8]);
+        assert_eq!(row[..8], [128, 64, 0, 128, 0, 0, 0, 0]);
+        create_premultiplication(2, 1)(&mut row[8..]);
+        assert_eq!(row[8..], [7, 255]);
+
+        let mut row = [0xff, 0xff, 0x80, 0x00];
+        create_premultiplication(4, 2)(&mut row);
+        assert_eq!(row, [0x80, 0x00, 0x80, 0x00]);
+    }
+
+    #[test]
+    fn premultiply_alpha_transformation() {
+        use crate::decoder::test_utils::{decode_pngsuite, samples};
+        use crate::{ColorType, Transformations as T};
+
+        for (name, base) in [
+            ("basn6a08", T::IDENTITY),
+            ("basn6a16", T::IDENTITY),
+            ("basn4a08", T::IDENTITY),
+            ("basn4a16", T::IDENTITY),
+            ("tbrn2c08", T::EXPAND),
+            ("tbbn3p08", T::EXPAND),
+            ("tbwn0g16", T::EXPAND),
+            ("basn2c08", T::FILLER),
+        ] {
+            let (expected, info) = decode_pngsuite(name, base);
+            let (actual, _) = decode_pngsuite(name, base | T::PREMULTIPLY_ALPHA);
+            assert!(matches!(
+                info.color_type,
+                ColorType::GrayscaleAlpha | ColorType::Rgba
+            ));
+
+            let max = if info.bit_depth as u8 == 16 {
+                65535.0
+            } else {
+                255.0
+            };
+            let channels = info.color_type.samples();
+            let is_filler = base.contains(T::FILLER);
+            let mut oracle = Vec::new();
+            for pixel in samples(&expected, &info).chunks_exact(channels) {
+                let alpha = if is_filler {
+                    max
+                } else {
+                    f64::from(pixel[channels - 1])
+                };
+                for &sample in &pixel[..channels - 1] {
+                    oracle.push((f64::from(sample) * alpha / max).round() as u32);
+                }
+                oracle.push(pixel[channels - 1]);
+            }
+            assert_eq!(samples(&actual, &info), oracle, "{}", name);
+        }
+
+        // The alpha sample is moved after premultiplication.
+        let (expected, _) = decode_pngsuite("basn6a08", T::PREMULTIPLY_ALPHA);
+        let (actual, _) = decode_pngsuite("basn6a08", T::PREMULTIPLY_ALPHA | T::SWAP_ALPHA);
+        let swapped: Vec<u8> = expected
+            .chunks_exact(4)
+            .flat_map(|p| [p[3], p[0], p[1], p[2]])
+            .collect();
+        assert_eq!(actual, swapped);
+    }
+}
@@ -0,0 +1,193 @@
+//! Compositing pixels with alpha onto a background color.
+//!
+//! The samples are blended as they are stored in the image, i.e. before any gamma correction,
+//! with the alpha sample as the weight of the color: `color * alpha + background * (1 - alpha)`.
+
+use super::{InPlaceFn, TransformOptions};
+use crate::{BitDepth, ColorType, Info};
+
+/// The background color for rows with samples of `bit_depth`, either the color set on the decoder
+/// or else that of the `bKGD` chunk of the image. Gray backgrounds have three equal samples.
+///
+/// Returns `None` if there is no background, or if the `bKGD` chunk of an indexed image refers to
+/// a missing palette entry.
+pub(super) fn background_color(
+    info: &Info,
+    options: &TransformOptions,
+    gray: bool,
+    bit_depth: BitDepth,
+) -> Option<[u16; 3]> {
+    let (rgb, depth) = match (options.background, info.bkgd.as_deref()) {
+        (Some(rgb), _) => (rgb, 16),
+        (None, Some(bkgd)) => match info.color_type {
+            ColorType::Indexed => {
+                let idx = usize::from(bkgd[0]);
+                let entry = info.palette.as_deref()?.get(3 * idx..3 * idx + 3)?;
+                ([entry[0], entry[1], entry[2]].map(u16::from), 8)
+            }
+            ColorType::Grayscale | ColorType::GrayscaleAlpha => {
+                let value = u16::from_be_bytes([bkgd[0], bkgd[1]]);
+                ([value; 3], info.bit_depth as u8)
+            }
+            ColorType::Rgb | ColorType::Rgba => {
+                let sample = |idx: usize| u16::from_be_bytes([bkgd[2 * idx], bkgd[2 * idx + 1]]);
+                ([sample(0), sample(1), sample(2)], info.bit_depth as u8)
+            }
+        },
+        (None, None) => return None,
+    };
+
+    let (max_in, max_out) = ((1u32 << depth) - 1, (1u32 << bit_depth as u8) - 1);
+    let rgb = rgb.map(|value| {
+        let value = u32::from(value).min(max_in);
+        ((value * max_out + max_in / 2) / max_in) as u16
+    });
+    Some(if gray {
+        [options.gray_coefficients.gray_of(rgb); 3]
+    } else {
+        rgb
+    })
+}
+
+/// Composites GrayscaleAlpha or RGBA pixels onto `background` and removes their alpha sample,
+/// front to back.
+pub(super) fn create_compositing(
+    color_type: ColorType,
+    sample_len: usize,
+    background: [u16; 3],
+) -> InPlaceFn {
+    let colors = color_type.samples() - 1;
+    let in_len = (colors + 1) * sample_len;
+    let out_len = colors * sample_len;
+    let max = if sample_len == 2 { 0xffff } else { 0xff };
+    let blend = move |value: u32, background: u16, alpha: u32| {
+        (value * alpha + u32::from(background) * (max - alpha) + max / 2) / max
+    };
+
+    Box::new(move |row| {
+        for idx in 0..row.len() / in_len {
+            let (src, out) = (idx * in_len, idx * out_len);
+            if sample_len == 2 {
+                let sample = |row: &[u8], pos: usize| {
+                    u32::from(u16::from_be_bytes([row[pos], row[pos + 1]]))
+                };
+                let alpha = sample(row, src + out_len);
+                for channel in 0..colors {
+                    let value = sample(row, src + 2 * channel);
+                    let value = blend(value, background[channel], alpha);
+                    row[out + 2 * channel..][..2].copy_from_slice(&(value as u16).to_be_bytes());
+                }
+            } else {
+                let alpha = u32::from(row[src + out_len]);
+                for channel in 0..colors {
+                    let value = u32::from(row[src + channel]);
+                    row[out + channel] = blend(value, background[channel], alpha) as u8;
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composite_rgba() {
+        let mut row = [255, 0, 0, 255, 255, 0, 0, 0, 255, 0, 0, 128];
+        create_compositing(ColorType::Rgba, 1, [0, 0, 255])(&mut row);
+        assert_eq!(row[..9], [255, 0, 0, 0, 0, 255, 128, 0, 127]);
+
+        let mut row = [0x00, 0x00, 0x80, 0x00];
+        create_compositing(ColorType::GrayscaleAlpha, 2, [0xffff; 3])(&mut row);
+        assert_eq!(row[..2], [0x7f, 0xff]);
+    }
+
+    #[test]
+    fn background_from_bkgd() {
+        let mut info = Info::with_size(1, 1);
+        info.color_type = ColorType::GrayscaleAlpha;
+        info.bit_depth = BitDepth::Sixteen;
+        info.bkgd = Some(vec![0xff, 0xff].into());
+        let options = TransformOptions::default();
+        assert_eq!(
+            background_color(&info, &options, true, BitDepth::Eight),
+            Some([0xff; 3])
+        );
+
+        let options = TransformOptions {
+            background: Some([0xffff, 0, 0]),
+            ..TransformOptions::default()
+        };
+        assert_eq!(
+            background_color(&info, &options, false, BitDepth::Eight),
+            Some([0xff, 0, 0])
+        );
+    }
+
+    #[test]
+    fn background_transformation() {
+        use crate::decoder::test_utils::{decode_pngsuite_with, samples};
+        use crate::Transformations as T;
+
+        let decode = |name, transform, color: Option<[u16; 3]>| {
+            let (buf, info) = decode_pngsuite_with(name, transform, |decoder| {
+                if let Some(color) = color {
+                    decoder.set_background_color(color);
+                }
+            });
+            (samples(&buf, &info), info)
+        };
+
+        for (name, base, color, background) in [
+            ("bgbn4a08", T::IDENTITY, None, [0; 3]),
+            ("bggn4a16", T::IDENTITY, None, [0xab84; 3]),
+            ("bggn4a16", T::STRIP_16, None, [171; 3]),
+            ("bgwn6a08", T::IDENTITY, None, [255; 3]),
+            ("bgyn6a16", T::IDENTITY, None, [0xffff, 0xffff, 0]),
+            ("tbrn2c08", T::EXPAND, None, [255, 0, 0]),
+            ("tbyn3p08", T::EXPAND, None, [255, 255, 0]),
+            ("tbwn0g16", T::EXPAND, None, [0xffff; 3]),
+            (
+                "bgan6a08",
+                T::IDENTITY,
+                Some([0, 0x8080, 0xffff]),
+                [0, 128, 255],
+            ),
+            ("bgwn6a08", T::IDENTITY, Some([0; 3]), [0; 3]),
+            // The gray value of red, with the default weights of `RGB_TO_GRAY`.
+            ("bggn4a16", T::IDENTITY, Some([0xffff, 0, 0]), [13932; 3]),
+        ] {
+            let (expected, expected_info) = decode(name, base, color);
+            let (actual, info) = decode(name, base | T::BACKGROUND, color);
+            let colors = match expected_info.color_type {
+                ColorType::GrayscaleAlpha => 1,
+                ColorType::Rgba => 3,
+                _ => unreachable!(),
+            };
+            assert_eq!(info.color_type.samples(), colors, "{}", name);
+
+            let max = if info.bit_depth as u8 == 16 {
+                0xffff
+            } else {
+                0xff
+            };
+            let mut oracle = Vec::new();
+            for pixel in expected.chunks_exact(colors + 1) {
+                let alpha = f64::from(pixel[colors]) / f64::from(max);
+                for channel in 0..colors {
+                    let value = f64::from(pixel[channel]) * alpha
+                        + f64::from(background[channel]) * (1.0 - alpha);
+                    oracle.push(value.round() as u32);
+                }
+            }
+            assert_eq!(actual, oracle, "{} {:?}", name, color);
+        }
+
+        // Without a background the alpha is kept.
+        let (expected, _) = decode("bgan6a08", T::IDENTITY, None);
+        let (actual, info) = decode("bgan6a08", T::BACKGROUND, None);
+        assert_eq!(info.color_type, ColorType::Rgba);
+        assert_eq!(actual, expected);
+    }
+}
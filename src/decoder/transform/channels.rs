@@ -0,0 +1,156 @@
+//! Reordering the samples of each pixel and adding filler samples.
+//!
+//! These operate on rows of 8 or 16-bit samples after expansion, so that e.g. palette entries end
+//! up in the requested channel order as well.
+
+use super::InPlaceFn;
+
+/// Swaps the red and blue samples of RGB or RGBA pixels of `pixel_len` bytes.
+pub(super) fn create_bgr_swap(pixel_len: usize, sample_len: usize) -> InPlaceFn {
+    Box::new(move |row| {
+        for pixel in row.chunks_exact_mut(pixel_len) {
+            let (red, rest) = pixel.split_at_mut(sample_len);
+            red.swap_with_slice(&mut rest[sample_len..][..sample_len]);
+        }
+    })
+}
+
+/// Moves the alpha sample at the end of each pixel in front of the color samples.
+pub(super) fn create_alpha_swap(pixel_len: usize, sample_len: usize) -> InPlaceFn {
+    Box::new(move |row| {
+        for pixel in row.chunks_exact_mut(pixel_len) {
+            pixel.rotate_right(sample_len);
+        }
+    })
+}
+
+/// Converts the alpha sample at the end of each pixel from opacity to transparency.
+pub(super) fn create_alpha_inversion(pixel_len: usize, sample_len: usize) -> InPlaceFn {
+    Box::new(move |row| {
+        for pixel in row.chunks_exact_mut(pixel_len) {
+            for byte in &mut pixel[pixel_len - sample_len..] {
+                *byte = !*byte;
+            }
+        }
+    })
+}
+
+/// Adds the `filler` sample to each pixel of `pixel_len` bytes. The filler goes in front of the
+/// other samples if `before` is set, otherwise after them.
+pub(super) fn create_filler_insertion(pixel_len: usize, filler: &[u8], before: bool) -> InPlaceFn {
+    let filler = filler.to_vec();
+    Box::new(move |row| insert_filler(row, pixel_len, &filler, before))
+}
+
+/// Adds the `filler` sample to each pixel of `pixel_len` bytes at the start of `row`, expanding
+/// the pixels into all of `row`.
+fn insert_filler(row: &mut [u8], pixel_len: usize, filler: &[u8], before: bool) {
+    let out_len = pixel_len + filler.len();
+    let (samples_pos, filler_pos) = if before {
+        (filler.len(), 0)
+    } else {
+        (0, pixel_len)
+    };
+
+    // Going backwards, the pixels are only moved onto bytes that have already been moved.
+    for idx in (0..row.len() / out_len).rev() {
+        let (src, out) = (idx * pixel_len, idx * out_len);
+        row.copy_within(src..src + pixel_len, out + samples_pos);
+        row[out + filler_pos..][..filler.len()].copy_from_slice(filler);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reorder_16bit_rgba() {
+        let mut row = [1, 2, 3, 4, 5, 6, 7, 8];
+        create_bgr_swap(8, 2)(&mut row);
+        assert_eq!(row, [5, 6, 3, 4, 1, 2, 7, 8]);
+        create_alpha_swap(8, 2)(&mut row);
+        assert_eq!(row, [7, 8, 5, 6, 3, 4, 1, 2]);
+    }
+
+    #[test]
+    fn filler() {
+        let mut row = [1, 2, 3, 4, 5, 6, 0, 0];
+        insert_filler(&mut row, 3, &[0xff], false);
+        assert_eq!(row, [1, 2, 3, 0xff, 4, 5, 6, 0xff]);
+
+        let mut row = [1, 2, 3, 4, 0, 0, 0, 0];
+        insert_filler(&mut row, 2, &[0xab, 0xcd], true);
+        assert_eq!(row, [0xab, 0xcd, 1, 2, 0xab, 0xcd, 3, 4]);
+    }
+
+    #[test]
+    fn channel_order_transformations() {
+        use crate::decoder::test_utils::decode_pngsuite_with;
+        use crate::{ColorType, Transformations as T};
+
+        let decode = |name, transform, filler| {
+            decode_pngsuite_with(name, transform, |d| d.set_filler(filler))
+        };
+
+        for (name, base, reorder) in [
+            ("basn6a08", T::IDENTITY, T::BGR),
+            ("basn6a08", T::IDENTITY, T::SWAP_ALPHA),
+            (
+                "basn6a08",
+                T::IDENTITY,
+                T::BGR | T::SWAP_ALPHA | T::INVERT_ALPHA,
+            ),
+            ("basn6a16", T::STRIP_16, T::BGR | T::INVERT_ALPHA),
+            ("basn4a16", T::IDENTITY, T::SWAP_ALPHA),
+            ("basn2c16", T::STRIP_16, T::FILLER),
+            ("basn2c16", T::IDENTITY, T::FILLER | T::BGR | T::SWAP_ALPHA),
+            ("basn3p04", T::EXPAND, T::BGR | T::FILLER),
+            ("tbrn2c08", T::EXPAND, T::BGR | T::FILLER),
+            ("basn0g08", T::IDENTITY, T::FILLER | T::INVERT_ALPHA),
+            ("basi2c08", T::IDENTITY, T::FILLER),
+            ("basn0g02", T::IDENTITY, T::FILLER | T::SWAP_ALPHA),
+        ] {
+            let (expected, expected_info) = decode(name, base, 0);
+            let (actual, info) = decode(name, base | reorder, 0x1234);
+
+            let sample_len = if info.bit_depth as u8 == 16 { 2 } else { 1 };
+            let filler = &[0x12, 0x34][2 - sample_len..];
+            let samples = expected_info.color_type.samples();
+            let has_alpha = matches!(
+                expected_info.color_type,
+                ColorType::GrayscaleAlpha | ColorType::Rgba
+            );
+            let adds_filler =
+                reorder.contains(T::FILLER) && !has_alpha && info.bit_depth as u8 >= 8;
+            if (info.bit_depth as u8) < 8 {
+                // Packed samples are left alone.
+                assert_eq!(actual, expected, "{}", name);
+                continue;
+            }
+
+            let mut oracle = Vec::new();
+            for row in expected.chunks_exact(expected_info.line_size) {
+                for pixel in row.chunks_exact(samples * sample_len) {
+                    let mut pixel: Vec<&[u8]> = pixel.chunks_exact(sample_len).collect();
+                    let inverted: Vec<u8>;
+                    if reorder.contains(T::INVERT_ALPHA) && has_alpha {
+                        inverted = pixel[samples - 1].iter().map(|b| !b).collect();
+                        pixel[samples - 1] = &inverted;
+                    }
+                    if reorder.contains(T::BGR) && samples >= 3 {
+                        pixel.swap(0, 2);
+                    }
+                    if adds_filler {
+                        pixel.push(filler);
+                    }
+                    if reorder.contains(T::SWAP_ALPHA) && (has_alpha || adds_filler) {
+                        pixel.rotate_right(1);
+                    }
+                    oracle.extend(pixel.concat());
+                }
+            }
+            assert_eq!(actual, oracle, "{} {:?}", name, reorder);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Gamma correction of samples, from the gamma of the image to the gamma of the display.
+//!
+//! The PNG specification describes the conversion for display in section 13.13: a sample `s`,
+//! normalized to `0.0..=1.0`, is displayed as `s ^ (1 / (file_gamma * display_exponent))`. The
+//! corrected values of all samples of a bit depth are computed once, up front, and rows are then
+//! converted through these lookup tables.
+
+use super::InPlaceFn;
+use crate::{BitDepth, ColorType, Info, ScaledFloat};
+
+/// Corrections with an exponent this close to 1 are skipped, like libpng does.
+const THRESHOLD: f64 = 0.05;
+
+/// The exponent that converts the samples of the image for a display with `display_gamma`.
+///
+/// Returns `None` if the image does not specify its gamma or if the correction would not be
+/// noticeable.
+pub(super) fn correction_exponent(info: &Info, display_gamma: ScaledFloat) -> Option<f64> {
+    let file_gamma = f64::from(info.gamma()?.into_value());
+    let exponent = 1.0 / (file_gamma * f64::from(display_gamma.into_value()));
+    (exponent.is_finite() && (exponent - 1.0).abs() >= THRESHOLD).then_some(exponent)
+}
+
+fn correct(sample: u32, max: u32, exponent: f64) -> u32 {
+    let max = f64::from(max);
+    ((f64::from(sample) / max).powf(exponent) * max).round() as u32
+}
+
+/// Maps each byte of a row of 8-bit samples, or of packed samples with a lower bit depth, to the
+/// byte with all its samples corrected.
+pub(super) fn create_table8(exponent: f64, bit_depth: u8) -> [u8; 256] {
+    let max = (1u32 << bit_depth) - 1;
+    let mut table = [0; 256];
+    for (byte, entry) in table.iter_mut().enumerate() {
+        for shift in (0..8).step_by(bit_depth.into()) {
+            let sample = (byte as u32 >> shift) & max;
+            *entry |= (correct(sample, max, exponent) << shift) as u8;
+        }
+    }
+    table
+}
+
+fn create_table16(exponent: f64) -> Vec<u16> {
+    (0..=u16::MAX)
+        .map(|sample| correct(sample.into(), u16::MAX.into(), exponent) as u16)
+        .collect()
+}
+
+/// Applies the gamma correction to the color samples of the palette, leaving the alpha alone.
+pub(super) fn correct_palette(rgba_palette: &mut [[u8; 4]; 256], exponent: f64) {
+    let table = create_table8(exponent, 8);
+    for rgba in rgba_palette.iter_mut() {
+        for sample in &mut rgba[..3] {
+            *sample = table[usize::from(*sample)];
+        }
+    }
+}
+
+/// Creates a function that applies the gamma correction in place to a row of the given output
+/// format. Alpha samples are linear and stay unchanged.
+///
+/// Indexed rows are not supported, their palette needs to be corrected during expansion.
+pub(super) fn create_correction(
+    exponent: f64,
+    color_type: ColorType,
+    bit_depth: BitDepth,
+) -> InPlaceFn {
+    debug_assert_ne!(color_type, ColorType::Indexed);
+    let samples = color_type.samples();
+    let color_samples = match color_type {
+        ColorType::GrayscaleAlpha | ColorType::Rgba => samples - 1,
+        _ => samples,
+    };
+
+    match bit_depth {
+        BitDepth::Sixteen => {
+            let table = create_table16(exponent);
+            Box::new(move |row| {
+                for pixel in row.chunks_exact_mut(2 * samples) {
+                    for sample in pixel[..2 * color_samples].chunks_exact_mut(2) {
+                        let value = u16::from_be_bytes([sample[0], sample[1]]);
+                        sample.copy_from_slice(&table[usize::from(value)].to_be_bytes());
+                    }
+                }
+            })
+        }
+        bit_depth if color_samples == samples => {
+            // Every byte holds only color samples, possibly packed.
+            let table = create_table8(exponent, bit_depth as u8);
+            Box::new(move |row| {
+                for byte in row {
+                    *byte = table[usize::from(*byte)];
+                }
+            })
+        }
+        _ => {
+            let table = create_table8(exponent, 8);
+            Box::new(move |row| {
+                for pixel in row.chunks_exact_mut(samples) {
+                    for sample in &mut pixel[..color_samples] {
+                        *sample = table[usize::from(*sample)];
+                    }
+                }
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packed_table() {
+        // An exponent of 2 squares the normalized samples: 1/3 => 1/9, 2/3 => 4/9.
+        let table = create_table8(2.0, 2);
+        assert_eq!(table[0b00_01_10_11], 0b00_00_01_11);
+        assert_eq!(table[0b01_01_01_01], 0);
+
+        let table = create_table8(2.0, 8);
+        assert_eq!((table[0], table[128], table[255]), (0, 64, 255));
+    }
+
+    #[test]
+    fn correction_skips_alpha() {
+        let correction = create_correction(2.0, ColorType::GrayscaleAlpha, BitDepth::Sixteen);
+        let mut row = [0x80, 0x00, 0x80, 0x00, 0xff, 0xff, 0x12, 0x34];
+        correction(&mut row);
+        assert_eq!(row, [0x40, 0x00, 0x80, 0x00, 0xff, 0xff, 0x12, 0x34]);
+
+        let correction = create_correction(2.0, ColorType::Rgba, BitDepth::Eight);
+        let mut row = [128, 255, 0, 128];
+        correction(&mut row);
+        assert_eq!(row, [64, 255, 0, 128]);
+    }
+
+    #[test]
+    fn gamma_transformation() {
+        use crate::decoder::test_utils::decode_pngsuite_with;
+        use crate::Transformations;
+
+        let decode = |name, transform| {
+            decode_pngsuite_with(name, transform, |decoder| {
+                decoder.set_display_gamma(ScaledFloat::new(2.0));
+            })
+        };
+
+        for (name, transform, file_gamma) in [
+            ("g03n0g16", Transformations::EXPAND, 0.35),
+            ("g25n0g16", Transformations::STRIP_16, 2.5),
+            ("g05n2c08", Transformations::IDENTITY, 0.55),
+            ("g07n3p04", Transformations::EXPAND, 0.7),
+            ("basn0g02", Transformations::IDENTITY, 1.0),
+            ("basn4a16", Transformations::IDENTITY, 1.0),
+            ("basn6a08", Transformations::IDENTITY, 1.0),
+        ] {
+            let (expected, expected_info) = decode(name, transform);
+            let (actual, info) = decode(name, transform | Transformations::GAMMA);
+            assert_eq!(info, expected_info);
+
+            let exponent = 1.0 / (file_gamma * 2.0);
+            let depth = info.bit_depth as usize;
+            let max = (1u32 << depth) - 1;
+            let samples = info.color_type.samples();
+            let has_alpha = matches!(info.color_type, ColorType::GrayscaleAlpha | ColorType::Rgba);
+            let sample = |row: &[u8], idx: usize| -> u32 {
+                match depth {
+                    16 => u16::from_be_bytes([row[2 * idx], row[2 * idx + 1]]).into(),
+                    _ => {
+                        let shift = 8 - depth - idx * depth % 8;
+                        u32::from(row[idx * depth / 8] >> shift) & max
+                    }
+                }
+            };
+
+            for (row, expected_row) in actual
+                .chunks_exact(info.line_size)
+                .zip(expected.chunks_exact(info.line_size))
+            {
+                for idx in 0..info.width as usize * samples {
+                    let value = sample(expected_row, idx);
+                    let corrected = if has_alpha && idx % samples == samples - 1 {
+                        value
+                    } else {
+                        let normalized = f64::from(value) / f64::from(max);
+                        (normalized.powf(exponent) * f64::from(max)).round() as u32
+                    };
+                    assert_eq!(sample(row, idx), corrected, "{} sample {}", name, idx);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn gamma_transformation_is_skipped() {
+        use crate::decoder::test_utils::decode_pngsuite_with;
+        use crate::Transformations;
+
+        // Indexed images are only corrected when expanded, and `g10n2c08` is linear, like the
+        // display.
+        for (name, display_gamma) in [("g03n3p04", 2.2), ("g10n2c08", 1.0)] {
+            let decode = |transform| {
+                decode_pngsuite_with(name, transform, |decoder| {
+                    decoder.set_display_gamma(ScaledFloat::new(display_gamma));
+                })
+                .0
+            };
+            assert_eq!(
+                decode(Transformations::GAMMA),
+                decode(Transformations::IDENTITY)
+            );
+        }
+    }
+}
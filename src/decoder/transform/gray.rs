@@ -0,0 +1,279 @@
+//! Conversions between grayscale and RGB.
+//!
+//! Gray samples are copied into all three color samples. RGB samples are combined into a weighted
+//! sum, with weights that add up to one in 15-bit fixed point so that white stays white.
+
+use super::InPlaceFn;
+use crate::ColorType;
+
+/// The weights of the red, green and blue samples in the gray value of
+/// [`Transformations::RGB_TO_GRAY`](crate::Transformations::RGB_TO_GRAY).
+///
+/// The weights are applied to the samples as they are stored in the image, without
+/// linearization.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GrayCoefficients {
+    /// The weight of the red sample.
+    pub red: f32,
+    /// The weight of the green sample.
+    pub green: f32,
+    /// The weight of the blue sample.
+    pub blue: f32,
+}
+
+impl GrayCoefficients {
+    /// The luma coefficients of ITU-R BT.601, used by standard definition television and JPEG.
+    pub const REC_601: Self = GrayCoefficients {
+        red: 0.299,
+        green: 0.587,
+        blue: 0.114,
+    };
+
+    /// The luma coefficients of ITU-R BT.709, which match the primaries of sRGB.
+    pub const REC_709: Self = GrayCoefficients {
+        red: 0.2126,
+        green: 0.7152,
+        blue: 0.0722,
+    };
+
+    /// The weights in fixed point with 15 fractional bits, normalized to a sum of `1 << 15`.
+    fn to_fixed(self) -> [u32; 3] {
+        let sum = self.red + self.green + self.blue;
+        let fixed = |weight: f32| {
+            let weight = if sum > 0.0 { weight / sum } else { 1.0 / 3.0 };
+            (weight.clamp(0.0, 1.0) * 32768.0).round() as u32
+        };
+        let red = fixed(self.red);
+        let green = fixed(self.green).min(32768 - red);
+        [red, green, 32768 - red - green]
+    }
+
+    /// The gray value of a color, rounded like that of [`create_rgb_to_gray`].
+    pub(super) fn gray_of(self, rgb: [u16; 3]) -> u16 {
+        let weights = self.to_fixed();
+        let sum: u32 = (0..3).map(|idx| weights[idx] * u32::from(rgb[idx])).sum();
+        ((sum + (1 << 14)) >> 15) as u16
+    }
+}
+
+impl Default for GrayCoefficients {
+    fn default() -> Self {
+        Self::REC_709
+    }
+}
+
+/// The color type that [`create_rgb_to_gray`] converts `color_type` into.
+pub(super) fn gray_color_type(color_type: ColorType) -> ColorType {
+    match color_type {
+        ColorType::Rgba => ColorType::GrayscaleAlpha,
+        _ => ColorType::Grayscale,
+    }
+}
+
+/// The color type that [`create_gray_to_rgb`] converts `color_type` into.
+pub(super) fn rgb_color_type(color_type: ColorType) -> ColorType {
+    match color_type {
+        ColorType::GrayscaleAlpha => ColorType::Rgba,
+        _ => ColorType::Rgb,
+    }
+}
+
+/// Replaces the color samples of RGB or RGBA pixels with their weighted sum, front to back.
+pub(super) fn create_rgb_to_gray(
+    color_type: ColorType,
+    sample_len: usize,
+    coefficients: GrayCoefficients,
+) -> InPlaceFn {
+    let in_len = color_type.samples() * sample_len;
+    let out_len = gray_color_type(color_type).samples() * sample_len;
+    let [red, green, blue] = coefficients.to_fixed();
+    let gray = move |r: u32, g: u32, b: u32| (red * r + green * g + blue * b + (1 << 14)) >> 15;
+
+    Box::new(move |row| {
+        for idx in 0..row.len() / in_len {
+            let (src, out) = (idx * in_len, idx * out_len);
+            if sample_len == 2 {
+                let sample = |pos: usize| u32::from(u16::from_be_bytes([row[pos], row[pos + 1]]));
+                let value = gray(sample(src), sample(src + 2), sample(src + 4)) as u16;
+                row[out..][..2].copy_from_slice(&value.to_be_bytes());
+            } else {
+                let sample = |pos: usize| u32::from(row[pos]);
+                row[out] = gray(sample(src), sample(src + 1), sample(src + 2)) as u8;
+            }
+            // Any alpha sample follows the gray sample.
+            row.copy_within(src + 3 * sample_len..src + in_len, out + sample_len);
+        }
+    })
+}
+
+/// Copies the gray sample of grayscale pixels into red, green and blue, back to front.
+pub(super) fn create_gray_to_rgb(color_type: ColorType, sample_len: usize) -> InPlaceFn {
+    let in_len = color_type.samples() * sample_len;
+    let out_len = rgb_color_type(color_type).samples() * sample_len;
+
+    Box::new(move |row| {
+        // Going backwards, the pixels are only moved onto bytes that have already been moved.
+        for idx in (0..row.len() / out_len).rev() {
+            let (src, out) = (idx * in_len, idx * out_len);
+            row.copy_within(src + sample_len..src + in_len, out + 3 * sample_len);
+            for channel in (0..3).rev() {
+                row.copy_within(src..src + sample_len, out + channel * sample_len);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_point_weights_sum_to_one() {
+        assert_eq!(GrayCoefficients::REC_709.to_fixed(), [6966, 23436, 2366]);
+        assert_eq!(GrayCoefficients::REC_601.to_fixed(), [9798, 19235, 3735]);
+    }
+
+    #[test]
+    fn rgba_to_gray_alpha() {
+        let mut row = [255, 255, 255, 1, 255, 0, 0, 2];
+        create_rgb_to_gray(ColorType::Rgba, 1, GrayCoefficients::REC_601)(&mut row);
+        assert_eq!(row[..4], [255, 1, 76, 2]);
+
+        let mut row = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        create_rgb_to_gray(ColorType::Rgb, 2, GrayCoefficients::default())(&mut row);
+        assert_eq!(row[..2], [0xff, 0xff]);
+    }
+
+    #[test]
+    fn gray_alpha_to_rgba() {
+        let mut row = [1, 2, 3, 4, 0, 0, 0, 0];
+        create_gray_to_rgb(ColorType::GrayscaleAlpha, 1)(&mut row);
+        assert_eq!(row, [1, 1, 1, 2, 3, 3, 3, 4]);
+
+        let mut row = [1, 2, 0, 0, 0, 0];
+        create_gray_to_rgb(ColorType::Grayscale, 2)(&mut row);
+        assert_eq!(row, [1, 2, 1, 2, 1, 2]);
+    }
+
+    #[test]
+    fn gray_conversion_transformations() {
+        use crate::decoder::test_utils::{decode_pngsuite_with, samples};
+        use crate::Transformations as T;
+
+        let decode = |name, transform, coefficients| {
+            let (buf, info) = decode_pngsuite_with(name, transform, |decoder| {
+                decoder.set_gray_coefficients(coefficients);
+            });
+            (samples(&buf, &info), info)
+        };
+
+        let rec_709 = GrayCoefficients::REC_709;
+        for (name, base, conversion, coefficients, color_type) in [
+            (
+                "basn2c08",
+                T::IDENTITY,
+                T::RGB_TO_GRAY,
+                rec_709,
+                ColorType::Grayscale,
+            ),
+            (
+                "basn6a16",
+                T::IDENTITY,
+                T::RGB_TO_GRAY,
+                rec_709,
+                ColorType::GrayscaleAlpha,
+            ),
+            (
+                "tbrn2c08",
+                T::EXPAND,
+                T::RGB_TO_GRAY,
+                rec_709,
+                ColorType::GrayscaleAlpha,
+            ),
+            (
+                "basn3p04",
+                T::EXPAND,
+                T::RGB_TO_GRAY,
+                rec_709,
+                ColorType::Grayscale,
+            ),
+            (
+                "basn3p04",
+                T::IDENTITY,
+                T::RGB_TO_GRAY,
+                rec_709,
+                ColorType::Indexed,
+            ),
+            (
+                "basn2c08",
+                T::IDENTITY,
+                T::RGB_TO_GRAY,
+                GrayCoefficients::REC_601,
+                ColorType::Grayscale,
+            ),
+            (
+                "basn0g02",
+                T::EXPAND,
+                T::GRAY_TO_RGB,
+                rec_709,
+                ColorType::Rgb,
+            ),
+            (
+                "basn4a08",
+                T::IDENTITY,
+                T::GRAY_TO_RGB,
+                rec_709,
+                ColorType::Rgba,
+            ),
+            (
+                "basn0g16",
+                T::IDENTITY,
+                T::GRAY_TO_RGB,
+                rec_709,
+                ColorType::Rgb,
+            ),
+            (
+                "tbwn0g16",
+                T::EXPAND,
+                T::GRAY_TO_RGB,
+                rec_709,
+                ColorType::Rgba,
+            ),
+        ] {
+            let (expected, expected_info) = decode(name, base, coefficients);
+            let (actual, info) = decode(name, base | conversion, coefficients);
+            assert_eq!(
+                (info.color_type, info.bit_depth),
+                (color_type, expected_info.bit_depth)
+            );
+
+            let GrayCoefficients { red, green, blue } = coefficients;
+            let samples = expected_info.color_type.samples();
+            let has_alpha = matches!(
+                expected_info.color_type,
+                ColorType::GrayscaleAlpha | ColorType::Rgba
+            );
+            let mut oracle = Vec::new();
+            for pixel in expected.chunks_exact(samples) {
+                match expected_info.color_type {
+                    ColorType::Rgb | ColorType::Rgba => {
+                        let [r, g, b] = [0, 1, 2].map(|idx| pixel[idx] as f32);
+                        oracle.push((r * red + g * green + b * blue).round() as u32);
+                    }
+                    ColorType::Grayscale | ColorType::GrayscaleAlpha => {
+                        oracle.extend([pixel[0]; 3]);
+                    }
+                    ColorType::Indexed => oracle.push(pixel[0]),
+                }
+                if has_alpha {
+                    oracle.push(pixel[samples - 1]);
+                }
+            }
+            assert_eq!(actual.len(), oracle.len(), "{}", name);
+            for (actual, oracle) in actual.iter().zip(&oracle) {
+                // The weights are rounded to fixed point.
+                assert!(actual.abs_diff(*oracle) <= 1, "{} {:?}", name, conversion);
+            }
+        }
+    }
+}
@@ -16,11 +16,12 @@
 //! Based on X and Y, we can try to calculate the breakeven point.  It seems
 //! that memoization is a net benefit for images bigger than around 13x13 pixels.
 
-use super::{unpack_bits, TransformFn};
+use super::{gamma, unpack_bits, TransformFn};
 use crate::{BitDepth, Info};
 
-pub fn create_expansion_into_rgb8(info: &Info) -> TransformFn {
-    let rgba_palette = create_rgba_palette(info);
+/// Creates the expansion into RGB, correcting the palette with the `gamma` exponent if given.
+pub fn create_expansion_into_rgb8(info: &Info, gamma: Option<f64>) -> TransformFn {
+    let rgba_palette = create_corrected_palette(info, gamma);
 
     if info.bit_depth == BitDepth::Eight {
         Box::new(move |input, output, _info| expand_8bit_into_rgb8(input, output, &rgba_palette))
@@ -29,13 +30,22 @@ pub fn create_expansion_into_rgb8(info: &Info) -> TransformFn {
     }
 }
 
-pub fn create_expansion_into_rgba8(info: &Info) -> TransformFn {
-    let rgba_palette = create_rgba_palette(info);
+/// Creates the expansion into RGBA, correcting the palette with the `gamma` exponent if given.
+pub fn create_expansion_into_rgba8(info: &Info, gamma: Option<f64>) -> TransformFn {
+    let rgba_palette = create_corrected_palette(info, gamma);
     Box::new(move |input, output, info| {
         expand_paletted_into_rgba8(input, output, info, &rgba_palette)
     })
 }
 
+fn create_corrected_palette(info: &Info, gamma: Option<f64>) -> [[u8; 4]; 256] {
+    let mut rgba_palette = create_rgba_palette(info);
+    if let Some(exponent) = gamma {
+        gamma::correct_palette(&mut rgba_palette, exponent);
+    }
+    rgba_palette
+}
+
 fn create_rgba_palette(info: &Info) -> [[u8; 4]; 256] {
     let palette = info.palette.as_deref().expect("Caller should verify");
     let trns = info.trns.as_deref().unwrap_or(&[]);
@@ -202,8 +212,9 @@ mod test {
         let samples_count = src.len() * samples_count_per_byte;
 
         let mut dst = vec![0; samples_count * output_bytes_per_input_sample];
-        let transform_fn =
-            super::super::create_transform_fn(&info, Transformations::EXPAND).unwrap();
+        let mut transform_fn =
+            super::super::create_transform_fn(&info, Transformations::EXPAND, &Default::default())
+                .unwrap();
         transform_fn(src, dst.as_mut_slice(), &info);
 
         {
@@ -338,10 +349,10 @@ mod test {
         fn create_expected_rgba_palette(plte: &[u8], trns: &[u8]) -> [[u8; 4]; 256] {
             let mut rgba = [[1, 2, 3, 4]; 256];
             for (i, rgba) in rgba.iter_mut().enumerate() {
-                rgba[0] = plte.get(i * 3 + 0).map(|&r| r).unwrap_or(0);
-                rgba[1] = plte.get(i * 3 + 1).map(|&g| g).unwrap_or(0);
-                rgba[2] = plte.get(i * 3 + 2).map(|&b| b).unwrap_or(0);
-                rgba[3] = trns.get(i * 1 + 0).map(|&a| a).unwrap_or(0xFF);
+                rgba[0] = plte.get(i * 3).copied().unwrap_or(0);
+                rgba[1] = plte.get(i * 3 + 1).copied().unwrap_or(0);
+                rgba[2] = plte.get(i * 3 + 2).copied().unwrap_or(0);
+                rgba[3] = trns.get(i).copied().unwrap_or(0xFF);
             }
             rgba
         }
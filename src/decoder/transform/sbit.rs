@@ -0,0 +1,190 @@
+//! Using the significant bits of the `sBIT` chunk.
+//!
+//! Encoders store samples with fewer significant bits by scaling them up to the bit depth of the
+//! image, so the significant bits are always the high bits of each sample. They can be recovered
+//! by shifting the samples down, or the samples can be rescaled to the full range from them.
+
+use super::InPlaceFn;
+use crate::{BitDepth, ColorType, Info};
+
+/// The number of significant bits of each sample of rows of `color_type` with `bit_depth`.
+///
+/// The samples of the image are mapped to the channels of the rows: gray samples that were
+/// converted to RGB keep their significant bits, gray samples converted from RGB have those of the
+/// most significant color, and alpha samples that were added for a `tRNS` chunk are significant.
+///
+/// Returns `None` if the image has no `sBIT` chunk, if all bits are significant or if the rows are
+/// indexed or packed.
+pub(super) fn significant_bits(
+    info: &Info,
+    color_type: ColorType,
+    bit_depth: BitDepth,
+) -> Option<Vec<u8>> {
+    let sbit = info.sbit.as_deref()?;
+    let depth = bit_depth as u8;
+    if color_type == ColorType::Indexed || depth < 8 {
+        return None;
+    }
+
+    let image_colors = match info.color_type {
+        ColorType::Grayscale | ColorType::GrayscaleAlpha => 1,
+        _ => 3,
+    };
+    let image_alpha = sbit.get(image_colors).copied();
+    let mut bits = match color_type {
+        ColorType::Grayscale | ColorType::GrayscaleAlpha => {
+            vec![sbit[..image_colors].iter().copied().max()?]
+        }
+        _ if image_colors == 1 => vec![sbit[0]; 3],
+        _ => sbit[..3].to_vec(),
+    };
+    if matches!(color_type, ColorType::GrayscaleAlpha | ColorType::Rgba) {
+        bits.push(image_alpha.unwrap_or(depth));
+    }
+
+    for bits in &mut bits {
+        *bits = (*bits).min(depth);
+    }
+    bits.iter().any(|&bits| bits < depth).then_some(bits)
+}
+
+/// Shifts each sample down to its significant bits, given per channel by `bits`.
+pub(super) fn create_shift(bits: Vec<u8>, sample_len: usize) -> InPlaceFn {
+    let depth = 8 * sample_len as u8;
+    let shifts: Vec<u8> = bits.iter().map(|&bits| depth - bits).collect();
+    let pixel_len = shifts.len() * sample_len;
+
+    Box::new(move |row| {
+        for pixel in row.chunks_exact_mut(pixel_len) {
+            for (sample, &shift) in pixel.chunks_exact_mut(sample_len).zip(&shifts) {
+                if sample_len == 2 {
+                    let value = u16::from_be_bytes([sample[0], sample[1]]) >> shift;
+                    sample.copy_from_slice(&value.to_be_bytes());
+                } else {
+                    sample[0] >>= shift;
+                }
+            }
+        }
+    })
+}
+
+/// Rescales each sample from its significant bits, given per channel by `bits`, to the full range
+/// of its bit depth.
+pub(super) fn create_rescale(bits: Vec<u8>, sample_len: usize) -> InPlaceFn {
+    let depth = 8 * sample_len as u8;
+    let max = (1u32 << depth) - 1;
+    let rescale = move |value: u32, bits: u8| {
+        let max_in = (1u32 << bits) - 1;
+        ((value >> (depth - bits)) * max + max_in / 2) / max_in
+    };
+    let pixel_len = bits.len() * sample_len;
+
+    if sample_len == 2 {
+        Box::new(move |row| {
+            for pixel in row.chunks_exact_mut(pixel_len) {
+                for (sample, &bits) in pixel.chunks_exact_mut(2).zip(&bits) {
+                    let value = u32::from(u16::from_be_bytes([sample[0], sample[1]]));
+                    let value = rescale(value, bits) as u16;
+                    sample.copy_from_slice(&value.to_be_bytes());
+                }
+            }
+        })
+    } else {
+        let tables: Vec<[u8; 256]> = bits
+            .iter()
+            .map(|&bits| {
+                let mut table = [0; 256];
+                for (value, entry) in table.iter_mut().enumerate() {
+                    *entry = rescale(value as u32, bits) as u8;
+                }
+                table
+            })
+            .collect();
+        Box::new(move |row| {
+            for pixel in row.chunks_exact_mut(pixel_len) {
+                for (sample, table) in pixel.iter_mut().zip(&tables) {
+                    *sample = table[usize::from(*sample)];
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channels_of_converted_rows() {
+        let mut info = Info::with_size(1, 1);
+        info.color_type = ColorType::Rgb;
+        info.sbit = Some(vec![5, 6, 5].into());
+        let bits = |info: &Info, color_type| significant_bits(info, color_type, BitDepth::Eight);
+        assert_eq!(bits(&info, ColorType::Rgb), Some(vec![5, 6, 5]));
+        assert_eq!(bits(&info, ColorType::Rgba), Some(vec![5, 6, 5, 8]));
+        assert_eq!(bits(&info, ColorType::Grayscale), Some(vec![6]));
+
+        info.color_type = ColorType::GrayscaleAlpha;
+        info.sbit = Some(vec![4, 8].into());
+        assert_eq!(bits(&info, ColorType::Rgba), Some(vec![4, 4, 4, 8]));
+        assert_eq!(bits(&info, ColorType::Grayscale), Some(vec![4]));
+        info.sbit = Some(vec![8, 8].into());
+        assert_eq!(bits(&info, ColorType::GrayscaleAlpha), None);
+    }
+
+    #[test]
+    fn shift_and_rescale() {
+        // A 5-6-5 pixel with maximal samples, scaled up by shifting.
+        let mut row = [0xf8, 0xfc, 0xf8];
+        create_shift(vec![5, 6, 5], 1)(&mut row);
+        assert_eq!(row, [31, 63, 31]);
+
+        let mut row = [0xf8, 0xfc, 0x08];
+        create_rescale(vec![5, 6, 5], 1)(&mut row);
+        assert_eq!(row, [255, 255, 8]);
+
+        let mut row = [0xff, 0xf0];
+        create_rescale(vec![12], 2)(&mut row);
+        assert_eq!(row, [0xff, 0xff]);
+        let mut row = [0xff, 0xf0];
+        create_shift(vec![12], 2)(&mut row);
+        assert_eq!(row, [0x0f, 0xff]);
+    }
+
+    #[test]
+    fn significant_bits_transformations() {
+        use crate::decoder::test_utils::{decode_pngsuite, samples};
+        use crate::Transformations as T;
+
+        let decode = |name, transform| {
+            let (buf, info) = decode_pngsuite(name, transform);
+            (samples(&buf, &info), info)
+        };
+
+        for (name, base, bits) in [
+            ("cs5n2c08", T::IDENTITY, 5),
+            ("cs3n2c16", T::IDENTITY, 13),
+            ("cs3n2c16", T::STRIP_16, 8),
+            ("cs3n3p08", T::EXPAND, 3),
+            ("cs5n3p08", T::EXPAND_16, 5),
+            ("cs8n2c08", T::IDENTITY, 8),
+        ] {
+            let (expected, info) = decode(name, base);
+            let depth = info.bit_depth as u32;
+            let shift = depth - bits;
+            let max_in = f64::from((1u32 << bits) - 1);
+            let max = f64::from((1u32 << depth) - 1);
+
+            let (shifted, _) = decode(name, base | T::SHIFT);
+            let oracle: Vec<u32> = expected.iter().map(|v| v >> shift).collect();
+            assert_eq!(shifted, oracle, "{} {:?}", name, base);
+
+            let (rescaled, _) = decode(name, base | T::RESCALE_SBIT);
+            let oracle: Vec<u32> = expected
+                .iter()
+                .map(|v| (f64::from(v >> shift) * max / max_in).round() as u32)
+                .collect();
+            assert_eq!(rescaled, oracle, "{} {:?}", name, base);
+        }
+    }
+}
@@ -1,23 +1,32 @@
 use borrow::Cow;
 use io::{Read, Write};
 use ops::{Deref, DerefMut};
+use std::time::SystemTime;
 use std::{borrow, error, fmt, io, mem, ops, result};
 
 use crc32fast::Hasher as Crc32;
 use flate2::write::ZlibEncoder;
 
+use crate::adam7::{subsample_pass, Adam7Iterator};
 use crate::chunk::{self, ChunkType};
 use crate::common::{
-    AnimationControl, BitDepth, BlendOp, BytesPerPixel, ColorType, Compression, DisposeOp,
-    FrameControl, Info, ParameterError, ParameterErrorKind, PixelDimensions, ScaledFloat, Unit,
+    AnimationControl, BitDepth, BlendOp, BytesPerPixel, ChunkLocation, CodingIndependentCodePoints,
+    ColorType, Compression, ContentLightLevelInfo, DisposeOp, FrameControl,
+    GifApplicationExtension, GifGraphicControl, ImageOffset, Info, MasteringDisplayColorVolume,
+    ParameterError, ParameterErrorKind, PhysicalScale, PixelCalibration, PixelDimensions,
+    ScaledFloat, StereoLayout, SuggestedPalette, Time, Unit, UnknownChunk,
 };
-use crate::filter::{filter, Filter};
+use crate::filter::{filter, Filter, RowFilter};
 use crate::text_metadata::{
     encode_iso_8859_1, EncodableTextChunk, ITXtChunk, TEXtChunk, TextEncodingError, ZTXtChunk,
 };
 use crate::traits::WriteBytesExt;
 use crate::DeflateCompression;
 
+mod async_writer;
+
+pub use self::async_writer::{AsyncStreamWriter, AsyncWrite, AsyncWriter};
+
 pub type Result<T> = result::Result<T, EncodingError>;
 
 #[derive(Debug)]
@@ -49,6 +58,14 @@ enum FormatErrorKind {
     MissingData(usize),
     Unrecoverable,
     BadTextEncoding(TextEncodingError),
+    InvalidMatrixCoefficients(u8),
+    MasteringChromaticitiesOutOfRange,
+    InvalidSignificantBits,
+    InvalidBackground,
+    InvalidHistogram,
+    InvalidSuggestedPalette,
+    InvalidPixelCalibration,
+    InvalidPhysicalScale,
 }
 
 impl error::Error for EncodingError {
@@ -85,6 +102,33 @@ impl fmt::Display for FormatError {
                 depth, color
             ),
             NoPalette => write!(fmt, "can't write indexed image without palette"),
+            InvalidMatrixCoefficients(coefficients) => write!(
+                fmt,
+                "cICP matrix coefficients must be 0 for RGB images, not {}",
+                coefficients
+            ),
+            MasteringChromaticitiesOutOfRange => write!(
+                fmt,
+                "mDCV chromaticities must be at most 1.3107 to fit the chunk"
+            ),
+            InvalidSignificantBits => write!(
+                fmt,
+                "sBIT does not match the color type and bit depth of the image"
+            ),
+            InvalidBackground => write!(
+                fmt,
+                "bKGD does not match the color type, bit depth or palette of the image"
+            ),
+            InvalidHistogram => write!(fmt, "hIST needs one entry per entry of the palette"),
+            InvalidSuggestedPalette => write!(
+                fmt,
+                "sPLT needs a unique, valid name and a sample depth of 8 or 16 bits that fits its samples"
+            ),
+            InvalidPixelCalibration => write!(
+                fmt,
+                "pCAL needs a valid name, a non-empty original range and finite parameters for its equation"
+            ),
+            InvalidPhysicalScale => write!(fmt, "sCAL needs a finite, positive width and height"),
             WrittenTooMuch(index) => write!(fmt, "wrong data size, got {} bytes too many", index),
             NotAnimated => write!(fmt, "not an animation"),
             OutOfBounds => write!(
@@ -159,6 +203,7 @@ struct Options {
     sep_def_img: bool,
     validate_sequence: bool,
     compression: DeflateCompression,
+    premultiplied_alpha: bool,
 }
 
 impl<'a, W: Write> Encoder<'a, W> {
@@ -297,6 +342,15 @@ impl<'a, W: Write> Encoder<'a, W> {
         self.info.bit_depth = depth;
     }
 
+    /// Set whether the image is encoded with Adam7 interlacing.
+    ///
+    /// The image data is still supplied row by row, in its natural order. The [`Writer`] splits
+    /// each frame into the seven interlace passes. Note that the [`StreamWriter`] has to buffer a
+    /// whole frame before it can encode the first pass of an interlaced image.
+    pub fn set_interlaced(&mut self, interlaced: bool) {
+        self.info.interlaced = interlaced;
+    }
+
     /// Set compression parameters, see [`Compression`] for the available options.
     pub fn set_compression(&mut self, compression: Compression) {
         self.set_deflate_compression(DeflateCompression::from_simple(compression));
@@ -412,6 +466,116 @@ impl<'a, W: Write> Encoder<'a, W> {
     pub fn set_pixel_dims(&mut self, pixel_dims: Option<PixelDimensions>) {
         self.info.pixel_dims = pixel_dims
     }
+
+    /// Set the number of significant bits of each channel of the image, written as an `sBIT`
+    /// chunk, e.g. `&[5, 6, 5]` for RGB samples that were scaled up from 5, 6 and 5 bits.
+    ///
+    /// There is one entry per sample, or three for the red, green and blue channels of the
+    /// palette of indexed images. Each entry must be between 1 and the bit depth, which is 8 for
+    /// palettes. Set the color type and bit depth before.
+    pub fn set_significant_bits(&mut self, bits: &[u8]) -> Result<()> {
+        validate_sbit(bits, self.info.color_type, self.info.bit_depth)?;
+        self.info.sbit = Some(Cow::Owned(bits.to_vec()));
+        Ok(())
+    }
+
+    /// Set the background color of the image, written as a `bKGD` chunk.
+    ///
+    /// This is a single palette index for indexed images, a single gray sample for grayscale
+    /// images and three samples for RGB images, with or without alpha. Samples must fit the bit
+    /// depth. Set the color type, bit depth and palette before.
+    pub fn set_background_color(&mut self, background: &[u16]) -> Result<()> {
+        let bkgd: Vec<u8> = if self.info.color_type == ColorType::Indexed {
+            let invalid = || EncodingError::Format(FormatErrorKind::InvalidBackground.into());
+            let index = background.first().ok_or_else(invalid)?;
+            vec![u8::try_from(*index).map_err(|_| invalid())?]
+        } else {
+            background.iter().flat_map(|s| s.to_be_bytes()).collect()
+        };
+        validate_bkgd(&bkgd, &self.info)?;
+        self.info.bkgd = Some(Cow::Owned(bkgd));
+        Ok(())
+    }
+
+    /// Set the approximate usage frequencies of the palette entries, written as a `hIST` chunk.
+    ///
+    /// Writing the header fails unless there is one frequency per entry of the palette.
+    pub fn set_histogram(&mut self, histogram: Vec<u16>) {
+        self.info.histogram = Some(histogram);
+    }
+
+    /// Add a suggested palette, written as an `sPLT` chunk.
+    ///
+    /// Writing the header fails if the name is not unique, is not 1 to 79 Latin-1 characters
+    /// long, or if the samples don't fit the sample depth.
+    pub fn add_suggested_palette(&mut self, palette: SuggestedPalette) {
+        self.info.suggested_palettes.push(palette);
+    }
+
+    /// Set the position of the image on a page or screen, written as an `oFFs` chunk.
+    pub fn set_offset(&mut self, offset: ImageOffset) {
+        self.info.offset = Some(offset);
+    }
+
+    /// Set the calibration of the samples of the image, written as a `pCAL` chunk.
+    ///
+    /// Writing the header fails if the original range is empty, if there are not as many finite
+    /// parameters as the equation takes, or if the name is not 1 to 79 Latin-1 characters long.
+    pub fn set_pixel_calibration(&mut self, pcal: PixelCalibration) {
+        self.info.pixel_calibration = Some(pcal);
+    }
+
+    /// Set the physical scale of the image subject, written as an `sCAL` chunk.
+    ///
+    /// Writing the header fails unless the width and height are finite and positive.
+    pub fn set_physical_scale(&mut self, scale: PhysicalScale) {
+        self.info.physical_scale = Some(scale);
+    }
+
+    /// Set the layout of a stereo image, written as an `sTER` chunk.
+    pub fn set_stereo_layout(&mut self, layout: StereoLayout) {
+        self.info.stereo_layout = Some(layout);
+    }
+
+    /// Add a GIF Graphic Control Extension, written as a `gIFg` chunk.
+    pub fn add_gif_graphic_control(&mut self, control: GifGraphicControl) {
+        self.info.gif_graphic_controls.push(control);
+    }
+
+    /// Add a GIF Application Extension, written as a `gIFx` chunk.
+    pub fn add_gif_application_extension(&mut self, extension: GifApplicationExtension) {
+        self.info.gif_application_extensions.push(extension);
+    }
+
+    /// Set the coding-independent code points of the image, written as a `cICP` chunk.
+    ///
+    /// Writing the header fails if the matrix coefficients are not 0, as PNG images are RGB.
+    pub fn set_coding_independent_code_points(&mut self, cicp: CodingIndependentCodePoints) {
+        self.info.coding_independent_code_points = Some(cicp);
+    }
+
+    /// Set the mastering display color volume of the image, written as an `mDCV` chunk.
+    ///
+    /// Writing the header fails if a chromaticity is above 1.3107, which the chunk can't store.
+    pub fn set_mastering_display_color_volume(&mut self, mdcv: MasteringDisplayColorVolume) {
+        self.info.mastering_display_color_volume = Some(mdcv);
+    }
+
+    /// Set the content light level information of the image, written as a `cLLI` chunk.
+    pub fn set_content_light_level(&mut self, clli: ContentLightLevelInfo) {
+        self.info.content_light_level = Some(clli);
+    }
+
+    /// Set the time of the last modification of the image, written as a `tIME` chunk.
+    pub fn set_modification_time(&mut self, time: Time) {
+        self.info.modification_time = Some(time);
+    }
+
+    /// Set the time of the last modification of the image from a system time, e.g.
+    /// `SystemTime::now()`. See [`Encoder::set_modification_time`].
+    pub fn set_modification_system_time(&mut self, time: SystemTime) {
+        self.set_modification_time(time.into());
+    }
     /// Convenience function to add tEXt chunks to [`Info`] struct
     pub fn add_text_chunk(&mut self, keyword: String, text: String) -> Result<()> {
         let text_chunk = TEXtChunk::new(keyword, text);
@@ -449,6 +613,17 @@ impl<'a, W: Write> Encoder<'a, W> {
     pub fn validate_sequence(&mut self, validate: bool) {
         self.options.validate_sequence = validate;
     }
+
+    /// Set whether the color samples of the image data are premultiplied with their alpha.
+    ///
+    /// PNG stores straight alpha, so premultiplied samples of [`ColorType::Rgba`] and
+    /// [`ColorType::GrayscaleAlpha`] images are divided by their alpha before they are written,
+    /// e.g. for framebuffers of compositors. Samples of fully transparent pixels become zero.
+    /// Other color types are written unchanged. The decoder can premultiply the samples again with
+    /// [`Transformations::PREMULTIPLY_ALPHA`](crate::Transformations::PREMULTIPLY_ALPHA).
+    pub fn set_premultiplied_alpha(&mut self, premultiplied: bool) {
+        self.options.premultiplied_alpha = premultiplied;
+    }
 }
 
 /// PNG writer
@@ -484,6 +659,9 @@ struct PartialInfo {
     frame_control: Option<FrameControl>,
     animation_control: Option<AnimationControl>,
     has_palette: bool,
+    interlaced: bool,
+    /// The unknown chunks that are written after the image data, just before IEND.
+    trailing_chunks: Vec<UnknownChunk>,
 }
 
 impl PartialInfo {
@@ -496,6 +674,13 @@ impl PartialInfo {
             frame_control: info.frame_control,
             animation_control: info.animation_control,
             has_palette: info.palette.is_some(),
+            interlaced: info.interlaced,
+            trailing_chunks: info
+                .unknown_chunks
+                .iter()
+                .filter(|chunk| chunk.location == ChunkLocation::AfterIdat)
+                .cloned()
+                .collect(),
         }
     }
 
@@ -517,6 +702,128 @@ impl PartialInfo {
     }
 }
 
+/// The samples of a frame, as passed to `write_image_data` or `write_image_data_u16`.
+#[derive(Clone, Copy)]
+enum ImageSamples<'a> {
+    Bytes(&'a [u8]),
+    U16(&'a [u16]),
+}
+
+impl ImageSamples<'_> {
+    /// The length of the samples in bytes.
+    fn len(&self) -> usize {
+        match self {
+            ImageSamples::Bytes(data) => data.len(),
+            ImageSamples::U16(data) => 2 * data.len(),
+        }
+    }
+}
+
+/// The unfiltered rows of a frame, which are converted one at a time before they are filtered.
+struct ImageRows<'a> {
+    data: ImageSamples<'a>,
+    /// The length of a row in bytes, without the filter byte.
+    row_len: usize,
+    /// The color type and bit depth of pixels with premultiplied alpha, which is undone per row.
+    premultiplied: Option<(ColorType, BitDepth)>,
+}
+
+impl ImageRows<'_> {
+    fn count(&self) -> usize {
+        self.data.len() / self.row_len
+    }
+
+    /// Returns row `y`, converted in `buf` if its samples have to be changed.
+    fn row<'b>(&'b self, y: usize, buf: &'b mut [u8]) -> &'b [u8] {
+        match self.data {
+            ImageSamples::Bytes(data) => {
+                let row = &data[y * self.row_len..][..self.row_len];
+                if self.premultiplied.is_none() {
+                    return row;
+                }
+                buf.copy_from_slice(row);
+            }
+            ImageSamples::U16(data) => {
+                let samples = &data[y * self.row_len / 2..][..self.row_len / 2];
+                for (bytes, sample) in buf.chunks_exact_mut(2).zip(samples) {
+                    bytes.copy_from_slice(&sample.to_be_bytes());
+                }
+            }
+        }
+        if let Some((color_type, bit_depth)) = self.premultiplied {
+            unpremultiply_alpha(color_type, bit_depth, buf);
+        }
+        buf
+    }
+}
+
+/// Splits the unfiltered rows of a `width` x `height` frame into the seven Adam7 passes.
+///
+/// Each pass is returned as a separate sub-image together with the length of its rows (without
+/// the filter byte). Passes that contain no pixels are skipped, as they are not encoded at all.
+fn adam7_passes(
+    color_type: ColorType,
+    bit_depth: BitDepth,
+    rows: &ImageRows<'_>,
+    width: u32,
+    height: u32,
+) -> Vec<(usize, Vec<u8>)> {
+    let bits_pp = color_type.samples_u8() * bit_depth.into_u8();
+    let mut buf = vec![0; rows.row_len];
+
+    let mut passes: Vec<(usize, Vec<u8>)> = Vec::new();
+    let mut current_pass = 0;
+    for adam7_info in Adam7Iterator::new(width, height) {
+        let row_len = color_type.raw_row_length_from_width(bit_depth, adam7_info.samples) - 1;
+        if adam7_info.pass != current_pass {
+            current_pass = adam7_info.pass;
+            passes.push((row_len, Vec::new()));
+        }
+
+        let pass = &mut passes.last_mut().unwrap().1;
+        let start = pass.len();
+        pass.resize(start + row_len, 0);
+        // With a stride of zero, the single row is sampled as if it was the line of the pass.
+        let (_, y) = adam7_info.image_position(0);
+        let row = rows.row(y as usize, &mut buf);
+        subsample_pass(&mut pass[start..], row, 0, &adam7_info, bits_pp);
+    }
+
+    passes
+}
+
+/// Divides the color samples of premultiplied pixels by their alpha, if the image has alpha.
+fn unpremultiply_alpha(color_type: ColorType, bit_depth: BitDepth, data: &mut [u8]) {
+    if !matches!(color_type, ColorType::GrayscaleAlpha | ColorType::Rgba) {
+        return;
+    }
+
+    let samples = color_type.samples();
+    let unpremultiply = |value: u32, alpha: u32, max: u32| match alpha {
+        0 => 0,
+        alpha => ((value * max + alpha / 2) / alpha).min(max),
+    };
+    if bit_depth == BitDepth::Sixteen {
+        for pixel in data.chunks_exact_mut(2 * samples) {
+            let (colors, alpha) = pixel.split_at_mut(2 * samples - 2);
+            let alpha = u32::from(u16::from_be_bytes([alpha[0], alpha[1]]));
+            for sample in colors.chunks_exact_mut(2) {
+                let value = u32::from(u16::from_be_bytes([sample[0], sample[1]]));
+                let value = unpremultiply(value, alpha, 0xffff) as u16;
+                sample.copy_from_slice(&value.to_be_bytes());
+            }
+        }
+    } else {
+        for pixel in data.chunks_exact_mut(samples) {
+            let (colors, alpha) = pixel.split_at_mut(samples - 1);
+            let alpha = u32::from(alpha[0]);
+            for sample in colors {
+                *sample = unpremultiply(u32::from(*sample), alpha, 0xff) as u8;
+            }
+        }
+    }
+}
+
 const DEFAULT_BUFFER_LENGTH: usize = 4 * 1024;
 
 pub(crate) fn write_chunk<W: Write>(mut w: W, name: chunk::ChunkType, data: &[u8]) -> Result<()> {
@@ -530,6 +837,55 @@ pub(crate) fn write_chunk<W: Write>(mut w: W, name: chunk::ChunkType, data: &[u8
     Ok(())
 }
 
+/// Check the `sBIT` chunk against the color type and bit depth of the image.
+fn validate_sbit(sbit: &[u8], color_type: ColorType, bit_depth: BitDepth) -> Result<()> {
+    let (samples, depth) = match color_type {
+        // The significant bits of indexed images are those of the palette.
+        ColorType::Indexed => (3, 8),
+        _ => (color_type.samples(), bit_depth as u8),
+    };
+    if sbit.len() != samples || sbit.iter().any(|&bits| bits == 0 || bits > depth) {
+        return Err(EncodingError::Format(
+            FormatErrorKind::InvalidSignificantBits.into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Check the `bKGD` chunk against the color type, bit depth and palette of the image.
+fn validate_bkgd(bkgd: &[u8], info: &Info<'_>) -> Result<()> {
+    let max = (1u32 << info.bit_depth as u8) - 1;
+    let samples_fit = |len: usize| {
+        bkgd.len() == len
+            && bkgd
+                .chunks_exact(2)
+                .all(|s| u32::from(u16::from_be_bytes([s[0], s[1]])) <= max)
+    };
+    let valid = match info.color_type {
+        ColorType::Indexed => {
+            let entries = info
+                .palette
+                .as_ref()
+                .map_or(256, |palette| palette.len() / 3);
+            bkgd.len() == 1 && usize::from(bkgd[0]) < entries
+        }
+        ColorType::Grayscale | ColorType::GrayscaleAlpha => samples_fit(2),
+        ColorType::Rgb | ColorType::Rgba => samples_fit(6),
+    };
+    if !valid {
+        return Err(EncodingError::Format(
+            FormatErrorKind::InvalidBackground.into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Whether `name` can be written as the keyword of an `sPLT` or `pCAL` chunk. It is terminated by a NUL
+/// byte and must not have leading or trailing spaces.
+fn is_valid_keyword(name: &str) -> bool {
+    !name.contains('\0') && !name.starts_with(' ') && !name.ends_with(' ')
+}
+
 impl<W: Write> Writer<W> {
     fn new(w: W, info: PartialInfo, options: Options) -> Writer<W> {
         Writer {
@@ -562,6 +918,82 @@ impl<W: Write> Writer<W> {
             ));
         }
 
+        if let Some(cicp) = info.coding_independent_code_points {
+            if cicp.matrix_coefficients != 0 {
+                return Err(EncodingError::Format(
+                    FormatErrorKind::InvalidMatrixCoefficients(cicp.matrix_coefficients).into(),
+                ));
+            }
+        }
+
+        if let Some(mdcv) = &info.mastering_display_color_volume {
+            if mdcv.scaled_chromaticities().is_none() {
+                return Err(EncodingError::Format(
+                    FormatErrorKind::MasteringChromaticitiesOutOfRange.into(),
+                ));
+            }
+        }
+
+        if let Some(sbit) = &info.sbit {
+            validate_sbit(sbit, info.color_type, info.bit_depth)?;
+        }
+
+        if let Some(bkgd) = &info.bkgd {
+            validate_bkgd(bkgd, info)?;
+        }
+
+        if let Some(histogram) = &info.histogram {
+            let entries = info.palette.as_ref().map(|palette| palette.len() / 3);
+            if entries != Some(histogram.len()) {
+                return Err(EncodingError::Format(
+                    FormatErrorKind::InvalidHistogram.into(),
+                ));
+            }
+        }
+
+        for (idx, palette) in info.suggested_palettes.iter().enumerate() {
+            let max = match palette.sample_depth {
+                BitDepth::Eight => u16::from(u8::MAX),
+                BitDepth::Sixteen => u16::MAX,
+                _ => 0,
+            };
+            let fits = palette.entries.iter().all(|entry| {
+                [entry.red, entry.green, entry.blue, entry.alpha]
+                    .iter()
+                    .all(|&sample| sample <= max)
+            });
+            let unique = info.suggested_palettes[..idx]
+                .iter()
+                .all(|other| other.name != palette.name);
+            if max == 0 || !fits || !unique || !is_valid_keyword(&palette.name) {
+                return Err(EncodingError::Format(
+                    FormatErrorKind::InvalidSuggestedPalette.into(),
+                ));
+            }
+        }
+
+        if let Some(pcal) = &info.pixel_calibration {
+            if pcal.x0 == pcal.x1
+                || !is_valid_keyword(&pcal.name)
+                || pcal.unit.contains('\0')
+                || pcal.parameters.len() != pcal.equation.parameter_count()
+                || !pcal.parameters.iter().all(|p| p.is_finite())
+            {
+                return Err(EncodingError::Format(
+                    FormatErrorKind::InvalidPixelCalibration.into(),
+                ));
+            }
+        }
+
+        if let Some(scal) = info.physical_scale {
+            let valid = |value: f64| value.is_finite() && value > 0.0;
+            if !valid(scal.width) || !valid(scal.height) {
+                return Err(EncodingError::Format(
+                    FormatErrorKind::InvalidPhysicalScale.into(),
+                ));
+            }
+        }
+
         self.encode_header(info)?;
 
         Ok(self)
@@ -592,6 +1024,24 @@ impl<W: Write> Writer<W> {
             self.write_chunk(chunk::pHYs, &phys_data)?;
         }
 
+        // The extension chunks that describe the image as a whole must come before IDAT.
+        if let Some(offset) = info.offset {
+            offset.encode(&mut self.w)?;
+        }
+        if let Some(pcal) = &info.pixel_calibration {
+            self.write_pcal_chunk(pcal)?;
+        }
+        if let Some(scal) = info.physical_scale {
+            let mut data = vec![scal.unit as u8];
+            data.extend(scal.width.to_string().bytes());
+            data.push(0);
+            data.extend(scal.height.to_string().bytes());
+            self.write_chunk(chunk::sCAL, &data)?;
+        }
+        if let Some(layout) = info.stereo_layout {
+            self.write_chunk(chunk::sTER, &[layout as u8])?;
+        }
+
         // If specified, the sRGB information overrides the source gamma and chromaticities.
         if let Some(srgb) = &info.srgb {
             srgb.encode(&mut self.w)?;
@@ -617,14 +1067,35 @@ impl<W: Write> Writer<W> {
             }
         }
 
+        // The HDR metadata chunks must come before the PLTE and IDAT chunks.
+        if let Some(cicp) = info.coding_independent_code_points {
+            cicp.encode(&mut self.w)?;
+        }
+        if let Some(mdcv) = info.mastering_display_color_volume {
+            mdcv.encode(&mut self.w)?;
+        }
+        if let Some(clli) = info.content_light_level {
+            clli.encode(&mut self.w)?;
+        }
+
+        if let Some(sbit) = &info.sbit {
+            self.write_chunk(chunk::sBIT, sbit)?;
+        }
+
         if let Some(exif) = &info.exif_metadata {
             self.write_chunk(chunk::eXIf, exif)?;
         }
 
+        if let Some(time) = info.modification_time {
+            time.encode(&mut self.w)?;
+        }
+
         if let Some(actl) = info.animation_control {
             actl.encode(&mut self.w)?;
         }
 
+        self.write_unknown_chunks(info, ChunkLocation::BeforePlte)?;
+
         // The position of the PLTE chunk is important, it must come before the tRNS chunk and after
         // many of the other metadata chunks.
         if let Some(p) = &info.palette {
@@ -635,6 +1106,21 @@ impl<W: Write> Writer<W> {
             self.write_chunk(chunk::tRNS, t)?;
         }
 
+        // The bKGD chunk of indexed images refers to the PLTE chunk, so it must follow it.
+        if let Some(bkgd) = &info.bkgd {
+            self.write_chunk(chunk::bKGD, bkgd)?;
+        }
+
+        // The hIST chunk must follow the PLTE chunk, which `init` made sure exists.
+        if let Some(histogram) = &info.histogram {
+            let data: Vec<u8> = histogram.iter().flat_map(|f| f.to_be_bytes()).collect();
+            self.write_chunk(chunk::hIST, &data)?;
+        }
+
+        for palette in &info.suggested_palettes {
+            self.write_splt_chunk(palette)?;
+        }
+
         for text_chunk in &info.uncompressed_latin1_text {
             self.write_text_chunk(text_chunk)?;
         }
@@ -647,6 +1133,19 @@ impl<W: Write> Writer<W> {
             self.write_text_chunk(text_chunk)?;
         }
 
+        for control in &info.gif_graphic_controls {
+            control.encode(&mut self.w)?;
+        }
+
+        for extension in &info.gif_application_extensions {
+            let mut data = extension.identifier.to_vec();
+            data.extend(extension.authentication_code);
+            data.extend(&extension.data);
+            self.write_chunk(chunk::gIFx, &data)?;
+        }
+
+        self.write_unknown_chunks(info, ChunkLocation::BeforeIdat)?;
+
         Ok(())
     }
 
@@ -671,6 +1170,47 @@ impl<W: Write> Writer<W> {
         text_chunk.encode(&mut self.w)
     }
 
+    fn write_splt_chunk(&mut self, palette: &SuggestedPalette) -> Result<()> {
+        let name = encode_iso_8859_1(&palette.name)?;
+        if name.is_empty() || name.len() > 79 {
+            return Err(TextEncodingError::InvalidKeywordSize.into());
+        }
+
+        let sixteen = palette.sample_depth == BitDepth::Sixteen;
+        let mut data = name;
+        data.extend([0, palette.sample_depth as u8]);
+        for entry in &palette.entries {
+            for sample in [entry.red, entry.green, entry.blue, entry.alpha] {
+                if sixteen {
+                    data.extend(sample.to_be_bytes());
+                } else {
+                    data.push(sample as u8);
+                }
+            }
+            data.extend(entry.frequency.to_be_bytes());
+        }
+        self.write_chunk(chunk::sPLT, &data)
+    }
+
+    fn write_pcal_chunk(&mut self, pcal: &PixelCalibration) -> Result<()> {
+        let name = encode_iso_8859_1(&pcal.name)?;
+        if name.is_empty() || name.len() > 79 {
+            return Err(TextEncodingError::InvalidKeywordSize.into());
+        }
+
+        let mut data = name;
+        data.push(0);
+        data.extend(pcal.x0.to_be_bytes());
+        data.extend(pcal.x1.to_be_bytes());
+        data.extend([pcal.equation as u8, pcal.parameters.len() as u8]);
+        data.extend(encode_iso_8859_1(&pcal.unit)?);
+        for parameter in &pcal.parameters {
+            data.push(0);
+            data.extend(parameter.to_string().bytes());
+        }
+        self.write_chunk(chunk::pCAL, &data)
+    }
+
     fn write_iccp_chunk(&mut self, profile_name: &str, icc_profile: &[u8]) -> Result<()> {
         let profile_name = encode_iso_8859_1(profile_name)?;
         if profile_name.is_empty() || profile_name.len() > 79 {
@@ -720,26 +1260,10 @@ impl<W: Write> Writer<W> {
         }
     }
 
-    fn validate_sequence_done(&self) -> Result<()> {
-        if !self.options.validate_sequence {
-            return Ok(());
-        }
-
-        if (self.info.animation_control.is_some() && self.info.frame_control.is_some())
-            || self.images_written == 0
-        {
-            Err(EncodingError::Format(FormatErrorKind::MissingFrames.into()))
-        } else {
-            Ok(())
-        }
-    }
-
-    const MAX_IDAT_CHUNK_LEN: u32 = u32::MAX >> 1;
-    #[allow(non_upper_case_globals)]
-    const MAX_fdAT_CHUNK_LEN: u32 = (u32::MAX >> 1) - 4;
-
-    /// Writes the next image data.
-    pub fn write_image_data(&mut self, data: &[u8]) -> Result<()> {
+    /// Checks that the next image can be written and that `len` is the size of its data in bytes.
+    ///
+    /// Returns the width and height of the image.
+    fn validate_image_data(&self, len: usize) -> Result<(usize, usize)> {
         if self.info.color_type == ColorType::Indexed && !self.info.has_palette {
             return Err(EncodingError::Format(FormatErrorKind::NoPalette.into()));
         }
@@ -748,7 +1272,7 @@ impl<W: Write> Writer<W> {
 
         let width: usize;
         let height: usize;
-        if let Some(ref mut fctl) = self.info.frame_control {
+        if let Some(ref fctl) = self.info.frame_control {
             width = fctl.width as usize;
             height = fctl.height as usize;
         } else {
@@ -758,18 +1282,93 @@ impl<W: Write> Writer<W> {
 
         let in_len = self.info.raw_row_length_from_width(width as u32) - 1;
         let data_size = in_len * height;
-        if data_size != data.len() {
+        if data_size != len {
             return Err(EncodingError::Parameter(
                 ParameterErrorKind::ImageBufferSize {
                     expected: data_size,
-                    actual: data.len(),
+                    actual: len,
                 }
                 .into(),
             ));
         }
 
-        let prev = vec![0; in_len];
-        let mut prev = prev.as_slice();
+        Ok((width, height))
+    }
+
+    fn validate_sequence_done(&self) -> Result<()> {
+        if !self.options.validate_sequence {
+            return Ok(());
+        }
+
+        if (self.info.animation_control.is_some() && self.info.frame_control.is_some())
+            || self.images_written == 0
+        {
+            Err(EncodingError::Format(FormatErrorKind::MissingFrames.into()))
+        } else {
+            Ok(())
+        }
+    }
+
+    const MAX_IDAT_CHUNK_LEN: u32 = u32::MAX >> 1;
+    #[allow(non_upper_case_globals)]
+    const MAX_fdAT_CHUNK_LEN: u32 = (u32::MAX >> 1) - 4;
+
+    /// Writes the next image data.
+    pub fn write_image_data(&mut self, data: &[u8]) -> Result<()> {
+        self.write_image_samples(ImageSamples::Bytes(data))
+    }
+
+    /// Writes the next image data like [`Writer::write_image_data`], from one element for each
+    /// 16-bit sample.
+    ///
+    /// The image must have a bit depth of 16. The samples are converted to the big-endian byte
+    /// order of PNG one row at a time.
+    pub fn write_image_data_u16(&mut self, data: &[u16]) -> Result<()> {
+        if self.info.bit_depth != BitDepth::Sixteen {
+            return Err(EncodingError::Parameter(
+                ParameterErrorKind::SampleBitDepth(self.info.bit_depth).into(),
+            ));
+        }
+
+        self.write_image_samples(ImageSamples::U16(data))
+    }
+
+    /// Implementation of [`Writer::write_image_data`] and [`Writer::write_image_data_u16`].
+    fn write_image_samples(&mut self, data: ImageSamples<'_>) -> Result<()> {
+        let (width, height) = self.validate_image_data(data.len())?;
+        let in_len = self.info.raw_row_length_from_width(width as u32) - 1;
+
+        let rows = ImageRows {
+            data,
+            row_len: in_len,
+            premultiplied: self
+                .options
+                .premultiplied_alpha
+                .then_some((self.info.color_type, self.info.bit_depth)),
+        };
+
+        // An interlaced frame is encoded as the sequence of its Adam7 passes. Each pass is
+        // filtered as an image of its own, i.e. the first row of every pass has no previous row.
+        let passes;
+        let sub_images: Vec<ImageRows<'_>> = if self.info.interlaced {
+            passes = adam7_passes(
+                self.info.color_type,
+                self.info.bit_depth,
+                &rows,
+                width as u32,
+                height as u32,
+            );
+            passes
+                .iter()
+                .map(|(row_len, pass)| ImageRows {
+                    data: ImageSamples::Bytes(pass),
+                    row_len: *row_len,
+                    premultiplied: None,
+                })
+                .collect()
+        } else {
+            vec![rows]
+        };
 
         let bpp = self.info.bpp_in_prediction();
         let filter_method = self.options.filter;
@@ -778,27 +1377,40 @@ impl<W: Write> Writer<W> {
             DeflateCompression::NoCompression => {
                 let mut compressor =
                     fdeflate::StoredOnlyCompressor::new(std::io::Cursor::new(Vec::new()))?;
-                for line in data.chunks(in_len) {
-                    compressor.write_data(&[0])?;
-                    compressor.write_data(line)?;
+                for sub_image in &sub_images {
+                    let mut buf = vec![0; sub_image.row_len];
+                    for y in 0..sub_image.count() {
+                        compressor.write_data(&[0])?;
+                        compressor.write_data(sub_image.row(y, &mut buf))?;
+                    }
                 }
                 compressor.finish()?.into_inner()
             }
             DeflateCompression::FdeflateUltraFast => {
                 let mut compressor = fdeflate::Compressor::new(std::io::Cursor::new(Vec::new()))?;
 
-                let mut current = vec![0; in_len + 1];
-                for line in data.chunks(in_len) {
-                    let filter_type = filter(filter_method, bpp, prev, line, &mut current[1..]);
-
-                    current[0] = filter_type as u8;
-                    compressor.write_data(&current)?;
-                    prev = line;
+                let mut filtered_size = 0;
+                for sub_image in &sub_images {
+                    let row_len = sub_image.row_len;
+                    let mut prev = vec![0; row_len];
+                    let mut buf = vec![0; row_len];
+
+                    let mut current = vec![0; row_len + 1];
+                    for y in 0..sub_image.count() {
+                        let line = sub_image.row(y, &mut buf);
+                        let filter_type =
+                            filter(filter_method, bpp, &prev, line, &mut current[1..]);
+
+                        current[0] = filter_type as u8;
+                        compressor.write_data(&current)?;
+                        prev.copy_from_slice(line);
+                    }
+                    filtered_size += sub_image.count() * (row_len + 1);
                 }
 
                 let compressed = compressor.finish()?.into_inner();
                 if compressed.len()
-                    > fdeflate::StoredOnlyCompressor::<()>::compressed_size((in_len + 1) * height)
+                    > fdeflate::StoredOnlyCompressor::<()>::compressed_size(filtered_size)
                 {
                     // Write uncompressed data since the result from fast compression would take
                     // more space than that.
@@ -806,9 +1418,12 @@ impl<W: Write> Writer<W> {
                     // This is essentially a fallback to NoCompression.
                     let mut compressor =
                         fdeflate::StoredOnlyCompressor::new(std::io::Cursor::new(Vec::new()))?;
-                    for line in data.chunks(in_len) {
-                        compressor.write_data(&[0])?;
-                        compressor.write_data(line)?;
+                    for sub_image in &sub_images {
+                        let mut buf = vec![0; sub_image.row_len];
+                        for y in 0..sub_image.count() {
+                            compressor.write_data(&[0])?;
+                            compressor.write_data(sub_image.row(y, &mut buf))?;
+                        }
                     }
                     compressor.finish()?.into_inner()
                 } else {
@@ -816,16 +1431,22 @@ impl<W: Write> Writer<W> {
                 }
             }
             DeflateCompression::Level(level) => {
-                let mut current = vec![0; in_len];
-
                 let mut zlib =
                     ZlibEncoder::new(Vec::new(), flate2::Compression::new(u32::from(level)));
-                for line in data.chunks(in_len) {
-                    let filter_type = filter(filter_method, bpp, prev, line, &mut current);
-
-                    zlib.write_all(&[filter_type as u8])?;
-                    zlib.write_all(&current)?;
-                    prev = line;
+                for sub_image in &sub_images {
+                    let row_len = sub_image.row_len;
+                    let mut prev = vec![0; row_len];
+                    let mut buf = vec![0; row_len];
+
+                    let mut current = vec![0; row_len];
+                    for y in 0..sub_image.count() {
+                        let line = sub_image.row(y, &mut buf);
+                        let filter_type = filter(filter_method, bpp, &prev, line, &mut current);
+
+                        zlib.write_all(&[filter_type as u8])?;
+                        zlib.write_all(&current)?;
+                        prev.copy_from_slice(line);
+                    }
                 }
                 zlib.finish()?
             }
@@ -875,8 +1496,21 @@ impl<W: Write> Writer<W> {
         }
     }
 
+    /// Writes the unknown chunks of `info` that belong at `location`, in their original order.
+    fn write_unknown_chunks(&mut self, info: &Info<'_>, location: ChunkLocation) -> Result<()> {
+        for unknown in &info.unknown_chunks {
+            if unknown.location == location {
+                self.write_chunk(unknown.type_, &unknown.data)?;
+            }
+        }
+        Ok(())
+    }
+
     fn write_iend(&mut self) -> Result<()> {
         self.iend_written = true;
+        for unknown in mem::take(&mut self.info.trailing_chunks) {
+            self.write_chunk(unknown.type_, &unknown.data)?;
+        }
         self.write_chunk(chunk::IEND, &[])
     }
 
@@ -1190,29 +1824,38 @@ impl<'a, W: Write> ChunkWriter<'a, W> {
         }
     }
 
+    /// Returns the width and height of the next frame
+    fn next_frame_dimensions(&self) -> (u32, u32) {
+        let wrt = self.writer.deref();
+
+        if let Some(fctl) = wrt.info.frame_control {
+            (fctl.width, fctl.height)
+        } else {
+            (wrt.info.width, wrt.info.height)
+        }
+    }
+
     /// Returns the size of each scanline for the next frame
     /// paired with the size of the whole frame
     ///
     /// This is used by the `StreamWriter` to know when the scanline ends
     /// so it can filter compress it and also to know when to start
     /// the next one
+    ///
+    /// Interlaced frames can only be filtered once all of their rows are known, so for those the
+    /// whole frame is treated as a single scanline.
     fn next_frame_info(&self) -> (usize, usize) {
         let wrt = self.writer.deref();
+        let (width, height) = self.next_frame_dimensions();
 
-        let width: usize;
-        let height: usize;
-        if let Some(fctl) = wrt.info.frame_control {
-            width = fctl.width as usize;
-            height = fctl.height as usize;
+        let in_len = wrt.info.raw_row_length_from_width(width) - 1;
+        let data_size = in_len * height as usize;
+
+        if wrt.info.interlaced {
+            (data_size, data_size)
         } else {
-            width = wrt.info.width as usize;
-            height = wrt.info.height as usize;
+            (in_len, data_size)
         }
-
-        let in_len = wrt.info.raw_row_length_from_width(width as u32) - 1;
-        let data_size = in_len * height;
-
-        (in_len, data_size)
     }
 
     /// NOTE: this bypasses the internal buffer so the flush method should be called before this
@@ -1232,6 +1875,7 @@ impl<'a, W: Write> ChunkWriter<'a, W> {
             Some(ref mut fctl) => {
                 fctl.encode(&mut wrt.w)?;
                 fctl.sequence_number += 1;
+                wrt.animation_written += 1;
             }
             _ => {}
         }
@@ -1281,10 +1925,10 @@ impl<'a, W: Write> Write for ChunkWriter<'a, W> {
         if self.index == 0 {
             let wrt = self.writer.deref_mut();
 
-            // Prepare the next animated frame, if any.
-            let no_fctl = wrt.should_skip_frame_control_on_default_image();
-            if wrt.info.frame_control.is_some() && !no_fctl {
-                let fctl = wrt.info.frame_control.as_mut().unwrap();
+            // Prepare the next animated frame, if any. The default image is always written as
+            // `IDAT`, which has no sequence number.
+            let is_fdat = self.curr_chunk == chunk::fdAT;
+            if let Some(fctl) = wrt.info.frame_control.as_mut().filter(|_| is_fdat) {
                 self.buffer[0..4].copy_from_slice(&fctl.sequence_number.to_be_bytes());
                 fctl.sequence_number += 1;
                 self.index = 4;
@@ -1367,6 +2011,25 @@ impl<'a, W: Write> Wrapper<'a, W> {
         mem::swap(self, &mut swap);
         swap
     }
+
+    /// Compresses a filtered scanline, preceded by its filter type.
+    ///
+    /// NOTE: This method must only be called while a frame is being compressed
+    fn write_row(&mut self, filter_type: RowFilter, filtered: &[u8]) -> io::Result<()> {
+        // This can't fail as the other variant is used only to allow the zlib encoder to finish
+        match self {
+            Wrapper::Flate2(wrt) => {
+                wrt.write_all(&[filter_type as u8])?;
+                wrt.write_all(filtered)?;
+            }
+            Wrapper::FDeflate(wrt) => {
+                wrt.write_data(&[filter_type as u8])?;
+                wrt.write_data(filtered)?;
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
 }
 
 /// Streaming PNG writer
@@ -1393,10 +2056,19 @@ pub struct StreamWriter<'a, W: Write> {
     width: u32,
     height: u32,
 
+    /// Interlaced frames are buffered in `curr_buf` and written pass by pass once complete.
+    interlaced: bool,
+    color_type: ColorType,
+    bit_depth: BitDepth,
+    /// dimensions of the frame currently being written
+    frame_width: u32,
+    frame_height: u32,
+
     bpp: BytesPerPixel,
     filter: Filter,
     fctl: Option<FrameControl>,
     compression: DeflateCompression,
+    premultiplied_alpha: bool,
 }
 
 impl<'a, W: Write> StreamWriter<'a, W> {
@@ -1404,7 +2076,10 @@ impl<'a, W: Write> StreamWriter<'a, W> {
         let PartialInfo {
             width,
             height,
+            bit_depth,
+            color_type,
             frame_control: fctl,
+            interlaced,
             ..
         } = writer.info;
 
@@ -1412,12 +2087,18 @@ impl<'a, W: Write> StreamWriter<'a, W> {
         let in_len = writer.info.raw_row_length() - 1;
         let filter = writer.options.filter;
         let compression = writer.options.compression;
+        let premultiplied_alpha = writer.options.premultiplied_alpha;
         let prev_buf = vec![0; in_len];
-        let curr_buf = vec![0; in_len];
+        let curr_buf = if interlaced {
+            vec![0; in_len * height as usize]
+        } else {
+            vec![0; in_len]
+        };
         let filtered_buf = vec![0; in_len];
 
         let mut chunk_writer = ChunkWriter::new(writer, buf_len);
         let (line_len, to_write) = chunk_writer.next_frame_info();
+        let (frame_width, frame_height) = chunk_writer.next_frame_dimensions();
         chunk_writer.write_header()?;
 
         Ok(StreamWriter {
@@ -1432,8 +2113,14 @@ impl<'a, W: Write> StreamWriter<'a, W> {
             height,
             line_len,
             to_write,
+            interlaced,
+            color_type,
+            bit_depth,
+            frame_width,
+            frame_height,
             fctl,
             compression,
+            premultiplied_alpha,
         })
     }
 
@@ -1621,10 +2308,15 @@ impl<'a, W: Write> StreamWriter<'a, W> {
                 wrt.writer.validate_sequence_done()?;
             }
             Wrapper::FDeflate(wrt) => {
-                wrt.finish()?;
+                let mut wrt = wrt.finish()?;
+                wrt.flush()?;
+                // A borrowed writer continues after the complete frame.
+                wrt.writer.increment_images_written();
             }
             Wrapper::Flate2(wrt) => {
-                wrt.finish()?;
+                let mut wrt = wrt.finish()?;
+                wrt.flush()?;
+                wrt.writer.increment_images_written();
             }
             Wrapper::None => unreachable!(),
             Wrapper::Unrecoverable => {
@@ -1636,6 +2328,41 @@ impl<'a, W: Write> StreamWriter<'a, W> {
         Ok(())
     }
 
+    /// Filters and compresses the complete interlaced frame buffered in `curr_buf`, one Adam7 pass
+    /// at a time.
+    fn write_interlaced_frame(&mut self) -> io::Result<()> {
+        let rows = ImageRows {
+            data: ImageSamples::Bytes(&self.curr_buf[..self.line_len]),
+            row_len: self
+                .color_type
+                .raw_row_length_from_width(self.bit_depth, self.frame_width)
+                - 1,
+            premultiplied: None,
+        };
+        let passes = adam7_passes(
+            self.color_type,
+            self.bit_depth,
+            &rows,
+            self.frame_width,
+            self.frame_height,
+        );
+
+        for (row_len, pass) in &passes {
+            // Each pass is filtered as an image of its own.
+            let prev = &mut self.prev_buf[..*row_len];
+            prev.fill(0);
+
+            let filtered = &mut self.filtered_buf[..*row_len];
+            for line in pass.chunks(*row_len) {
+                let filter_type = filter(self.filter, self.bpp, prev, line, filtered);
+                self.writer.write_row(filter_type, filtered)?;
+                prev.copy_from_slice(line);
+            }
+        }
+
+        Ok(())
+    }
+
     /// Flushes the buffered chunk, checks if it was the last frame,
     /// writes the next frame header and gets the next frame scanline size
     /// and image size.
@@ -1653,17 +2380,21 @@ impl<'a, W: Write> StreamWriter<'a, W> {
             Wrapper::None => unreachable!(),
         };
         wrt.flush()?;
+        // The previous frame is complete now.
+        wrt.writer.increment_images_written();
         wrt.writer.validate_new_image()?;
 
-        if let Some(fctl) = self.fctl {
+        if let (Some(fctl), Some(_)) = (self.fctl, wrt.writer.info.frame_control) {
             wrt.set_fctl(fctl);
         }
         let (scansize, size) = wrt.next_frame_info();
         self.line_len = scansize;
         self.to_write = size;
+        (self.frame_width, self.frame_height) = wrt.next_frame_dimensions();
+        // Each frame is filtered on its own.
+        self.prev_buf.fill(0);
 
         wrt.write_header()?;
-        wrt.writer.increment_images_written();
 
         // now it can be taken because the next statements cannot cause any errors
         match self.writer.take() {
@@ -1721,26 +2452,23 @@ impl<'a, W: Write> Write for StreamWriter<'a, W> {
         self.index += written;
         self.to_write -= written;
 
-        if self.index == self.line_len {
+        if self.index == self.line_len && self.premultiplied_alpha {
+            let line = &mut self.curr_buf[..self.line_len];
+            unpremultiply_alpha(self.color_type, self.bit_depth, line);
+        }
+        if self.index == self.line_len && self.interlaced {
+            self.write_interlaced_frame()?;
+            self.index = 0;
+        } else if self.index == self.line_len {
+            let filtered = &mut self.filtered_buf[..self.line_len];
             let filter_type = filter(
                 self.filter,
                 self.bpp,
-                &self.prev_buf,
-                &self.curr_buf,
-                &mut self.filtered_buf,
+                &self.prev_buf[..self.line_len],
+                &self.curr_buf[..self.line_len],
+                filtered,
             );
-            // This can't fail as the other variant is used only to allow the zlib encoder to finish
-            match &mut self.writer {
-                Wrapper::Flate2(wrt) => {
-                    wrt.write_all(&[filter_type as u8])?;
-                    wrt.write_all(&self.filtered_buf)?;
-                }
-                Wrapper::FDeflate(wrt) => {
-                    wrt.write_data(&[filter_type as u8])?;
-                    wrt.write_data(&self.filtered_buf)?;
-                }
-                _ => unreachable!(),
-            };
+            self.writer.write_row(filter_type, filtered)?;
 
             mem::swap(&mut self.prev_buf, &mut self.curr_buf);
             self.index = 0;
@@ -1762,7 +2490,8 @@ impl<'a, W: Write> Write for StreamWriter<'a, W> {
             }
         }
 
-        if self.index > 0 {
+        // A partially written interlaced frame stays buffered until it is complete.
+        if self.index > 0 && !self.interlaced {
             let err = FormatErrorKind::WrittenTooMuch(self.index).into();
             return Err(EncodingError::Format(err).into());
         }
@@ -1780,7 +2509,7 @@ impl<W: Write> Drop for StreamWriter<'_, W> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::Decoder;
+    use crate::{CalibrationEquation, Decoder, OffsetUnit, ScaleUnit, SuggestedPaletteEntry};
 
     use io::BufReader;
     use rand::{rng, Rng};
@@ -1801,49 +2530,7 @@ mod tests {
     fn roundtrip_inner() {
         // More loops = more random testing, but also more test wait time
         for _ in 0..5 {
-            for path in glob::glob("tests/pngsuite/*.png")
-                .unwrap()
-                .map(|r| r.unwrap())
-            {
-                if path.file_name().unwrap().to_str().unwrap().starts_with('x') {
-                    // x* files are expected to fail to decode
-                    continue;
-                }
-                eprintln!("{}", path.display());
-                // Decode image
-                let decoder = Decoder::new(BufReader::new(File::open(path).unwrap()));
-                let mut reader = decoder.read_info().unwrap();
-                let mut buf = vec![0; reader.output_buffer_size().unwrap()];
-                let info = reader.next_frame(&mut buf).unwrap();
-                use DeflateCompression::*;
-                for compression in [NoCompression, FdeflateUltraFast, Level(4)] {
-                    // Encode decoded image
-                    let mut out = Vec::new();
-                    {
-                        let mut wrapper = RandomChunkWriter {
-                            rng: rng(),
-                            w: &mut out,
-                        };
-
-                        let mut encoder = Encoder::new(&mut wrapper, info.width, info.height);
-                        encoder.set_color(info.color_type);
-                        encoder.set_depth(info.bit_depth);
-                        encoder.set_deflate_compression(compression);
-                        if let Some(palette) = &reader.info().palette {
-                            encoder.set_palette(palette.clone());
-                        }
-                        let mut encoder = encoder.write_header().unwrap();
-                        encoder.write_image_data(&buf).unwrap();
-                    }
-                    // Decode encoded decoded image
-                    let decoder = Decoder::new(Cursor::new(&*out));
-                    let mut reader = decoder.read_info().unwrap();
-                    let mut buf2 = vec![0; reader.output_buffer_size().unwrap()];
-                    reader.next_frame(&mut buf2).unwrap();
-                    // check if the encoded image is ok:
-                    assert_eq!(buf, buf2);
-                }
-            }
+            roundtrip_pngsuite(false, false);
         }
     }
 
@@ -1860,37 +2547,54 @@ mod tests {
     fn roundtrip_stream_inner() {
         // More loops = more random testing, but also more test wait time
         for _ in 0..5 {
-            for path in glob::glob("tests/pngsuite/*.png")
-                .unwrap()
-                .map(|r| r.unwrap())
-            {
-                if path.file_name().unwrap().to_str().unwrap().starts_with('x') {
-                    // x* files are expected to fail to decode
-                    continue;
-                }
-                // Decode image
-                let decoder = Decoder::new(BufReader::new(File::open(path).unwrap()));
-                let mut reader = decoder.read_info().unwrap();
-                let mut buf = vec![0; reader.output_buffer_size().unwrap()];
-                let info = reader.next_frame(&mut buf).unwrap();
-                use DeflateCompression::*;
-                for compression in [NoCompression, FdeflateUltraFast, Level(4)] {
-                    // Encode decoded image
-                    let mut out = Vec::new();
-                    {
-                        let mut wrapper = RandomChunkWriter {
-                            rng: rng(),
-                            w: &mut out,
-                        };
+            roundtrip_pngsuite(false, true);
+        }
+    }
 
-                        let mut encoder = Encoder::new(&mut wrapper, info.width, info.height);
-                        encoder.set_color(info.color_type);
-                        encoder.set_depth(info.bit_depth);
-                        encoder.set_deflate_compression(compression);
-                        if let Some(palette) = &reader.info().palette {
-                            encoder.set_palette(palette.clone());
-                        }
-                        let mut encoder = encoder.write_header().unwrap();
+    #[test]
+    fn roundtrip_interlaced() {
+        for stream in [false, true] {
+            roundtrip_pngsuite(true, stream);
+        }
+    }
+
+    /// Decodes each image of the PNG suite, encodes it again with every compression and checks
+    /// that the result decodes to the same image.
+    fn roundtrip_pngsuite(interlaced: bool, stream: bool) {
+        for path in glob::glob("tests/pngsuite/*.png")
+            .unwrap()
+            .map(|r| r.unwrap())
+        {
+            if path.file_name().unwrap().to_str().unwrap().starts_with('x') {
+                // x* files are expected to fail to decode
+                continue;
+            }
+            eprintln!("{}", path.display());
+            // Decode image
+            let decoder = Decoder::new(BufReader::new(File::open(path).unwrap()));
+            let mut reader = decoder.read_info().unwrap();
+            let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+            let info = reader.next_frame(&mut buf).unwrap();
+            use DeflateCompression::*;
+            for compression in [NoCompression, FdeflateUltraFast, Level(4)] {
+                // Encode decoded image
+                let mut out = Vec::new();
+                {
+                    let mut wrapper = RandomChunkWriter {
+                        rng: rng(),
+                        w: &mut out,
+                    };
+
+                    let mut encoder = Encoder::new(&mut wrapper, info.width, info.height);
+                    encoder.set_color(info.color_type);
+                    encoder.set_depth(info.bit_depth);
+                    encoder.set_deflate_compression(compression);
+                    encoder.set_interlaced(interlaced);
+                    if let Some(palette) = &reader.info().palette {
+                        encoder.set_palette(palette.clone());
+                    }
+                    let mut encoder = encoder.write_header().unwrap();
+                    if stream {
                         let mut stream_writer = encoder.stream_writer().unwrap();
 
                         let mut outer_wrapper = RandomChunkWriter {
@@ -1899,19 +2603,519 @@ mod tests {
                         };
 
                         outer_wrapper.write_all(&buf).unwrap();
+                    } else {
+                        encoder.write_image_data(&buf).unwrap();
                     }
-                    // Decode encoded decoded image
-                    let decoder = Decoder::new(Cursor::new(&*out));
-                    let mut reader = decoder.read_info().unwrap();
-                    let mut buf2 = vec![0; reader.output_buffer_size().unwrap()];
-                    reader.next_frame(&mut buf2).unwrap();
-                    // check if the encoded image is ok:
-                    assert_eq!(buf, buf2);
                 }
+                // Decode encoded decoded image
+                let decoder = Decoder::new(Cursor::new(&*out));
+                let mut reader = decoder.read_info().unwrap();
+                assert_eq!(reader.info().interlaced, interlaced);
+                let mut buf2 = vec![0; reader.output_buffer_size().unwrap()];
+                reader.next_frame(&mut buf2).unwrap();
+                // check if the encoded image is ok:
+                assert_eq!(buf, buf2);
             }
         }
     }
 
+    #[test]
+    fn roundtrip_u16() {
+        let decoder = Decoder::new(BufReader::new(
+            File::open("tests/pngsuite/basn6a16.png").unwrap(),
+        ));
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size().unwrap() / 2];
+        let info = reader.next_frame_u16(&mut buf).unwrap();
+
+        let bytes: Vec<u8> = buf.iter().flat_map(|s| s.to_be_bytes()).collect();
+        for interlaced in [false, true] {
+            let encode = |u16_samples: bool| {
+                let mut out = Vec::new();
+                {
+                    let mut encoder = Encoder::new(&mut out, info.width, info.height);
+                    encoder.set_color(info.color_type);
+                    encoder.set_depth(info.bit_depth);
+                    encoder.set_interlaced(interlaced);
+                    let mut encoder = encoder.write_header().unwrap();
+                    if u16_samples {
+                        encoder.write_image_data_u16(&buf).unwrap();
+                    } else {
+                        encoder.write_image_data(&bytes).unwrap();
+                    }
+                }
+                out
+            };
+            let out = encode(true);
+            assert_eq!(out, encode(false));
+
+            let mut reader = Decoder::new(Cursor::new(&*out)).read_info().unwrap();
+            let mut buf2 = vec![0; reader.output_buffer_size().unwrap() / 2];
+            reader.next_frame_u16(&mut buf2).unwrap();
+            assert_eq!(buf, buf2);
+        }
+
+        let mut encoder = Encoder::new(Vec::new(), 1, 1);
+        encoder.set_depth(BitDepth::Eight);
+        let mut encoder = encoder.write_header().unwrap();
+        assert!(matches!(
+            encoder.write_image_data_u16(&[0]),
+            Err(EncodingError::Parameter(_))
+        ));
+    }
+
+    #[test]
+    fn roundtrip_hdr_metadata() {
+        let mut decoder = Decoder::new(BufReader::new(
+            File::open("tests/bugfixes/cicp_pq.png").unwrap(),
+        ));
+        decoder.ignore_checksums(true);
+        let reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        let cicp = info.coding_independent_code_points.unwrap();
+        let mdcv = info.mastering_display_color_volume.unwrap();
+        let clli = info.content_light_level.unwrap();
+
+        // The matrix coefficients must be zero for RGB images.
+        let invalid_cicp = CodingIndependentCodePoints {
+            matrix_coefficients: 1,
+            ..cicp
+        };
+        let mut invalid_mdcv = mdcv;
+        invalid_mdcv.chromaticities.white.0 = ScaledFloat::new(1.5);
+
+        for (cicp, mdcv, clli, valid) in [
+            (Some(cicp), Some(mdcv), Some(clli), true),
+            (Some(cicp), None, None, true),
+            (None, Some(mdcv), None, true),
+            (None, None, Some(clli), true),
+            (Some(invalid_cicp), None, None, false),
+            (None, Some(invalid_mdcv), None, false),
+        ] {
+            let mut info = Info::with_size(1, 1);
+            info.color_type = ColorType::Rgb;
+            info.coding_independent_code_points = cicp;
+            info.mastering_display_color_volume = mdcv;
+            info.content_light_level = clli;
+            match roundtrip_info(info) {
+                Ok(info) => {
+                    assert!(valid);
+                    assert_eq!(info.coding_independent_code_points, cicp);
+                    assert_eq!(info.mastering_display_color_volume, mdcv);
+                    assert_eq!(info.content_light_level, clli);
+                }
+                Err(err) => assert!(!valid && matches!(err, EncodingError::Format(_))),
+            }
+        }
+    }
+
+    #[test]
+    fn roundtrip_sbit_and_bkgd() {
+        for name in ["cs3n3p08", "cs5n2c08", "bggn4a16", "bgyn6a16", "tbbn3p08"] {
+            let path = format!("tests/pngsuite/{}.png", name);
+            let decoder = Decoder::new(BufReader::new(File::open(path).unwrap()));
+            let info = decoder.read_info().unwrap().info().clone();
+            assert!(info.sbit.is_some() || info.bkgd.is_some(), "{}", name);
+
+            let roundtrip = roundtrip_info(info.clone()).unwrap();
+            assert_eq!(roundtrip.sbit, info.sbit, "{}", name);
+            assert_eq!(roundtrip.bkgd, info.bkgd, "{}", name);
+        }
+
+        let mut encoder = Encoder::new(Vec::new(), 1, 1);
+        encoder.set_color(ColorType::Rgb);
+        encoder.set_depth(BitDepth::Eight);
+        for (bits, valid) in [
+            (&[5, 6, 5][..], true),
+            (&[5, 6], false),
+            (&[5, 6, 9], false),
+        ] {
+            assert_eq!(
+                encoder.set_significant_bits(bits).is_ok(),
+                valid,
+                "{:?}",
+                bits
+            );
+        }
+        for (background, valid) in [
+            (&[255, 0, 128][..], true),
+            (&[256, 0, 128], false),
+            (&[255], false),
+        ] {
+            assert_eq!(
+                encoder.set_background_color(background).is_ok(),
+                valid,
+                "{:?}",
+                background
+            );
+        }
+
+        encoder.set_color(ColorType::Indexed);
+        encoder.set_palette(vec![0; 6]);
+        assert!(encoder.set_background_color(&[1]).is_ok());
+        assert!(encoder.set_background_color(&[2]).is_err());
+        assert_eq!(encoder.info.bkgd.as_deref(), Some(&[1][..]));
+
+        // Changing the color type afterwards is caught when writing the header.
+        encoder.set_color(ColorType::Grayscale);
+        assert!(encoder.write_header().is_err());
+    }
+
+    #[test]
+    fn roundtrip_histogram_and_suggested_palettes() {
+        for name in ["ch1n3p04", "ch2n3p08", "ps1n0g08", "ps2n2c16"] {
+            let path = format!("tests/pngsuite/{}.png", name);
+            let decoder = Decoder::new(BufReader::new(File::open(path).unwrap()));
+            let info = decoder.read_info().unwrap().info().clone();
+            assert!(
+                info.histogram.is_some() || !info.suggested_palettes.is_empty(),
+                "{}",
+                name
+            );
+
+            let roundtrip = roundtrip_info(info.clone()).unwrap();
+            assert_eq!(roundtrip.histogram, info.histogram, "{}", name);
+            assert_eq!(
+                roundtrip.suggested_palettes, info.suggested_palettes,
+                "{}",
+                name
+            );
+        }
+
+        let palette = SuggestedPalette {
+            name: "six-cube".to_string(),
+            sample_depth: BitDepth::Eight,
+            entries: vec![SuggestedPaletteEntry {
+                red: 255,
+                green: 0,
+                blue: 0,
+                alpha: 255,
+                frequency: 1,
+            }],
+        };
+        let named = |name: &str| SuggestedPalette {
+            name: name.to_string(),
+            ..palette.clone()
+        };
+        let mut wide = palette.clone();
+        wide.entries[0].red = 256;
+        let wide16 = SuggestedPalette {
+            sample_depth: BitDepth::Sixteen,
+            ..wide.clone()
+        };
+
+        // A histogram needs a palette, and suggested palettes need unique names without NUL or
+        // leading or trailing spaces, and samples that fit their depth.
+        for (idx, (histogram, palettes, valid)) in [
+            (None, vec![palette.clone()], true),
+            (None, vec![wide16], true),
+            (Some(vec![1]), vec![], false),
+            (None, vec![palette.clone(), palette.clone()], false),
+            (None, vec![named("six\0cube")], false),
+            (None, vec![named(" six-cube")], false),
+            (None, vec![named("six-cube ")], false),
+            (None, vec![wide], false),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let mut info = Info::with_size(1, 1);
+            info.histogram = histogram;
+            info.suggested_palettes = palettes.clone();
+            match roundtrip_info(info) {
+                Ok(info) => {
+                    assert!(valid, "{}", idx);
+                    assert_eq!(info.suggested_palettes, palettes, "{}", idx);
+                }
+                Err(_) => assert!(!valid, "{}", idx),
+            }
+        }
+    }
+
+    #[test]
+    fn roundtrip_extension_chunks() {
+        let pcal = PixelCalibration {
+            name: "Höhe".to_string(),
+            x0: -100,
+            x1: 100,
+            equation: CalibrationEquation::Hyperbolic,
+            unit: "m".to_string(),
+            parameters: vec![0.5, -2.0, 1e-7, 12345.678],
+        };
+        let scal = PhysicalScale {
+            unit: ScaleUnit::Radian,
+            width: 0.001,
+            height: 2.5e10,
+        };
+        let named = |name: &str| PixelCalibration {
+            name: name.to_string(),
+            ..pcal.clone()
+        };
+        let mut too_few = pcal.clone();
+        too_few.parameters.pop();
+        let empty_range = PixelCalibration {
+            x1: pcal.x0,
+            ..pcal.clone()
+        };
+        let with_pcal = |pcal: PixelCalibration| Info {
+            pixel_calibration: Some(pcal),
+            ..Info::default()
+        };
+        let with_scal = |height: f64| Info {
+            physical_scale: Some(PhysicalScale { height, ..scal }),
+            ..Info::default()
+        };
+
+        let cases = [
+            (
+                Info {
+                    offset: Some(ImageOffset {
+                        x: -20,
+                        y: 30,
+                        unit: OffsetUnit::Pixel,
+                    }),
+                    ..Info::default()
+                },
+                true,
+            ),
+            (with_pcal(pcal.clone()), true),
+            (with_scal(scal.height), true),
+            (
+                Info {
+                    stereo_layout: Some(StereoLayout::CrossFuse),
+                    ..Info::default()
+                },
+                true,
+            ),
+            (
+                Info {
+                    gif_graphic_controls: vec![GifGraphicControl {
+                        disposal_method: 1,
+                        user_input: false,
+                        delay_time: 50,
+                    }],
+                    gif_application_extensions: vec![GifApplicationExtension {
+                        identifier: *b"NETSCAPE",
+                        authentication_code: *b"2.0",
+                        data: vec![1, 0, 0],
+                    }],
+                    ..Info::default()
+                },
+                true,
+            ),
+            (with_pcal(too_few), false),
+            (with_pcal(empty_range), false),
+            // The name must not be empty, contain NUL or have leading or trailing spaces.
+            (with_pcal(named("")), false),
+            (with_pcal(named("Hö\0he")), false),
+            (with_pcal(named(" Höhe")), false),
+            (with_pcal(named("Höhe ")), false),
+            (with_scal(0.0), false),
+            (with_scal(-1.0), false),
+            (with_scal(f64::INFINITY), false),
+            (with_scal(f64::NAN), false),
+        ];
+        let extensions = |info: &Info| {
+            (
+                info.offset,
+                info.pixel_calibration.clone(),
+                info.physical_scale,
+                info.stereo_layout,
+                info.gif_graphic_controls.clone(),
+                info.gif_application_extensions.clone(),
+            )
+        };
+        for (idx, (info, valid)) in cases.into_iter().enumerate() {
+            let expected = extensions(&info);
+            match roundtrip_info(Info {
+                width: 1,
+                height: 1,
+                ..info
+            }) {
+                Ok(info) => {
+                    assert!(valid, "{}", idx);
+                    assert_eq!(extensions(&info), expected, "{}", idx);
+                }
+                Err(_) => assert!(!valid, "{}", idx),
+            }
+        }
+    }
+
+    #[test]
+    fn roundtrip_modification_time() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        assert_eq!(Time::new(1900, 2, 29, 0, 0, 0), None);
+        assert_eq!(Time::new(2000, 4, 31, 0, 0, 0), None);
+        assert_eq!(Time::new(2000, 1, 1, 24, 0, 0), None);
+
+        for (system_time, time) in [
+            (
+                UNIX_EPOCH + Duration::from_secs(951_782_400),
+                Time::new(2000, 2, 29, 0, 0, 0),
+            ),
+            (
+                UNIX_EPOCH + Duration::from_secs(1 << 31),
+                Time::new(2038, 1, 19, 3, 14, 8),
+            ),
+            (
+                UNIX_EPOCH - Duration::from_millis(1),
+                Time::new(1969, 12, 31, 23, 59, 59),
+            ),
+        ] {
+            let mut encoder = Encoder::new(Vec::new(), 1, 1);
+            encoder.set_modification_system_time(system_time);
+            assert_eq!(encoder.info.modification_time, time);
+            let info = roundtrip_info(encoder.info.clone()).unwrap();
+            assert_eq!(info.modification_time, time);
+        }
+    }
+
+    #[test]
+    fn roundtrip_unknown_chunks() {
+        let unknown = |type_: &[u8; 4], location| UnknownChunk {
+            type_: ChunkType(*type_),
+            data: type_.to_vec(),
+            location,
+        };
+        let mut info = Info::with_size(1, 1);
+        info.color_type = ColorType::Indexed;
+        info.palette = Some(Cow::Borrowed(&[0, 0, 0]));
+        info.unknown_chunks = vec![
+            unknown(b"prVa", ChunkLocation::BeforePlte),
+            unknown(b"prVb", ChunkLocation::BeforeIdat),
+            unknown(b"prVc", ChunkLocation::AfterIdat),
+            unknown(b"prVd", ChunkLocation::BeforePlte),
+        ];
+
+        let mut out = Vec::new();
+        {
+            let mut encoder = Encoder::with_info(&mut out, info.clone())
+                .unwrap()
+                .write_header()
+                .unwrap();
+            encoder.write_image_data(&[0]).unwrap();
+            encoder.finish().unwrap();
+        }
+
+        let mut decoder = Decoder::new(Cursor::new(&*out));
+        decoder.set_unknown_chunk_policy(crate::UnknownChunkPolicy::All);
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut buf).unwrap();
+        reader.finish().unwrap();
+
+        // The chunks are grouped by location, in their original order within each location.
+        let mut expected = info.unknown_chunks.clone();
+        expected.sort_by_key(|chunk| chunk.location as u8);
+        assert_eq!(reader.info().unknown_chunks, expected);
+    }
+
+    #[test]
+    fn roundtrip_premultiplied_alpha() {
+        use crate::Transformations;
+
+        fn decode(data: impl io::BufRead) -> (Vec<u8>, crate::OutputInfo) {
+            let mut decoder = Decoder::new(data);
+            decoder.set_transformations(Transformations::PREMULTIPLY_ALPHA);
+            let mut reader = decoder.read_info().unwrap();
+            let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+            let info = reader.next_frame(&mut buf).unwrap();
+            (buf, info)
+        }
+
+        for name in ["basn6a08", "basn6a16", "basn4a08"] {
+            let path = format!("tests/pngsuite/{}.png", name);
+            let (premultiplied, info) = decode(BufReader::new(File::open(path).unwrap()));
+            for (stream, interlaced) in [(false, false), (false, true), (true, false), (true, true)]
+            {
+                let mut out = Vec::new();
+                {
+                    let mut encoder = Encoder::new(&mut out, info.width, info.height);
+                    encoder.set_color(info.color_type);
+                    encoder.set_depth(info.bit_depth);
+                    encoder.set_interlaced(interlaced);
+                    encoder.set_premultiplied_alpha(true);
+                    let mut encoder = encoder.write_header().unwrap();
+                    if stream {
+                        let mut stream_writer = encoder.stream_writer().unwrap();
+                        stream_writer.write_all(&premultiplied).unwrap();
+                        stream_writer.finish().unwrap();
+                    } else {
+                        encoder.write_image_data(&premultiplied).unwrap();
+                    }
+                }
+                // Premultiplying the straight samples again is lossless.
+                assert_eq!(decode(Cursor::new(&out)).0, premultiplied, "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn unpremultiply() {
+        let mut data = [128, 64, 0, 128, 10, 20, 30, 0];
+        unpremultiply_alpha(ColorType::Rgba, BitDepth::Eight, &mut data);
+        assert_eq!(data, [255, 128, 0, 128, 0, 0, 0, 0]);
+
+        let mut data = [0x80, 0x00, 0x80, 0x00, 0x90, 0x00, 0x80, 0x00];
+        unpremultiply_alpha(ColorType::GrayscaleAlpha, BitDepth::Sixteen, &mut data);
+        assert_eq!(data, [0xff, 0xff, 0x80, 0x00, 0xff, 0xff, 0x80, 0x00]);
+    }
+
+    #[test]
+    fn roundtrip_interlaced_animation() -> Result<()> {
+        let width = 13;
+        let height = 9;
+        // The second frame is a sub-region, the third one a single pixel.
+        let frames: [(u32, u32, u32, u32); 3] = [(0, 0, 13, 9), (3, 2, 7, 5), (12, 8, 1, 1)];
+        let data =
+            |w: u32, h: u32| -> Vec<u8> { (0..w * h * 3).map(|i| (i * 7 + w) as u8).collect() };
+
+        for stream in [false, true] {
+            let mut out = Vec::new();
+            {
+                let mut encoder = Encoder::new(&mut out, width, height);
+                encoder.set_color(ColorType::Rgb);
+                encoder.set_depth(BitDepth::Eight);
+                encoder.set_interlaced(true);
+                encoder.set_animated(frames.len() as u32, 0)?;
+                let mut writer = encoder.write_header()?;
+
+                if stream {
+                    // The first frame covers the whole image, the following frames are
+                    // configured before their first write.
+                    let mut stream_writer = writer.stream_writer()?;
+                    for &(x, y, w, h) in &frames {
+                        stream_writer.reset_frame_position()?;
+                        stream_writer.set_frame_dimension(w, h)?;
+                        stream_writer.set_frame_position(x, y)?;
+                        stream_writer.write_all(&data(w, h))?;
+                    }
+                    stream_writer.finish()?;
+                } else {
+                    for &(x, y, w, h) in &frames {
+                        writer.reset_frame_position()?;
+                        writer.set_frame_dimension(w, h)?;
+                        writer.set_frame_position(x, y)?;
+                        writer.write_image_data(&data(w, h))?;
+                    }
+                }
+            }
+
+            let decoder = Decoder::new(Cursor::new(&*out));
+            let mut reader = decoder.read_info().unwrap();
+            assert!(reader.info().interlaced);
+            for &(_, _, w, h) in &frames {
+                let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+                let info = reader.next_frame(&mut buf).unwrap();
+                assert_eq!((info.width, info.height), (w, h));
+                assert_eq!(buf[..info.buffer_size()], data(w, h));
+            }
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn image_palette() -> Result<()> {
         for &bit_depth in &[1u8, 2, 4, 8] {
@@ -2389,6 +3593,69 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn stream_writer_animation() -> Result<()> {
+        let width = 4;
+        let height = 3;
+        // The second frame is a sub-region, so its rows are shorter than those of the image.
+        let frames: [(u32, u32, u32, u32); 3] = [(0, 0, 4, 3), (1, 1, 2, 2), (0, 0, 4, 3)];
+        let data =
+            |w: u32, h: u32| -> Vec<u8> { (0..w * h * 3).map(|i| (i * 7 + w) as u8).collect() };
+
+        let mut out = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut out, width, height);
+            encoder.set_color(ColorType::Rgb);
+            encoder.set_depth(BitDepth::Eight);
+            encoder.set_filter(Filter::Sub);
+            encoder.set_animated(frames.len() as u32, 0)?;
+            let mut writer = encoder.write_header()?;
+
+            let mut stream = writer.stream_writer()?;
+            for &(x, y, w, h) in &frames {
+                stream.reset_frame_position()?;
+                stream.set_frame_dimension(w, h)?;
+                stream.set_frame_position(x, y)?;
+                stream.write_all(&data(w, h))?;
+            }
+            stream.finish()?;
+        }
+
+        let mut reader = Decoder::new(Cursor::new(out)).read_info().unwrap();
+        for &(_, _, w, h) in &frames {
+            let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+            let info = reader.next_frame(&mut buf).unwrap();
+            assert_eq!((info.width, info.height), (w, h));
+            assert_eq!(buf[..info.buffer_size()], data(w, h));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn stream_writer_per_frame() -> Result<()> {
+        let mut output = Vec::new();
+        let mut encoder = Encoder::new(&mut output, 2, 1);
+        encoder.set_color(ColorType::Grayscale);
+        encoder.set_animated(2, 0)?;
+        let mut writer = encoder.write_header()?;
+        for frame in [[1, 2], [3, 4]] {
+            let mut stream = writer.stream_writer()?;
+            stream.write_all(&frame)?;
+            stream.finish()?;
+        }
+        writer.finish()?;
+
+        let mut decoder = Decoder::new(Cursor::new(output)).read_info().unwrap();
+        let mut buffer = [0u8; 2];
+        for frame in [[1, 2], [3, 4]] {
+            decoder.next_frame(&mut buffer).unwrap();
+            assert_eq!(buffer, frame);
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn stream_filtering() -> Result<()> {
         let output = vec![0u8; 1024];
@@ -2558,4 +3825,20 @@ mod tests {
             self.w.flush()
         }
     }
+
+    /// Encodes a blank image described by `info` and returns the info decoded from it,
+    /// including the chunks after the image data.
+    fn roundtrip_info(info: Info<'_>) -> Result<Info<'static>> {
+        let data = vec![0; (info.raw_row_length() - 1) * info.height as usize];
+        let mut out = Vec::new();
+        let mut writer = Encoder::with_info(&mut out, info)?.write_header()?;
+        writer.write_image_data(&data)?;
+        writer.finish()?;
+
+        let mut reader = Decoder::new(Cursor::new(out)).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut buf).unwrap();
+        reader.finish().unwrap();
+        Ok(reader.info().clone())
+    }
 }
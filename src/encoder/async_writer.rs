@@ -0,0 +1,598 @@
+//! Asynchronous encoding that does not depend on a particular executor.
+//!
+//! The [`AsyncWriter`] and [`AsyncStreamWriter`] encode with a [`Writer`] into a queue of chunks.
+//! Each chunk is written to an [`AsyncWrite`] sink as soon as it is complete, and encoding only
+//! continues once the sink has accepted it.
+
+use std::future::poll_fn;
+use std::io::{self, ErrorKind};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{ready, Context, Poll};
+
+use super::{Encoder, Result, StreamWriter, Writer};
+use crate::chunk::ChunkType;
+use crate::common::{BlendOp, DisposeOp};
+use crate::filter::Filter;
+use crate::text_metadata::EncodableTextChunk;
+
+/// An asynchronous sink of bytes, the counterpart of [`Write`](std::io::Write).
+///
+/// The methods have the same signatures as `AsyncWrite` of the `futures` crate, so writers of
+/// any async runtime can be adapted by forwarding to them.
+pub trait AsyncWrite {
+    /// Attempts to write bytes from `buf` into the sink, returning how many were written.
+    ///
+    /// If the sink can not accept data yet, returns `Poll::Pending` and arranges for the task of
+    /// `cx` to be woken up when it can.
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>>;
+
+    /// Attempts to flush the sink, ensuring that all written data reaches its destination.
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+}
+
+impl AsyncWrite for Vec<u8> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: AsyncWrite + Unpin + ?Sized> AsyncWrite for &mut T {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut **self.get_mut()).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut **self.get_mut()).poll_flush(cx)
+    }
+}
+
+/// The chunks that were encoded but not yet written to the sink.
+///
+/// The queue is shared between the [`Writer`] that appends the chunks, possibly from within the
+/// compressor of a [`StreamWriter`], and the async writer that drains them into the sink.
+#[derive(Clone, Default)]
+struct ChunkQueue(Arc<Mutex<QueuedChunks>>);
+
+#[derive(Default)]
+struct QueuedChunks {
+    data: Vec<u8>,
+    /// How many bytes of `data` have already been written to the sink.
+    written: usize,
+}
+
+impl ChunkQueue {
+    fn lock(&self) -> MutexGuard<'_, QueuedChunks> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Writes the queued chunks to the sink.
+    ///
+    /// Progress is recorded in the queue, so a cancelled call does not write data twice.
+    async fn drain<W: AsyncWrite + Unpin>(&self, sink: &mut W) -> Result<()> {
+        poll_fn(|cx| {
+            let mut queue = self.lock();
+            let QueuedChunks { data, written } = &mut *queue;
+            while *written < data.len() {
+                let len = ready!(Pin::new(&mut *sink).poll_write(cx, &data[*written..]))?;
+                if len == 0 {
+                    return Poll::Ready(Err(io::Error::from(ErrorKind::WriteZero)));
+                }
+                *written += len;
+            }
+
+            data.clear();
+            *written = 0;
+            Poll::Ready(Ok(()))
+        })
+        .await?;
+        Ok(())
+    }
+}
+
+impl io::Write for ChunkQueue {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// PNG writer for an [`AsyncWrite`] sink, the asynchronous counterpart of [`Writer`].
+///
+/// The image is configured with an [`Encoder`] that writes into a `Vec<u8>`, the data of the
+/// vector is written to the sink before the header. Each method completes once all chunks it
+/// encoded have been written to the sink. The image data is encoded one row at a time, and every
+/// `IDAT` or `fdAT` chunk is written to the sink as soon as it is full, before the following rows
+/// are encoded. Hence apart from the data held back by the compressor, at most one chunk is
+/// buffered. Interlaced frames are the exception, they are buffered completely as their passes can
+/// only be encoded once all rows are known. The writer does not spawn tasks or depend on an async
+/// runtime, the returned futures can be awaited on any executor.
+///
+/// The image is only complete after [`AsyncWriter::finish`], dropping the writer does not write
+/// the `IEND` chunk. Dropping a future of [`AsyncWriter::write_image_data`] before it completes
+/// leaves the frame incomplete.
+///
+/// ```
+/// use png::{AsyncWrite, AsyncWriter, ColorType, Encoder, EncodingError};
+///
+/// async fn encode(sink: impl AsyncWrite + Unpin, data: &[u8]) -> Result<(), EncodingError> {
+///     let mut encoder = Encoder::new(Vec::new(), 2, 1);
+///     encoder.set_color(ColorType::Rgba);
+///     let mut writer = AsyncWriter::write_header(encoder, sink).await?;
+///     writer.write_image_data(data).await?;
+///     writer.finish().await
+/// }
+/// ```
+pub struct AsyncWriter<W: AsyncWrite + Unpin> {
+    writer: Writer<ChunkQueue>,
+    queue: ChunkQueue,
+    sink: W,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWriter<W> {
+    /// Start encoding by writing the header data, see [`Encoder::write_header`].
+    ///
+    /// Data in the vector of the `encoder` is written to the sink before the header.
+    pub async fn write_header(encoder: Encoder<'_, Vec<u8>>, sink: W) -> Result<Self> {
+        let Encoder { w, info, options } = encoder;
+        let queue = ChunkQueue::default();
+        queue.lock().data = w;
+
+        let encoder = Encoder {
+            w: queue.clone(),
+            info,
+            options,
+        };
+        let mut writer = AsyncWriter {
+            writer: encoder.write_header()?,
+            queue,
+            sink,
+        };
+        writer.queue.drain(&mut writer.sink).await?;
+        Ok(writer)
+    }
+
+    /// Writes the next image data, see [`Writer::write_image_data`].
+    ///
+    /// The frame is encoded like with a [`StreamWriter`], the sink is given each chunk before the
+    /// next row is encoded.
+    pub async fn write_image_data(&mut self, data: &[u8]) -> Result<()> {
+        let (width, _) = self.writer.validate_image_data(data.len())?;
+        let row_len = self.writer.info.raw_row_length_from_width(width as u32) - 1;
+
+        let mut stream = self.writer.stream_writer()?;
+        for row in data.chunks(row_len) {
+            io::Write::write_all(&mut stream, row)?;
+            self.queue.drain(&mut self.sink).await?;
+        }
+        stream.finish()?;
+        self.queue.drain(&mut self.sink).await
+    }
+
+    /// Writes a raw chunk, see [`Writer::write_chunk`].
+    pub async fn write_chunk(&mut self, name: ChunkType, data: &[u8]) -> Result<()> {
+        self.writer.write_chunk(name, data)?;
+        self.queue.drain(&mut self.sink).await
+    }
+
+    /// Writes a text chunk, see [`Writer::write_text_chunk`].
+    pub async fn write_text_chunk<T: EncodableTextChunk>(&mut self, text_chunk: &T) -> Result<()> {
+        self.writer.write_text_chunk(text_chunk)?;
+        self.queue.drain(&mut self.sink).await
+    }
+
+    /// Set the used filter type, see [`Writer::set_filter`].
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.writer.set_filter(filter);
+    }
+
+    /// Set the fraction of a second that the following frames will be displayed, see
+    /// [`Writer::set_frame_delay`].
+    pub fn set_frame_delay(&mut self, numerator: u16, denominator: u16) -> Result<()> {
+        self.writer.set_frame_delay(numerator, denominator)
+    }
+
+    /// Set the dimension of the following frames, see [`Writer::set_frame_dimension`].
+    pub fn set_frame_dimension(&mut self, width: u32, height: u32) -> Result<()> {
+        self.writer.set_frame_dimension(width, height)
+    }
+
+    /// Set the position of the following frames, see [`Writer::set_frame_position`].
+    pub fn set_frame_position(&mut self, x: u32, y: u32) -> Result<()> {
+        self.writer.set_frame_position(x, y)
+    }
+
+    /// Set the frame dimension to occupy all the image, see [`Writer::reset_frame_dimension`].
+    pub fn reset_frame_dimension(&mut self) -> Result<()> {
+        self.writer.reset_frame_dimension()
+    }
+
+    /// Set the frame position to (0, 0), see [`Writer::reset_frame_position`].
+    pub fn reset_frame_position(&mut self) -> Result<()> {
+        self.writer.reset_frame_position()
+    }
+
+    /// Set the blend operation for the following frames, see [`Writer::set_blend_op`].
+    pub fn set_blend_op(&mut self, op: BlendOp) -> Result<()> {
+        self.writer.set_blend_op(op)
+    }
+
+    /// Set the dispose operation for the following frames, see [`Writer::set_dispose_op`].
+    pub fn set_dispose_op(&mut self, op: DisposeOp) -> Result<()> {
+        self.writer.set_dispose_op(op)
+    }
+
+    /// Turn this into a stream writer for image data, see [`Writer::into_stream_writer`].
+    pub fn into_stream_writer(self) -> Result<AsyncStreamWriter<W>> {
+        self.into_stream_writer_with_size(super::DEFAULT_BUFFER_LENGTH)
+    }
+
+    /// Turn this into a stream writer with custom buffer size, see
+    /// [`Writer::into_stream_writer_with_size`].
+    pub fn into_stream_writer_with_size(self, size: usize) -> Result<AsyncStreamWriter<W>> {
+        let AsyncWriter {
+            writer,
+            queue,
+            sink,
+        } = self;
+        Ok(AsyncStreamWriter {
+            stream: writer.into_stream_writer_with_size(size)?,
+            queue,
+            sink,
+        })
+    }
+
+    /// Writes the `IEND` chunk and flushes the sink, see [`Writer::finish`].
+    pub async fn finish(mut self) -> Result<()> {
+        self.writer.validate_sequence_done()?;
+        self.writer.write_iend()?;
+        self.queue.drain(&mut self.sink).await?;
+        poll_fn(|cx| Pin::new(&mut self.sink).poll_flush(cx)).await?;
+        Ok(())
+    }
+}
+
+/// Streaming PNG writer for an [`AsyncWrite`] sink, the asynchronous counterpart of
+/// [`StreamWriter`].
+///
+/// Created with [`AsyncWriter::into_stream_writer`]. The image data of all remaining frames is
+/// written with [`AsyncStreamWriter::write_all`], in pieces of any size. Each `IDAT` or `fdAT`
+/// chunk is written to the sink as soon as it is full, before the next row is encoded. Like for the
+/// [`AsyncWriter`], interlaced frames are only encoded once they are complete.
+///
+/// The image is only complete after [`AsyncStreamWriter::finish`], dropping the writer does not
+/// write the `IEND` chunk.
+pub struct AsyncStreamWriter<W: AsyncWrite + Unpin> {
+    stream: StreamWriter<'static, ChunkQueue>,
+    queue: ChunkQueue,
+    sink: W,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncStreamWriter<W> {
+    /// Writes all of `data` as the image data of the current and following frames.
+    ///
+    /// The data is encoded one row at a time, and the chunks completed by a row are written to the
+    /// sink before the next row is encoded.
+    pub async fn write_all(&mut self, mut data: &[u8]) -> Result<()> {
+        while !data.is_empty() {
+            let len = io::Write::write(&mut self.stream, data)?;
+            if len == 0 {
+                return Err(io::Error::from(ErrorKind::WriteZero).into());
+            }
+            data = &data[len..];
+            self.queue.drain(&mut self.sink).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Set the used filter type, see [`StreamWriter::set_filter`].
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.stream.set_filter(filter);
+    }
+
+    /// Set the fraction of a second that the following frames will be displayed, see
+    /// [`StreamWriter::set_frame_delay`].
+    pub fn set_frame_delay(&mut self, numerator: u16, denominator: u16) -> Result<()> {
+        self.stream.set_frame_delay(numerator, denominator)
+    }
+
+    /// Set the dimension of the following frames, see [`StreamWriter::set_frame_dimension`].
+    pub fn set_frame_dimension(&mut self, width: u32, height: u32) -> Result<()> {
+        self.stream.set_frame_dimension(width, height)
+    }
+
+    /// Set the position of the following frames, see [`StreamWriter::set_frame_position`].
+    pub fn set_frame_position(&mut self, x: u32, y: u32) -> Result<()> {
+        self.stream.set_frame_position(x, y)
+    }
+
+    /// Set the frame dimension to occupy all the image, see
+    /// [`StreamWriter::reset_frame_dimension`].
+    pub fn reset_frame_dimension(&mut self) -> Result<()> {
+        self.stream.reset_frame_dimension()
+    }
+
+    /// Set the frame position to (0, 0), see [`StreamWriter::reset_frame_position`].
+    pub fn reset_frame_position(&mut self) -> Result<()> {
+        self.stream.reset_frame_position()
+    }
+
+    /// Set the blend operation for the following frames, see [`StreamWriter::set_blend_op`].
+    pub fn set_blend_op(&mut self, op: BlendOp) -> Result<()> {
+        self.stream.set_blend_op(op)
+    }
+
+    /// Set the dispose operation for the following frames, see [`StreamWriter::set_dispose_op`].
+    pub fn set_dispose_op(&mut self, op: DisposeOp) -> Result<()> {
+        self.stream.set_dispose_op(op)
+    }
+
+    /// Completes the last frame, writes the `IEND` chunk and flushes the sink, see
+    /// [`StreamWriter::finish`].
+    pub async fn finish(self) -> Result<()> {
+        let AsyncStreamWriter {
+            stream,
+            queue,
+            mut sink,
+        } = self;
+        // The owned `Writer` is dropped with the stream, which queues the `IEND` chunk.
+        stream.finish()?;
+        queue.drain(&mut sink).await?;
+        poll_fn(|cx| Pin::new(&mut sink).poll_flush(cx)).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::DEFAULT_BUFFER_LENGTH;
+    use crate::test_utils::block_on;
+    use crate::{BitDepth, ColorType, Decoder};
+
+    /// Accepts at most `max_len` bytes per write, and is pending before each write.
+    struct Throttled {
+        data: Vec<u8>,
+        max_len: usize,
+        ready: bool,
+        pending_polls: usize,
+        flushed: bool,
+    }
+
+    impl Throttled {
+        fn new(max_len: usize) -> Self {
+            Throttled {
+                data: Vec::new(),
+                max_len,
+                ready: false,
+                pending_polls: 0,
+                flushed: false,
+            }
+        }
+    }
+
+    impl AsyncWrite for Throttled {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let this = self.get_mut();
+            if !this.ready {
+                this.ready = true;
+                this.pending_polls += 1;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+
+            this.ready = false;
+            let len = buf.len().min(this.max_len);
+            this.data.extend_from_slice(&buf[..len]);
+            Poll::Ready(Ok(len))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.get_mut().flushed = true;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn configure<W: std::io::Write>(encoder: &mut Encoder<'_, W>) {
+        encoder.set_color(ColorType::Rgb);
+        encoder.set_depth(BitDepth::Eight);
+        encoder.set_animated(2, 0).unwrap();
+    }
+
+    fn frames() -> [Vec<u8>; 2] {
+        [
+            vec![0x40; 3 * 8 * 4],
+            (0..3 * 8 * 2).map(|i| i as u8).collect(),
+        ]
+    }
+
+    fn assert_frames(data: &[u8], frames: &[Vec<u8>]) {
+        let mut reader = Decoder::new(data).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+        for frame in frames {
+            let info = reader.next_frame(&mut buf).unwrap();
+            assert_eq!(&buf[..info.buffer_size()], frame.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_async_writer_matches_writer() {
+        let (width, height) = (8, 4);
+        let frames = frames();
+
+        let mut expected = Vec::new();
+        let mut encoder = Encoder::new(&mut expected, width, height);
+        configure(&mut encoder);
+        let mut writer = encoder.write_header().unwrap();
+        for (i, frame) in frames.iter().enumerate() {
+            if i == 1 {
+                writer.set_frame_dimension(8, 2).unwrap();
+            }
+            let mut stream = writer.stream_writer().unwrap();
+            std::io::Write::write_all(&mut stream, frame).unwrap();
+            stream.finish().unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut sink = Throttled::new(7);
+        block_on(async {
+            let mut encoder = Encoder::new(Vec::new(), width, height);
+            configure(&mut encoder);
+            let mut writer = AsyncWriter::write_header(encoder, &mut sink).await?;
+            writer.write_image_data(&frames[0]).await?;
+            writer.set_frame_dimension(8, 2)?;
+            writer.write_image_data(&frames[1]).await?;
+            writer.finish().await
+        })
+        .unwrap();
+
+        assert_eq!(sink.data, expected);
+        assert!(sink.flushed);
+        assert!(sink.pending_polls >= expected.len().div_ceil(7));
+        assert_frames(&sink.data, &frames);
+    }
+
+    #[test]
+    fn test_async_stream_writer_matches_stream_writer() {
+        let (width, height) = (8, 4);
+        let frames = frames();
+
+        // A stream writer that owns its `Writer` needs an owned output.
+        let output = ChunkQueue::default();
+        let mut encoder = Encoder::new(output.clone(), width, height);
+        configure(&mut encoder);
+        let mut stream = encoder
+            .write_header()
+            .unwrap()
+            .into_stream_writer()
+            .unwrap();
+        std::io::Write::write_all(&mut stream, &frames[0]).unwrap();
+        stream.set_frame_dimension(8, 2).unwrap();
+        std::io::Write::write_all(&mut stream, &frames[1]).unwrap();
+        stream.finish().unwrap();
+        let expected = std::mem::take(&mut output.lock().data);
+
+        let mut sink = Throttled::new(7);
+        block_on(async {
+            let mut encoder = Encoder::new(Vec::new(), width, height);
+            configure(&mut encoder);
+            let writer = AsyncWriter::write_header(encoder, &mut sink).await?;
+            let mut stream = writer.into_stream_writer()?;
+            stream.write_all(&frames[0]).await?;
+            stream.set_frame_dimension(8, 2)?;
+            stream.write_all(&frames[1]).await?;
+            stream.finish().await
+        })
+        .unwrap();
+
+        assert_eq!(sink.data, expected);
+        assert!(sink.flushed);
+        assert_frames(&sink.data, &frames);
+    }
+
+    #[test]
+    fn test_async_writer_writes_each_chunk() {
+        /// Records the length of the longest write.
+        #[derive(Default)]
+        struct Longest {
+            data: Vec<u8>,
+            longest: usize,
+        }
+
+        impl AsyncWrite for Longest {
+            fn poll_write(
+                self: Pin<&mut Self>,
+                _: &mut Context<'_>,
+                buf: &[u8],
+            ) -> Poll<io::Result<usize>> {
+                let this = self.get_mut();
+                this.longest = this.longest.max(buf.len());
+                this.data.extend_from_slice(buf);
+                Poll::Ready(Ok(buf.len()))
+            }
+
+            fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        let (width, height) = (64, 64);
+        let mut state = 1u32;
+        let frame: Vec<u8> = (0..4 * width * height)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+
+        let mut sink = Longest::default();
+        block_on(async {
+            let mut encoder = Encoder::new(Vec::new(), width as u32, height as u32);
+            encoder.set_color(ColorType::Rgba);
+            encoder.set_compression(crate::Compression::Fastest);
+            let mut writer = AsyncWriter::write_header(encoder, &mut sink).await?;
+            writer.write_image_data(&frame).await?;
+            writer.finish().await
+        })
+        .unwrap();
+
+        // The frame does not compress, so it takes several chunks. The fast compressor outputs
+        // them while the rows are written, so they reach the sink one by one.
+        assert!(sink.data.len() > 3 * DEFAULT_BUFFER_LENGTH);
+        assert!(sink.longest <= DEFAULT_BUFFER_LENGTH + 12);
+        assert_frames(&sink.data, &[frame]);
+    }
+
+    #[test]
+    fn test_async_writer_write_zero() {
+        struct Full;
+        impl AsyncWrite for Full {
+            fn poll_write(
+                self: Pin<&mut Self>,
+                _: &mut Context<'_>,
+                _: &[u8],
+            ) -> Poll<io::Result<usize>> {
+                Poll::Ready(Ok(0))
+            }
+
+            fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        let encoder = Encoder::new(Vec::new(), 1, 1);
+        let result = block_on(AsyncWriter::write_header(encoder, Full));
+        assert!(matches!(
+            result,
+            Err(crate::EncodingError::IoError(e)) if e.kind() == ErrorKind::WriteZero
+        ));
+    }
+}
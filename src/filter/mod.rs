@@ -586,8 +586,9 @@ fn entropy(buf: &[u8]) -> u64 {
     }
 
     // Consolidate the counts.
-    for i in 0..256 {
-        counts[0][i] += counts[1][i] + counts[2][i] + counts[3][i];
+    let [first, second, third, fourth] = &mut counts;
+    for (i, count) in first.iter_mut().enumerate() {
+        *count += second[i] + third[i] + fourth[i];
     }
 
     // Compute the entropy.
@@ -727,4 +728,15 @@ mod test {
 
         assert_eq!(sum, crate::filter::sum_buffer(&buf));
     }
+
+    #[test]
+    // The counts are scattered over four arrays, so they must all be consolidated for the byte
+    // values that only occur in the later arrays to count.
+    fn entropy_test() {
+        assert_eq!(entropy(&[0; 64]), 0);
+
+        // 8 byte values that occur 4 times each: 32 * log2(32) - 8 * (4 * log2(4)).
+        let buf: Vec<u8> = (1..=8).cycle().take(32).collect();
+        assert_eq!(entropy(&buf), 160 - 8 * 8);
+    }
 }
@@ -27,6 +27,9 @@
 //! let in_animation = reader.info().frame_control.is_some();
 //! ```
 //!
+//! [`AsyncDecoder`] and [`AsyncReader`] provide the same interface with `async` methods for input
+//! implementing [`AsyncBufRead`]. They work with any executor and do not depend on a runtime.
+//!
 //! ## Encoder
 //! ### Using the encoder
 //!
@@ -58,6 +61,10 @@
 //! writer.write_image_data(&data).unwrap(); // Save
 //! ```
 //!
+//! To encode into a non-blocking sink implementing [`AsyncWrite`], configure the `Encoder` with a
+//! `Vec<u8>` as buffer and continue with [`AsyncWriter::write_header`]. Each chunk is written to
+//! the sink as soon as it is complete.
+//!
 
 #![forbid(unsafe_code)]
 // Silence certain clippy warnings until our MSRV is higher.
@@ -84,10 +91,19 @@ pub use crate::adam7::{
 
 pub use crate::adam7::{Adam7Info, Adam7Variant};
 pub use crate::common::*;
-pub use crate::decoder::stream::{DecodeOptions, Decoded, DecodingError, StreamingDecoder};
-pub use crate::decoder::{Decoder, InterlaceInfo, InterlacedRow, Limits, OutputInfo, Reader};
+pub use crate::decoder::stream::{
+    ChunkHandling, DecodeOptions, Decoded, DecodingError, StreamingDecoder, Strictness,
+    UnknownChunkPolicy,
+};
+pub use crate::decoder::{
+    AsyncBufRead, AsyncDecoder, AsyncReader, CompositedFrameInfo, Decoder, FrameProgress,
+    GrayCoefficients, InterlaceHandling, InterlaceInfo, InterlacedRow, Limits, OutputInfo, Reader,
+    Region, Scale,
+};
 pub use crate::decoder::{UnfilterBuf, UnfilterRegion};
-pub use crate::encoder::{Encoder, EncodingError, StreamWriter, Writer};
+pub use crate::encoder::{
+    AsyncStreamWriter, AsyncWrite, AsyncWriter, Encoder, EncodingError, StreamWriter, Writer,
+};
 pub use crate::filter::Filter;
 
 #[cfg(test)]
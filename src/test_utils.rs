@@ -115,3 +115,26 @@ pub fn write_rgba8_idats(w: &mut impl Write, size: u32, idat_bytes: usize) {
 pub fn write_iend(w: &mut impl Write) {
     write_chunk(w, b"IEND", &[]);
 }
+
+/// Polls `future` until it completes, with a waker that does nothing.
+///
+/// This is sufficient for futures of this crate, which only wait for their reader or writer.
+#[allow(dead_code)] // Not used by `benches/decoder.rs`.
+pub fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct NoopWaker;
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    let waker = Waker::from(Arc::new(NoopWaker));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}